@@ -0,0 +1,81 @@
+// Analog movement input. Stick-style inputs report how far they are pushed,
+// and a response curve reshapes that before it becomes speed so that small
+// deflections give fine control while full deflection is still full speed.
+
+use std::str::FromStr;
+
+use crate::RaycastError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    Squared,
+    // Deflection raised to this power; above 1 softens small movements
+    Exponent(f64),
+}
+
+impl ResponseCurve {
+    // Map a deflection from 0 to 1 onto a speed factor from 0 to 1
+    pub fn apply(self, magnitude: f64) -> f64 {
+        let magnitude = magnitude.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => magnitude,
+            ResponseCurve::Squared => magnitude * magnitude,
+            ResponseCurve::Exponent(exponent) => magnitude.powf(exponent),
+        }
+    }
+}
+
+impl FromStr for ResponseCurve {
+    type Err = RaycastError;
+
+    // "linear", "squared", or an exponent such as "1.5"
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "linear" => Ok(ResponseCurve::Linear),
+            "squared" => Ok(ResponseCurve::Squared),
+            _ => value
+                .parse()
+                .ok()
+                .filter(|&exponent: &f64| exponent > 0.0 && exponent.is_finite())
+                .map(ResponseCurve::Exponent)
+                .ok_or_else(|| RaycastError::Config(format!("unknown response curve: {}", value))),
+        }
+    }
+}
+
+// Movement for one frame as fractions of full speed: forward (negative is
+// backward), strafe right (negative is left) and turn right (negative is left)
+#[derive(Clone, Copy, Default)]
+pub struct Movement {
+    pub forward: f64,
+    pub strafe: f64,
+    pub turn: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_keep_the_ends_and_shape_the_middle() {
+        for (curve, midpoint) in [
+            (ResponseCurve::Linear, 0.5),
+            (ResponseCurve::Squared, 0.25),
+            (ResponseCurve::Exponent(3.0), 0.125),
+        ] {
+            assert_eq!(curve.apply(0.0), 0.0, "{:?}", curve);
+            assert_eq!(curve.apply(1.0), 1.0, "{:?}", curve);
+            assert!((curve.apply(0.5) - midpoint).abs() < 1e-12, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn curves_parse_by_name_or_exponent() {
+        assert_eq!("linear".parse::<ResponseCurve>().ok(), Some(ResponseCurve::Linear));
+        assert_eq!("squared".parse::<ResponseCurve>().ok(), Some(ResponseCurve::Squared));
+        assert_eq!("1.5".parse::<ResponseCurve>().ok(), Some(ResponseCurve::Exponent(1.5)));
+        assert!("-1".parse::<ResponseCurve>().is_err());
+        assert!("cubic".parse::<ResponseCurve>().is_err());
+    }
+}
@@ -0,0 +1,68 @@
+// Proximity cues using the terminal bell. When a sprite is nearby but outside
+// the field of view, the bell rings at a rate that increases as it gets closer.
+// Sprites behind the player get a double ring so the pattern hints at direction.
+
+use std::time::{Duration, Instant};
+
+use crate::raycaster::{Player, Sprite, FOV};
+
+const CUE_RANGE: f64 = 8.0; // Sprites farther away than this are silent
+const MIN_INTERVAL: Duration = Duration::from_millis(250); // Interval at point-blank range
+const MAX_INTERVAL: Duration = Duration::from_millis(1500); // Interval at the edge of CUE_RANGE
+const ECHO_DELAY: Duration = Duration::from_millis(150); // Gap between the two rings of a double ring
+const BEHIND_ANGLE: f64 = 2.0 * std::f64::consts::FRAC_PI_3; // Beyond 120° off-axis counts as behind
+
+pub struct AudioCues {
+    last_ring: Option<Instant>,
+    echo_at: Option<Instant>,
+}
+
+impl Default for AudioCues {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioCues {
+    pub fn new() -> Self {
+        AudioCues {
+            last_ring: None,
+            echo_at: None,
+        }
+    }
+
+    // Decide whether to ring the bell this frame
+    pub fn update(&mut self, player: &Player, sprites: &[Sprite], now: Instant) -> bool {
+        if self.echo_at.is_some_and(|at| now >= at) {
+            self.echo_at = None;
+            return true;
+        }
+        
+        let half_fov = FOV.atan();
+        let nearest = sprites
+            .iter()
+            .filter_map(|sprite| {
+                let (ahead, right) = player.view_offset(sprite.x, sprite.y);
+                let distance = ahead.hypot(right);
+                let off_axis = right.atan2(ahead).abs();
+                (distance <= CUE_RANGE && off_axis > half_fov).then_some((distance, off_axis))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        
+        let Some((distance, off_axis)) = nearest else {
+            self.last_ring = None;
+            return false;
+        };
+        
+        let interval = MIN_INTERVAL + (MAX_INTERVAL - MIN_INTERVAL).mul_f64(distance / CUE_RANGE);
+        if self.last_ring.is_some_and(|last| now < last + interval) {
+            return false;
+        }
+        
+        self.last_ring = Some(now);
+        if off_axis > BEHIND_ANGLE {
+            self.echo_at = Some(now + ECHO_DELAY);
+        }
+        true
+    }
+}
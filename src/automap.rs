@@ -0,0 +1,111 @@
+// Full-screen automap: the whole map from above in place of the 3D view,
+// with an arrow for the player. It can be scrolled around and zoomed in,
+// and starts out fitting the whole map on screen.
+
+use crossterm::event::KeyCode;
+
+use crate::editor::{cell_color, BACKGROUND_COLOR, PLAYER_COLOR};
+use crate::raycaster::Raycaster;
+
+const MAX_ZOOM: f64 = 16.0; // Zoom relative to the whole map fitting on screen
+const ZOOM_STEP: f64 = 1.5; // Zoom factor per key press
+const SCROLL_STEP: f64 = 0.125; // Fraction of the visible span scrolled per key press
+const ARROW_LENGTH: f64 = 0.8; // Cells from the arrow's tail to its point
+const ARROW_MIN_PIXELS: f64 = 4.0; // The arrow is never drawn smaller than this
+const ARROW_HEAD_ANGLE: f64 = 2.5; // Radians between the shaft and each side of the head
+
+pub struct Automap {
+    pub center: (f64, f64), // World point at the middle of the view
+    pub zoom: f64, // 1 fits the whole map on screen
+}
+
+impl Automap {
+    // Start with the whole map in view
+    pub fn new(raycaster: &Raycaster) -> Self {
+        let map = raycaster.map();
+        Automap {
+            center: (map.width() as f64 / 2.0, map.height() as f64 / 2.0),
+            zoom: 1.0,
+        }
+    }
+
+    // Handle a key press. Returns false for keys the automap doesn't use.
+    pub fn handle_key(&mut self, code: KeyCode, raycaster: &Raycaster) -> bool {
+        let map = raycaster.map();
+        let step = map.width().max(map.height()) as f64 / self.zoom * SCROLL_STEP;
+        let (x, y) = self.center;
+        match code {
+            KeyCode::Left => self.center.0 = x - step,
+            KeyCode::Right => self.center.0 = x + step,
+            KeyCode::Up => self.center.1 = y - step,
+            KeyCode::Down => self.center.1 = y + step,
+            KeyCode::Char('+' | '=') => self.zoom = (self.zoom * ZOOM_STEP).min(MAX_ZOOM),
+            KeyCode::Char('-') => self.zoom = (self.zoom / ZOOM_STEP).max(1.0),
+            KeyCode::Char('c') => self.center = (raycaster.player.x, raycaster.player.y),
+            KeyCode::Char('0') => *self = Automap::new(raycaster),
+            _ => return false,
+        }
+        // Keep some of the map in view
+        self.center.0 = self.center.0.clamp(0.0, map.width() as f64);
+        self.center.1 = self.center.1.clamp(0.0, map.height() as f64);
+        true
+    }
+
+    // Top-down view of the map at the given pixel size
+    pub fn frame(&self, raycaster: &Raycaster, width: usize, height: usize) -> Vec<Vec<u8>> {
+        let map = raycaster.map();
+        let fit = (width as f64 / map.width() as f64).min(height as f64 / map.height() as f64);
+        let scale = fit * self.zoom; // Pixels per cell
+        let origin = (
+            self.center.0 - width as f64 / 2.0 / scale,
+            self.center.1 - height as f64 / 2.0 / scale,
+        );
+
+        let mut frame_buffer: Vec<Vec<u8>> = (0..height)
+            .map(|y| {
+                let world_y = origin.1 + (y as f64 + 0.5) / scale;
+                (0..width)
+                    .map(|x| {
+                        let world_x = origin.0 + (x as f64 + 0.5) / scale;
+                        if world_x < 0.0 || world_y < 0.0 {
+                            return BACKGROUND_COLOR;
+                        }
+                        map.get(world_x as usize, world_y as usize).map_or(BACKGROUND_COLOR, cell_color)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Player arrow, centered on the player and pointing the way they face
+        let to_pixel = |(x, y): (f64, f64)| ((x - origin.0) * scale, (y - origin.1) * scale);
+        let player = &raycaster.player;
+        let length = (ARROW_LENGTH * scale).max(ARROW_MIN_PIXELS);
+        let (sin, cos) = player.angle.sin_cos();
+        let (px, py) = to_pixel((player.x, player.y));
+        let tip = (px + cos * length / 2.0, py + sin * length / 2.0);
+        let tail = (px - cos * length / 2.0, py - sin * length / 2.0);
+        draw_line(&mut frame_buffer, tail, tip, PLAYER_COLOR);
+        for side in [-1.0, 1.0] {
+            let (sin, cos) = (player.angle + side * ARROW_HEAD_ANGLE).sin_cos();
+            let barb = (tip.0 + cos * length / 3.0, tip.1 + sin * length / 3.0);
+            draw_line(&mut frame_buffer, tip, barb, PLAYER_COLOR);
+        }
+
+        frame_buffer
+    }
+}
+
+// Draw a line between two pixel positions, skipping the parts off screen
+fn draw_line(frame_buffer: &mut [Vec<u8>], from: (f64, f64), to: (f64, f64), color: u8) {
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        let (x, y) = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+        if x < 0.0 || y < 0.0 {
+            continue;
+        }
+        if let Some(pixel) = frame_buffer.get_mut(y as usize).and_then(|row| row.get_mut(x as usize)) {
+            *pixel = color;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+// Render benchmark that sweeps several virtual terminal sizes, showing how
+// frame time scales with screen area. Frames are rendered and encoded in
+// memory, so terminal throughput does not affect the numbers.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::keybindings::Action;
+use crate::raycaster::Raycaster;
+use crate::render::RenderMode;
+
+// Terminal sizes in character cells used when none are given
+pub const DEFAULT_SWEEP_SIZES: &[(usize, usize)] = &[(80, 24), (120, 40), (200, 60), (400, 100)];
+
+pub struct BenchResult {
+    pub width: usize,
+    pub height: usize,
+    pub frames: u32,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn fps(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64()
+    }
+
+    // Average render time per pixel (two pixels per character cell)
+    pub fn nanos_per_pixel(&self) -> f64 {
+        let pixels = (self.width * self.height * 2) as f64;
+        self.elapsed.as_nanos() as f64 / self.frames as f64 / pixels
+    }
+}
+
+// Render each size for `duration` from the default spawn, turning slowly so
+// consecutive frames differ
+pub fn sweep(sizes: &[(usize, usize)], duration: Duration) -> Vec<BenchResult> {
+    sizes
+        .iter()
+        .map(|&(width, height)| {
+            let mut raycaster = Raycaster::new();
+            let start = Instant::now();
+            let mut frames = 0;
+            
+            while frames == 0 || start.elapsed() < duration {
+                raycaster.update(&[Action::TurnRight]);
+                let frame_buffer = raycaster.render_frame(width, height * 2);
+                black_box(RenderMode::HalfBlock.encode(&frame_buffer));
+                frames += 1;
+            }
+            
+            BenchResult {
+                width,
+                height,
+                frames,
+                elapsed: start.elapsed(),
+            }
+        })
+        .collect()
+}
+
+pub fn format_table(results: &[BenchResult]) -> String {
+    let mut table = format!("{:<10}{:>8}{:>10}{:>12}\n", "Size", "Frames", "FPS", "ns/pixel");
+    for result in results {
+        table.push_str(&format!(
+            "{:<10}{:>8}{:>10.1}{:>12.2}\n",
+            format!("{}x{}", result.width, result.height),
+            result.frames,
+            result.fps(),
+            result.nanos_per_pixel()
+        ));
+    }
+    table
+}
@@ -0,0 +1,142 @@
+// Campaigns: an ordered list of levels played one after another, read from a
+// manifest in the same TOML subset as map files:
+//
+//     name = "The Cellars"
+//
+//     [[level]]
+//     map = "cellar.txt"
+//     name = "Wine Cellar"
+//     spawn = [2.5, 2.5, 90]  # x, y and an optional facing in degrees
+//
+//     [[level]]
+//     map = "GAMEMAPS.WL1"
+//     wolf_level = 2
+//
+// Map paths are relative to the manifest. A level's name and spawn point
+// replace the map's own.
+
+use std::path::{Path, PathBuf};
+
+use crate::mapfile::{parse_string, strip_comment, MapFile};
+use crate::wolf3d;
+use crate::RaycastError;
+
+pub struct Campaign {
+    pub name: String,
+    pub levels: Vec<CampaignLevel>,
+}
+
+pub struct CampaignLevel {
+    pub map: PathBuf,
+    pub name: Option<String>,
+    pub spawn: Option<(f64, f64, f64)>, // (x, y, facing in radians)
+    pub wolf_level: usize, // Level to import if the map is a GAMEMAPS file, 0-based
+}
+
+impl Campaign {
+    // Read a campaign manifest. Without a name the campaign is named after
+    // its file; it must list at least one level.
+    pub fn load(path: &Path) -> Result<Self, RaycastError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| RaycastError::Config(format!("campaign {}: {}", path.display(), err)))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut name = None;
+        let mut levels: Vec<CampaignLevel> = Vec::new();
+
+        for (index, text) in text.lines().enumerate() {
+            let error = |reason: String| {
+                RaycastError::Config(format!("campaign {} line {}: {}", path.display(), index + 1, reason))
+            };
+            let content = strip_comment(text).trim();
+            if content.is_empty() {
+                continue;
+            }
+            if content.starts_with('[') {
+                if content != "[[level]]" {
+                    return Err(error(format!("unknown table {}", content)));
+                }
+                levels.push(CampaignLevel {
+                    map: PathBuf::new(),
+                    name: None,
+                    spawn: None,
+                    wolf_level: 0,
+                });
+                continue;
+            }
+
+            let (key, value) = content
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error("expected key = value".to_string()))?;
+            let string = || parse_string(value).ok_or_else(|| error(format!("expected a quoted {}", key)));
+            let Some(level) = levels.last_mut() else {
+                match key {
+                    "name" => name = Some(string()?),
+                    _ => return Err(error(format!("unknown key {}", key))),
+                }
+                continue;
+            };
+            match key {
+                "map" => level.map = directory.join(string()?),
+                "name" => level.name = Some(string()?),
+                "spawn" => level.spawn = Some(parse_spawn(value).ok_or_else(|| error(format!("invalid spawn: {}", value)))?),
+                "wolf_level" => {
+                    level.wolf_level = value
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|level| level.checked_sub(1))
+                        .ok_or_else(|| error(format!("invalid level: {}", value)))?;
+                }
+                _ => return Err(error(format!("unknown key {}", key))),
+            }
+        }
+
+        if levels.is_empty() {
+            return Err(RaycastError::Config(format!("campaign {}: no levels", path.display())));
+        }
+        if let Some(number) = levels.iter().position(|level| level.map.as_os_str().is_empty()) {
+            return Err(RaycastError::Config(format!("campaign {}: level {} has no map", path.display(), number + 1)));
+        }
+        Ok(Campaign {
+            name: name.unwrap_or_else(|| {
+                path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+            }),
+            levels,
+        })
+    }
+}
+
+impl CampaignLevel {
+    // Load the level's map with its name and spawn point applied
+    pub fn load(&self) -> Result<MapFile, RaycastError> {
+        let mut map = if wolf3d::is_gamemaps(&self.map) {
+            wolf3d::load(&self.map, self.wolf_level)?
+        } else {
+            MapFile::load(&self.map)?
+        };
+        if let Some(name) = &self.name {
+            map.name = name.clone();
+        }
+        if let Some(spawn) = self.spawn {
+            map.spawns = vec![spawn];
+            // The new spawn point could be inside a wall
+            map.validate()?;
+        }
+        Ok(map)
+    }
+}
+
+// `[x, y]` or `[x, y, degrees]`
+fn parse_spawn(value: &str) -> Option<(f64, f64, f64)> {
+    let numbers: Vec<f64> = value
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split(',')
+        .map(|number| number.trim().parse().ok().filter(|number: &f64| number.is_finite()))
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [x, y] => Some((x, y, 0.0)),
+        [x, y, degrees] => Some((x, y, degrees.to_radians())),
+        _ => None,
+    }
+}
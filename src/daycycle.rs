@@ -0,0 +1,95 @@
+// Time of day. The sky gradient and the overall light level follow the clock
+// through night, sunrise, day and sunset. The clock only moves when advanced,
+// so a cycle started at the same hour always plays out the same way.
+
+use crate::palette::{self, Rgb};
+
+pub const DEFAULT_DAY_LENGTH: f64 = 120.0; // Seconds for a full day
+pub const DEFAULT_START_HOUR: f64 = 12.0; // Noon
+
+// Look of the sky and light at one hour; the hours between are interpolated
+struct SkyKey {
+    hour: f64,
+    zenith: (u8, u8, u8), // Sky color overhead
+    horizon: (u8, u8, u8), // Sky color just above the walls
+    light: f64, // Brightness factor for walls, floor and sprites
+}
+
+// In order of hour; midnight wraps around to the first key
+const SKY_KEYS: [SkyKey; 7] = [
+    SkyKey { hour: 0.0, zenith: (5, 5, 25), horizon: (20, 20, 50), light: 0.35 },
+    SkyKey { hour: 5.0, zenith: (20, 20, 60), horizon: (90, 60, 90), light: 0.45 },
+    SkyKey { hour: 7.0, zenith: (60, 110, 190), horizon: (250, 150, 80), light: 0.75 },
+    SkyKey { hour: 12.0, zenith: (0, 135, 215), horizon: (95, 215, 255), light: 1.0 },
+    SkyKey { hour: 17.0, zenith: (30, 110, 200), horizon: (150, 190, 240), light: 0.95 },
+    SkyKey { hour: 19.0, zenith: (70, 60, 130), horizon: (250, 120, 50), light: 0.7 },
+    SkyKey { hour: 21.0, zenith: (15, 15, 50), horizon: (60, 40, 80), light: 0.45 },
+];
+
+pub struct DayCycle {
+    pub hour: f64, // Time of day, 0 to 24
+    pub day_length: f64, // Seconds of play per full day
+    pub running: bool,
+}
+
+impl DayCycle {
+    pub fn new(hour: f64, day_length: f64) -> Self {
+        DayCycle {
+            hour: hour.rem_euclid(24.0),
+            day_length,
+            running: true,
+        }
+    }
+
+    // Move the clock on by `seconds` of play, unless paused
+    pub fn advance(&mut self, seconds: f64) {
+        if self.running {
+            self.scrub(24.0 * seconds / self.day_length);
+        }
+    }
+
+    // Jump the clock forwards (or backwards, if negative) by some hours
+    pub fn scrub(&mut self, hours: f64) {
+        self.hour = (self.hour + hours).rem_euclid(24.0);
+    }
+
+    // Brightness factor for the world at the current hour
+    pub fn light(&self) -> f64 {
+        let (from, to, t) = self.keys();
+        from.light + (to.light - from.light) * t
+    }
+
+    // Sky color at `height` above the horizon, from 0 (horizon) to 1 (zenith)
+    pub fn sky_color(&self, height: f64) -> u8 {
+        let (r, g, b) = self.sky_rgb(height);
+        palette::from_rgb(r, g, b)
+    }
+
+    // Exact sky color at `height`, before matching it to the palette
+    pub fn sky_rgb(&self, height: f64) -> Rgb {
+        let (from, to, t) = self.keys();
+        let mix = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            let lerp = |a: u8, b: u8| a as f64 + (b as f64 - a as f64) * t;
+            (lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+        };
+        let zenith = mix(from.zenith, to.zenith);
+        let horizon = mix(from.horizon, to.horizon);
+        let height = height.clamp(0.0, 1.0);
+        let channel = |h: f64, z: f64| (h + (z - h) * height).round() as u8;
+        (
+            channel(horizon.0, zenith.0),
+            channel(horizon.1, zenith.1),
+            channel(horizon.2, zenith.2),
+        )
+    }
+
+    // The keys either side of the current hour and how far it is between them
+    fn keys(&self) -> (&'static SkyKey, &'static SkyKey, f64) {
+        let next = SKY_KEYS.iter().position(|key| key.hour > self.hour).unwrap_or(0);
+        let from = &SKY_KEYS[(next + SKY_KEYS.len() - 1) % SKY_KEYS.len()];
+        let to = &SKY_KEYS[next];
+        let span = (to.hour - from.hour).rem_euclid(24.0);
+        let t = (self.hour - from.hour).rem_euclid(24.0) / span;
+        (from, to, t)
+    }
+}
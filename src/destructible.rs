@@ -0,0 +1,35 @@
+// Destructible walls. Each hit from the interaction ray (fire or mouse click)
+// wears one down: it shows cracks once half its strength is gone and
+// crumbles into open floor after the last hit.
+
+pub const DESTRUCTIBLE_CELL: u8 = b'%';
+
+const WALL_HEALTH: u32 = 4; // Hits a destructible wall takes before crumbling
+
+#[derive(Clone, Copy, Debug)]
+pub struct DestructibleWall {
+    pub health: u32, // Hits left before it crumbles
+}
+
+impl DestructibleWall {
+    pub fn new() -> Self {
+        DestructibleWall { health: WALL_HEALTH }
+    }
+
+    // Take one hit. Returns true if the wall has crumbled.
+    pub fn hit(&mut self) -> bool {
+        self.health = self.health.saturating_sub(1);
+        self.health == 0
+    }
+
+    // Whether enough hits have landed to show cracks
+    pub fn is_cracked(&self) -> bool {
+        self.health <= WALL_HEALTH / 2
+    }
+}
+
+impl Default for DestructibleWall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
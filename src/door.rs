@@ -0,0 +1,105 @@
+// Sliding doors. A door cell holds a panel across its middle that slides
+// sideways into the wall beside it when opened, stays open for a while, then
+// slides back unless something is standing in the doorway.
+
+pub const DOOR_CELL: u8 = b'+';
+
+const SLIDE_FRAMES: u32 = 30; // Frames to open or close fully
+const HOLD_FRAMES: u32 = 180; // Frames a door stays fully open before closing
+const PASSABLE_OPENING: f64 = 0.8; // How far open a door must be to walk through
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DoorState {
+    Closed,
+    Opening,
+    Open { frames_left: u32 },
+    Closing,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Door {
+    // Whether the panel runs along the x axis, between walls to the west and
+    // east; otherwise it runs along the y axis
+    pub along_x: bool,
+    pub opening: f64, // 0.0 (closed) to 1.0 (fully open)
+    state: DoorState,
+}
+
+impl Door {
+    pub fn new(along_x: bool) -> Self {
+        Door {
+            along_x,
+            opening: 0.0,
+            state: DoorState::Closed,
+        }
+    }
+
+    // Start opening the door, or keep it open for longer if it already is.
+    // Returns false if it was already open or opening.
+    pub fn open(&mut self) -> bool {
+        match self.state {
+            DoorState::Closed | DoorState::Closing => {
+                self.state = DoorState::Opening;
+                true
+            }
+            DoorState::Open { .. } => {
+                self.state = DoorState::Open { frames_left: HOLD_FRAMES };
+                false
+            }
+            DoorState::Opening => false,
+        }
+    }
+
+    // Whether the gap is wide enough to walk through
+    pub fn is_passable(&self) -> bool {
+        self.opening >= PASSABLE_OPENING
+    }
+
+    // Advance one frame. An occupied doorway is held open, so the door never
+    // closes on the player or an enemy.
+    pub fn update(&mut self, occupied: bool) {
+        let step = 1.0 / SLIDE_FRAMES as f64;
+        self.state = match self.state {
+            DoorState::Opening => {
+                self.opening = (self.opening + step).min(1.0);
+                if self.opening < 1.0 {
+                    DoorState::Opening
+                } else {
+                    DoorState::Open { frames_left: HOLD_FRAMES }
+                }
+            }
+            DoorState::Open { .. } if occupied => DoorState::Open { frames_left: HOLD_FRAMES },
+            DoorState::Open { frames_left: 0 } => DoorState::Closing,
+            DoorState::Open { frames_left } => DoorState::Open { frames_left: frames_left - 1 },
+            DoorState::Closing if occupied => DoorState::Opening,
+            DoorState::Closing => {
+                self.opening = (self.opening - step).max(0.0);
+                if self.opening > 0.0 {
+                    DoorState::Closing
+                } else {
+                    DoorState::Closed
+                }
+            }
+            DoorState::Closed => DoorState::Closed,
+        };
+    }
+
+    // Distance along a ray to the visible part of the panel, and how far
+    // along the panel it strikes (0.0 at its leading edge), for a ray leaving
+    // (u, v) within the cell with direction (cos, sin)
+    pub fn intersect(&self, u: f64, v: f64, cos: f64, sin: f64) -> Option<(f64, f64)> {
+        let (start, across, direction, along) = if self.along_x {
+            (v, u, sin, cos)
+        } else {
+            (u, v, cos, sin)
+        };
+        if direction == 0.0 {
+            return None;
+        }
+        let t = (0.5 - start) / direction;
+        let position = across + t * along;
+        // The panel slides towards the far end of the cell, opening a gap
+        // from the near end
+        (t >= 0.0 && (self.opening..=1.0).contains(&position)).then_some((t, position - self.opening))
+    }
+}
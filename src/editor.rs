@@ -0,0 +1,162 @@
+// In-game map editor. Shows the current map from above with a cursor; walls
+// are placed and removed and the spawn point moved with the keyboard or
+// mouse, and the edited map can be exported as text in the same format as
+// the built-in maps or saved back to the file it came from.
+
+use crossterm::event::KeyCode;
+
+use crate::destructible::DESTRUCTIBLE_CELL;
+use crate::door::DOOR_CELL;
+use crate::map::EXIT_CELL;
+use crate::mirror::MIRROR_CELL;
+use crate::pushwall::PUSH_WALL_CELL;
+use crate::raycaster::{Raycaster, MARKER_COLOR};
+use crate::window::WINDOW_CELL;
+
+pub(crate) const BACKGROUND_COLOR: u8 = 16; // Black, around the map
+const CURSOR_COLOR: u8 = 196; // Red
+pub(crate) const PLAYER_COLOR: u8 = 226; // Yellow
+const SPAWN_COLOR: u8 = 39; // Sky blue
+const OTHER_SPAWN_COLOR: u8 = 24; // Dark blue, spawn points not chosen
+
+pub struct Editor {
+    pub cursor: (usize, usize),
+    pub material: u8, // Map character placed by the next edit
+    pub status: Option<String>, // Outcome of the last save, shown in the label
+}
+
+impl Editor {
+    // Start with the cursor on the player's cell, placing brick
+    pub fn new(raycaster: &Raycaster) -> Self {
+        let map = raycaster.map();
+        Editor {
+            cursor: (
+                (raycaster.player.x as usize).min(map.width() - 1),
+                (raycaster.player.y as usize).min(map.height() - 1),
+            ),
+            material: b'1',
+            status: None,
+        }
+    }
+
+    // Handle a key press. Returns false for keys the editor doesn't use.
+    pub fn handle_key(&mut self, code: KeyCode, raycaster: &mut Raycaster) -> bool {
+        let (x, y) = self.cursor;
+        let (width, height) = (raycaster.map().width(), raycaster.map().height());
+        match code {
+            KeyCode::Left => self.cursor.0 = x.saturating_sub(1),
+            KeyCode::Right => self.cursor.0 = (x + 1).min(width - 1),
+            KeyCode::Up => self.cursor.1 = y.saturating_sub(1),
+            KeyCode::Down => self.cursor.1 = (y + 1).min(height - 1),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle(raycaster),
+            KeyCode::Char('s') => self.place_spawn(raycaster),
+            KeyCode::Char(c @ ('1'..='9' | '+' | '*' | '%' | '=' | '@' | '>')) => self.material = c as u8,
+            _ => return false,
+        }
+        true
+    }
+
+    // Move the cursor to the cell at a pixel of the editor view and toggle it
+    pub fn click(&mut self, pixel: (usize, usize), size: (usize, usize), raycaster: &mut Raycaster) {
+        let (cell_size, offset_x, offset_y) = layout(raycaster, size.0, size.1);
+        let (Some(px), Some(py)) = (pixel.0.checked_sub(offset_x), pixel.1.checked_sub(offset_y)) else {
+            return;
+        };
+        let (x, y) = (px / cell_size, py / cell_size);
+        if raycaster.map().get(x, y).is_some() {
+            self.cursor = (x, y);
+            self.toggle(raycaster);
+        }
+    }
+
+    // Toggle the cell under the cursor between empty and the current material
+    fn toggle(&self, raycaster: &mut Raycaster) {
+        let (x, y) = self.cursor;
+        let cell = if raycaster.cell(x, y) == Some(b'0') { self.material } else { b'0' };
+        raycaster.set_cell(x, y, cell);
+    }
+
+    // Move the spawn point to the middle of the cell under the cursor,
+    // keeping its facing. Only plain floor can hold it.
+    fn place_spawn(&self, raycaster: &mut Raycaster) {
+        let (x, y) = self.cursor;
+        if raycaster.cell(x, y) == Some(b'0') {
+            raycaster.spawn = (x as f64 + 0.5, y as f64 + 0.5, raycaster.spawn.2);
+        }
+    }
+
+    // Top-down view of the map at the given pixel size
+    pub fn frame(&self, raycaster: &Raycaster, width: usize, height: usize) -> Vec<Vec<u8>> {
+        let mut frame_buffer = vec![vec![BACKGROUND_COLOR; width]; height];
+        let (cell_size, offset_x, offset_y) = layout(raycaster, width, height);
+
+        let spawn = (raycaster.spawn.0 as usize, raycaster.spawn.1 as usize);
+        let other_spawns: Vec<(usize, usize)> = raycaster
+            .other_spawn_points()
+            .map(|(x, y, _)| (x as usize, y as usize))
+            .collect();
+        for ((x, y), cell) in raycaster.map().cells() {
+            let color = match (x, y) {
+                position if position == self.cursor => CURSOR_COLOR,
+                position if position == spawn => SPAWN_COLOR,
+                position if other_spawns.contains(&position) => OTHER_SPAWN_COLOR,
+                _ => cell_color(cell),
+            };
+            for row in frame_buffer.iter_mut().skip(offset_y + y * cell_size).take(cell_size) {
+                for pixel in row.iter_mut().skip(offset_x + x * cell_size).take(cell_size) {
+                    *pixel = color;
+                }
+            }
+        }
+
+        let points = raycaster
+            .markers()
+            .iter()
+            .map(|&marker| (marker, MARKER_COLOR))
+            .chain([((raycaster.player.x, raycaster.player.y), PLAYER_COLOR)]);
+        for ((x, y), color) in points {
+            let pixel_x = offset_x + (x * cell_size as f64) as usize;
+            let pixel_y = offset_y + (y * cell_size as f64) as usize;
+            if let Some(pixel) = frame_buffer.get_mut(pixel_y).and_then(|row| row.get_mut(pixel_x)) {
+                *pixel = color;
+            }
+        }
+
+        frame_buffer
+    }
+}
+
+// Pixels per map cell and the offset that centers the map in the view
+fn layout(raycaster: &Raycaster, width: usize, height: usize) -> (usize, usize, usize) {
+    let map = raycaster.map();
+    let cell_size = (width / map.width()).min(height / map.height()).max(1);
+    let offset_x = width.saturating_sub(cell_size * map.width()) / 2;
+    let offset_y = height.saturating_sub(cell_size * map.height()) / 2;
+    (cell_size, offset_x, offset_y)
+}
+
+// Color of a map character in the top-down view
+pub(crate) fn cell_color(cell: u8) -> u8 {
+    match cell {
+        b'0' => 236, // Empty: dark gray
+        b'1' => 130, // Brick
+        b'2' => 250, // Checker
+        b'3' => 67, // Stripes
+        b'4' => 51, // Glass
+        b'5' => 27, // Water curtain
+        b'6' => 71,
+        b'7' => 133,
+        b'8' => 109,
+        b'9' => 168,
+        b'/' | b'\\' => 94, // Diagonal brick
+        b'-' | b'|' => 67, // Thin wall
+        DOOR_CELL => 179, // Door
+        PUSH_WALL_CELL => 166, // Push-wall
+        DESTRUCTIBLE_CELL => 137, // Destructible wall
+        WINDOW_CELL => 109, // Window
+        MIRROR_CELL => 153, // Mirror
+        EXIT_CELL => 46, // Exit
+        b'A'..=b'Z' => 201, // Teleporter
+        _ => 244,
+    }
+}
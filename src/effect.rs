@@ -0,0 +1,51 @@
+// Full-screen color effects laid over the view: flashes that fade out, such
+// as the red flash on taking damage, and fades that build up to a solid
+// color and hold it, such as the fade to black at the end of a level. Each
+// runs for its own duration; several can run at once and are laid on in the
+// order they started.
+
+use std::time::{Duration, Instant};
+
+use crate::palette::Rgb;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenEffect {
+    // Starts `strength` of the way to `color`, from 0 to 1, and fades out
+    Flash { color: Rgb, strength: f64 },
+    // Builds up from nothing to solid `color`, then holds it until cleared
+    Fade { color: Rgb },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EffectQueue {
+    effects: Vec<(ScreenEffect, Instant, Duration)>,
+}
+
+impl EffectQueue {
+    pub fn push(&mut self, effect: ScreenEffect, duration: Duration, now: Instant) {
+        self.effects.push((effect, now, duration));
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    // Color and strength of each effect at `now`, in the order to lay them
+    // on. Flashes that have faded out are dropped.
+    pub fn active(&mut self, now: Instant) -> Vec<(Rgb, f64)> {
+        self.effects.retain(|&(effect, started, duration)| {
+            matches!(effect, ScreenEffect::Fade { .. }) || now.duration_since(started) < duration
+        });
+        self.effects
+            .iter()
+            .map(|&(effect, started, duration)| {
+                let progress = (now.duration_since(started).as_secs_f64() / duration.as_secs_f64().max(f64::EPSILON))
+                    .min(1.0);
+                match effect {
+                    ScreenEffect::Flash { color, strength } => (color, strength * (1.0 - progress)),
+                    ScreenEffect::Fade { color } => (color, progress),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,119 @@
+// Simple enemy AI: walk a patrol route, chase the player on sight, and wait
+// a moment after losing track of them before returning to the route.
+
+const PATROL_SPEED: f64 = 0.03; // World units per frame
+const CHASE_SPEED: f64 = 0.04;
+const WAYPOINT_RADIUS: f64 = 0.1; // Distance at which a waypoint counts as reached
+const IDLE_FRAMES: u32 = 90; // Frames to wait after losing sight of the player
+const ENEMY_RADIUS: f64 = 0.25; // Clearance kept from walls
+pub const CHASE_RANGE: f64 = 8.0; // Enemies only notice players within this distance
+pub const CONTACT_RANGE: f64 = 0.5; // Center distance at which an enemy touches the player
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyState {
+    Idle,
+    Patrol,
+    Chase,
+}
+
+pub struct Enemy {
+    pub x: f64,
+    pub y: f64,
+    pub state: EnemyState,
+    patrol: Vec<(f64, f64)>,
+    waypoint: usize,
+    idle_frames: u32,
+}
+
+impl Enemy {
+    // Enemy starting at the first point of its patrol route. A route with a
+    // single point makes a guard that stands still until it sees the player.
+    pub fn new(patrol: Vec<(f64, f64)>) -> Self {
+        let (x, y) = patrol.first().copied().unwrap_or((0.0, 0.0));
+        Enemy {
+            x,
+            y,
+            state: if patrol.len() > 1 { EnemyState::Patrol } else { EnemyState::Idle },
+            patrol,
+            waypoint: 0,
+            idle_frames: 0,
+        }
+    }
+
+    // Sprite color reflecting the current state
+    pub fn color(&self) -> u8 {
+        match self.state {
+            EnemyState::Idle => 136,   // Brown
+            EnemyState::Patrol => 34,  // Green
+            EnemyState::Chase => 196,  // Red
+        }
+    }
+
+    // Advance one frame. `sees_player` should be true when the player is in
+    // range and in line of sight; `is_open` reports whether a world position
+    // is free of walls.
+    pub fn update(&mut self, player: (f64, f64), sees_player: bool, is_open: impl Fn(f64, f64) -> bool) {
+        if sees_player {
+            self.state = EnemyState::Chase;
+        } else if self.state == EnemyState::Chase {
+            self.state = EnemyState::Idle;
+            self.idle_frames = IDLE_FRAMES;
+        }
+        
+        match self.state {
+            EnemyState::Chase => {
+                // Stop short of the player's center so contact is a touch, not an overlap
+                let distance = (player.0 - self.x).hypot(player.1 - self.y);
+                if distance > CONTACT_RANGE * 0.8 {
+                    self.move_towards(player, CHASE_SPEED, &is_open);
+                }
+            }
+            EnemyState::Idle => {
+                if self.patrol.len() > 1 {
+                    self.idle_frames = self.idle_frames.saturating_sub(1);
+                    if self.idle_frames == 0 {
+                        self.state = EnemyState::Patrol;
+                    }
+                }
+            }
+            EnemyState::Patrol => {
+                let target = self.patrol[self.waypoint];
+                if (target.0 - self.x).hypot(target.1 - self.y) <= WAYPOINT_RADIUS {
+                    self.waypoint = (self.waypoint + 1) % self.patrol.len();
+                } else if !self.move_towards(target, PATROL_SPEED, &is_open) {
+                    // Blocked by a wall; give up on this waypoint
+                    self.waypoint = (self.waypoint + 1) % self.patrol.len();
+                }
+            }
+        }
+    }
+
+    // Step towards a point, sliding along walls one axis at a time.
+    // Returns false if the enemy could not move at all.
+    fn move_towards(&mut self, target: (f64, f64), speed: f64, is_open: impl Fn(f64, f64) -> bool) -> bool {
+        let (dx, dy) = (target.0 - self.x, target.1 - self.y);
+        let distance = dx.hypot(dy);
+        if distance == 0.0 {
+            return true;
+        }
+        let step = speed.min(distance);
+        let (move_x, move_y) = (dx / distance * step, dy / distance * step);
+        let clear = |x: f64, y: f64| {
+            is_open(x - ENEMY_RADIUS, y - ENEMY_RADIUS)
+                && is_open(x + ENEMY_RADIUS, y - ENEMY_RADIUS)
+                && is_open(x - ENEMY_RADIUS, y + ENEMY_RADIUS)
+                && is_open(x + ENEMY_RADIUS, y + ENEMY_RADIUS)
+        };
+        
+        let mut moved = false;
+        if clear(self.x + move_x, self.y) {
+            self.x += move_x;
+            moved = true;
+        }
+        if clear(self.x, self.y + move_y) {
+            self.y += move_y;
+            moved = true;
+        }
+        moved
+    }
+}
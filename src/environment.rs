@@ -0,0 +1,72 @@
+// Environment settings a map can carry, giving a level its own atmosphere:
+// fog thickening with distance or closing in over a set range, the overall
+// light level and the base colors of the sky and floor. The defaults leave
+// the view exactly as it is drawn without any.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Environment {
+    pub fog_density: f64, // Fraction of the view lost to fog per cell of distance; 0 for none
+    pub fog_start: f64, // Distance where linear fog begins
+    pub fog_end: f64, // Distance where linear fog hides everything; none unless past fog_start
+    pub fog_color: u8, // 256-color code things fade towards in fog
+    pub ambient: f64, // Brightness factor for walls, floor and sprites; 1 is normal
+    pub sky_color: Option<u8>, // Base of the ceiling gradient, instead of the usual blues
+    pub floor_color: Option<u8>, // Base of the floor gradient, instead of the usual grays
+}
+
+impl Environment {
+    // How much of a color at a distance is hidden by fog, from 0 (none) to 1.
+    // Density fog thickens gradually from the viewer; linear fog is clear
+    // up to its start and complete at its end. With both, the thicker wins.
+    pub fn fog(&self, distance: f64) -> f64 {
+        let distance = distance.max(0.0);
+        let density = if self.fog_density > 0.0 {
+            1.0 - (-self.fog_density * distance).exp()
+        } else {
+            0.0
+        };
+        let linear = if self.fog_end > self.fog_start {
+            ((distance - self.fog_start) / (self.fog_end - self.fog_start)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        density.max(linear)
+    }
+
+    // These settings with the fog range and color left at their defaults
+    // taken from `fallback` instead, so a map's own fog wins over fog given
+    // on the command line
+    pub fn or_fog(self, fallback: &Environment) -> Environment {
+        let default = Environment::default();
+        let (fog_start, fog_end) = if self.fog_end != default.fog_end {
+            (self.fog_start, self.fog_end)
+        } else {
+            (fallback.fog_start, fallback.fog_end)
+        };
+        let fog_color = if self.fog_color != default.fog_color {
+            self.fog_color
+        } else {
+            fallback.fog_color
+        };
+        Environment {
+            fog_start,
+            fog_end,
+            fog_color,
+            ..self
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            fog_density: 0.0,
+            fog_start: 0.0,
+            fog_end: 0.0,
+            fog_color: 16, // Black
+            ambient: 1.0,
+            sky_color: None,
+            floor_color: None,
+        }
+    }
+}
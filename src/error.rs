@@ -0,0 +1,49 @@
+// Error type shared by everything that can fail while loading or running a
+// level, so callers can tell a malformed map apart from a missing file.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum RaycastError {
+    // Reading or writing a file or the terminal failed
+    Io(io::Error),
+    // A map file could not be parsed; `line` is 1-based
+    MapParse { line: usize, reason: String },
+    // A map parsed but can't be played; `row` and `column` are 1-based
+    MapInvalid { row: usize, column: usize, reason: String },
+    // Invalid command-line or configuration value
+    Config(String),
+    // A save file could not be read or written
+    Save(String),
+}
+
+impl fmt::Display for RaycastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RaycastError::Io(err) => write!(f, "{}", err),
+            RaycastError::MapParse { line, reason } => write!(f, "map line {}: {}", line, reason),
+            RaycastError::MapInvalid { row, column, reason } => {
+                write!(f, "map row {}, column {}: {}", row, column, reason)
+            }
+            RaycastError::Config(message) => write!(f, "{}", message),
+            RaycastError::Save(message) => write!(f, "save file: {}", message),
+        }
+    }
+}
+
+impl Error for RaycastError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RaycastError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RaycastError {
+    fn from(err: io::Error) -> Self {
+        RaycastError::Io(err)
+    }
+}
@@ -0,0 +1,26 @@
+// Gameplay events raised by the raycaster during input handling and update.
+// They queue up until the caller drains them, usually once per frame.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    // The interaction ray (keyboard fire or mouse click) struck a wall cell
+    WallHit { map_x: i32, map_y: i32, distance: f64 },
+    // An enemy is touching the player; raised every frame while in contact
+    EnemyContact { enemy: usize },
+    // Standing on a lava or acid cell cost the player `amount` health
+    HazardDamage { map_x: i32, map_y: i32, amount: f64 },
+    // The player moved into a different map cell
+    TileEntered { map_x: i32, map_y: i32 },
+    // A teleporter moved the player to its partner cell
+    Teleported { map_x: i32, map_y: i32 },
+    // The player opened a door
+    DoorOpened { map_x: i32, map_y: i32 },
+    // The player set a push-wall sliding from this cell
+    WallPushed { map_x: i32, map_y: i32 },
+    // A destructible wall took its last hit and crumbled into floor
+    WallDestroyed { map_x: i32, map_y: i32 },
+    // The player was put back at the spawn point
+    Reset,
+    // The current map was finished, after `seconds` of play
+    LevelComplete { seconds: f64 },
+}
@@ -0,0 +1,318 @@
+// Procedural map generators. Each builds a wall grid from a seed, so the
+// same seed and size always give the same level.
+
+use std::str::FromStr;
+
+use crate::map::{Map, EXIT_CELL};
+use crate::mapfile::MapFile;
+use crate::RaycastError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Generator {
+    // Corridors one cell wide with exactly one path between any two points
+    Maze,
+    // Rectangular rooms joined by corridors, from binary space partitioning
+    Dungeon,
+    // Organic caverns grown by cellular automaton smoothing
+    Caves,
+}
+
+impl FromStr for Generator {
+    type Err = RaycastError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "maze" => Ok(Generator::Maze),
+            "dungeon" => Ok(Generator::Dungeon),
+            "caves" => Ok(Generator::Caves),
+            _ => Err(RaycastError::Config(format!("unknown generator: {}", value))),
+        }
+    }
+}
+
+impl Generator {
+    fn name(self) -> &'static str {
+        match self {
+            Generator::Maze => "Maze",
+            Generator::Dungeon => "Dungeon",
+            Generator::Caves => "Caves",
+        }
+    }
+}
+
+// Range of generated map sizes, in cells along each side
+const MIN_SIZE: usize = 5;
+const MAX_SIZE: usize = 1024;
+
+// Small deterministic random number generator (SplitMix64)
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform integer in 0..bound; bound must be non-zero
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+// Generate a `width` x `height` level
+pub fn generate(generator: Generator, width: usize, height: usize, seed: u64) -> Result<MapFile, RaycastError> {
+    if width < MIN_SIZE || height < MIN_SIZE || width > MAX_SIZE || height > MAX_SIZE {
+        return Err(RaycastError::Config(format!(
+            "generated maps must be between {min} x {min} and {max} x {max}, not {} x {}",
+            width,
+            height,
+            min = MIN_SIZE,
+            max = MAX_SIZE
+        )));
+    }
+    let mut rng = Rng::new(seed);
+    let cells = match generator {
+        Generator::Maze => maze(width, height, &mut rng),
+        Generator::Dungeon => dungeon(width, height, &mut rng),
+        Generator::Caves => caves(width, height, &mut rng),
+    };
+
+    // Start in the first open cell, facing along the corridor leading out of
+    // it, and put the exit as far away as the level allows
+    let (x, y) = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .find(|&(x, y)| cells[y][x])
+        .unwrap_or((1, 1));
+    let angle = if cells[y].get(x + 1) == Some(&true) { 0.0 } else { std::f64::consts::FRAC_PI_2 };
+    let exit = farthest_cell(&cells, (x, y));
+
+    let mut rows: Vec<Vec<u8>> = cells
+        .iter()
+        .map(|row| row.iter().map(|&open| if open { b'0' } else { b'1' }).collect())
+        .collect();
+    if exit != (x, y) {
+        rows[exit.1][exit.0] = EXIT_CELL;
+    }
+    let walls = Map::from_rows(&rows).expect("generators fill the whole grid");
+
+    Ok(MapFile {
+        name: format!("{} (seed {})", generator.name(), seed),
+        spawns: vec![(x as f64 + 0.5, y as f64 + 0.5, angle)],
+        ..MapFile::from_walls(walls)
+    })
+}
+
+// Open cell with the longest walk from `start`, found by breadth-first search
+fn farthest_cell(open: &[Vec<bool>], start: (usize, usize)) -> (usize, usize) {
+    let mut visited = vec![vec![false; open[0].len()]; open.len()];
+    let mut queue = std::collections::VecDeque::from([start]);
+    let mut last = start;
+    visited[start.1][start.0] = true;
+    while let Some((x, y)) = queue.pop_front() {
+        last = (x, y);
+        for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if open[ny][nx] && !visited[ny][nx] {
+                visited[ny][nx] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    last
+}
+
+// Recursive backtracker maze. Rooms sit on odd coordinates with walls between
+// them; the walk carves into a random unvisited neighbour and backs up when
+// it runs out, which visits every room exactly once. Returns open cells.
+fn maze(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<bool>> {
+    let mut open = vec![vec![false; width]; height];
+    let mut stack: Vec<(usize, usize)> = vec![(1, 1)];
+    open[1][1] = true;
+    while let Some(&(x, y)) = stack.last() {
+        let neighbours: Vec<(usize, usize)> = [(2, 0), (0, 2), (-2, 0), (0, -2)]
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let nx = x.checked_add_signed(dx)?;
+                let ny = y.checked_add_signed(dy)?;
+                (nx < width - 1 && ny < height - 1 && !open[ny][nx]).then_some((nx, ny))
+            })
+            .collect();
+        if neighbours.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = neighbours[rng.below(neighbours.len())];
+        open[(y + ny) / 2][(x + nx) / 2] = true;
+        open[ny][nx] = true;
+        stack.push((nx, ny));
+    }
+    open
+}
+
+// Smallest part of the map a split may leave, and the smallest room
+const MIN_PARTITION: usize = 7;
+const MIN_ROOM: usize = 3;
+
+// Rectangle of cells: (x, y, width, height)
+type Rect = (usize, usize, usize, usize);
+
+// Room-and-corridor dungeon. The map is split in two again and again at
+// random points (binary space partitioning), a room is placed in each final
+// part, and sibling parts are joined by an L-shaped corridor between their
+// rooms, so every room is reachable. Returns open cells.
+fn dungeon(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<bool>> {
+    let mut open = vec![vec![false; width]; height];
+    // Leave the outer wall intact
+    partition(&mut open, (1, 1, width - 2, height - 2), rng);
+    open
+}
+
+// Fill `area` with rooms and corridors. Returns the center of one room in it,
+// for the caller to connect to its sibling.
+fn partition(open: &mut [Vec<bool>], area: Rect, rng: &mut Rng) -> (usize, usize) {
+    let (x, y, width, height) = area;
+    let split_vertical = match (width >= MIN_PARTITION * 2, height >= MIN_PARTITION * 2) {
+        (false, false) => return room(open, area, rng),
+        (true, false) => true,
+        (false, true) => false,
+        // Split across the longer side
+        (true, true) => width > height || (width == height && rng.below(2) == 0),
+    };
+    let length = if split_vertical { width } else { height };
+    let at = MIN_PARTITION + rng.below(length - MIN_PARTITION * 2 + 1);
+    let (first, second) = if split_vertical {
+        ((x, y, at, height), (x + at, y, width - at, height))
+    } else {
+        ((x, y, width, at), (x, y + at, width, height - at))
+    };
+    let a = partition(open, first, rng);
+    let b = partition(open, second, rng);
+    corridor(open, a, b, rng);
+    if rng.below(2) == 0 { a } else { b }
+}
+
+// Carve a room of random size and position within `area`, keeping a wall
+// between it and the area's edges where there is space. Returns its center.
+fn room(open: &mut [Vec<bool>], area: Rect, rng: &mut Rng) -> (usize, usize) {
+    let (x, y, width, height) = area;
+    let mut span = |offset: usize, length: usize| {
+        let inner = length.saturating_sub(2).max(1);
+        let size = MIN_ROOM.min(inner) + rng.below(inner - MIN_ROOM.min(inner) + 1);
+        let start = offset + (length - inner) / 2 + rng.below(inner - size + 1);
+        (start, size)
+    };
+    let (left, room_width) = span(x, width);
+    let (top, room_height) = span(y, height);
+    for row in &mut open[top..top + room_height] {
+        row[left..left + room_width].fill(true);
+    }
+    (left + room_width / 2, top + room_height / 2)
+}
+
+// Carve an L-shaped corridor between two points, turning at a random corner
+fn corridor(open: &mut [Vec<bool>], a: (usize, usize), b: (usize, usize), rng: &mut Rng) {
+    let corner = if rng.below(2) == 0 { (b.0, a.1) } else { (a.0, b.1) };
+    for (from, to) in [(a, corner), (corner, b)] {
+        for row in &mut open[from.1.min(to.1)..=from.1.max(to.1)] {
+            row[from.0.min(to.0)..=from.0.max(to.0)].fill(true);
+        }
+    }
+}
+
+const CAVE_WALL_PERCENT: usize = 45; // Chance of each cell starting as wall
+const CAVE_PASSES: usize = 5;
+const CAVE_WALL_NEIGHBOURS: usize = 5; // Walls among the eight neighbours that make a wall
+const CAVE_KEEP_NEIGHBOURS: usize = 4; // Walls among the neighbours that keep a wall standing
+const CAVE_ATTEMPTS: usize = 10; // Tries at growing a cavern covering a third of the map
+
+// Caverns from a cellular automaton. Cells start as random wall or floor,
+// then each pass turns a cell to wall if most of its neighbours are walls,
+// keeps a wall with half its neighbours walls, and opens everything else,
+// which smooths the noise into blobs. Only the largest cavern is kept, so
+// the player can't start in a sealed pocket, and the whole thing is grown
+// again if that cavern is small. Returns open cells.
+fn caves(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<bool>> {
+    let mut open = cavern(width, height, rng);
+    for _ in 1..CAVE_ATTEMPTS {
+        let size = open.iter().flatten().filter(|&&open| open).count();
+        if size * 3 >= (width - 2) * (height - 2) {
+            break;
+        }
+        open = cavern(width, height, rng);
+    }
+    open
+}
+
+// One attempt at growing caves, keeping the largest cavern
+fn cavern(width: usize, height: usize, rng: &mut Rng) -> Vec<Vec<bool>> {
+    let border = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+    let mut open: Vec<Vec<bool>> = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| !border(x, y) && rng.below(100) >= CAVE_WALL_PERCENT)
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..CAVE_PASSES {
+        open = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        if border(x, y) {
+                            return false;
+                        }
+                        let walls = (y - 1..=y + 1)
+                            .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                            .filter(|&(nx, ny)| (nx, ny) != (x, y) && !open[ny][nx])
+                            .count();
+                        let threshold = if open[y][x] { CAVE_WALL_NEIGHBOURS } else { CAVE_KEEP_NEIGHBOURS };
+                        walls < threshold
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    // Keep only the largest cavern, found by flood filling each in turn
+    let mut region = vec![vec![usize::MAX; width]; height];
+    let mut sizes = Vec::new();
+    for start_y in 0..height {
+        for start_x in 0..width {
+            if !open[start_y][start_x] || region[start_y][start_x] != usize::MAX {
+                continue;
+            }
+            let id = sizes.len();
+            let mut size = 0;
+            let mut stack = vec![(start_x, start_y)];
+            region[start_y][start_x] = id;
+            while let Some((x, y)) = stack.pop() {
+                size += 1;
+                for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                    if open[ny][nx] && region[ny][nx] == usize::MAX {
+                        region[ny][nx] = id;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+    }
+    let Some(largest) = (0..sizes.len()).max_by_key(|&id| sizes[id]) else {
+        // Everything filled in; leave a single open cell to stand in
+        open[height / 2][width / 2] = true;
+        return open;
+    };
+    for (row, regions) in open.iter_mut().zip(&region) {
+        for (cell, &id) in row.iter_mut().zip(regions) {
+            *cell &= id == largest;
+        }
+    }
+    open
+}
@@ -0,0 +1,53 @@
+// Hazard floors. Lava and acid tiles hurt the player every so often while
+// they stand on them, and shimmer through a few colors so they stand out
+// from ordinary floor.
+
+pub const TICK_FRAMES: u32 = 60; // Frames between damage while standing on a hazard
+const FLOW_SPEED: f64 = 0.05; // Color bands passing a point per frame
+
+// Colors each hazard cycles through, as 256-color codes
+const LAVA_COLORS: [u8; 4] = [196, 202, 208, 202];
+const ACID_COLORS: [u8; 4] = [46, 82, 118, 82];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Hazard {
+    Lava,
+    Acid,
+}
+
+impl Hazard {
+    // The hazard a map character stands for: `l` lava, `a` acid
+    pub fn from_char(c: u8) -> Option<Self> {
+        match c {
+            b'l' => Some(Hazard::Lava),
+            b'a' => Some(Hazard::Acid),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> u8 {
+        match self {
+            Hazard::Lava => b'l',
+            Hazard::Acid => b'a',
+        }
+    }
+
+    // Health lost each damage tick
+    pub fn damage(self) -> f64 {
+        match self {
+            Hazard::Lava => 10.0,
+            Hazard::Acid => 5.0,
+        }
+    }
+
+    // Color of the hazard at a world point after `frames` of play. Bands of
+    // color flow diagonally across the floor over time.
+    pub fn color(self, frames: u64, x: f64, y: f64) -> u8 {
+        let colors = match self {
+            Hazard::Lava => &LAVA_COLORS,
+            Hazard::Acid => &ACID_COLORS,
+        };
+        let phase = frames as f64 * FLOW_SPEED + x + y;
+        colors[(phase.floor() as i64).rem_euclid(colors.len() as i64) as usize]
+    }
+}
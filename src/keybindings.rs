@@ -0,0 +1,511 @@
+// Key bindings: which key presses trigger which game actions. Bindings can be
+// loaded from a JSON file mapping action names to key specifications, either
+// a single key or a list:
+//
+//     { "forward": ["w", "Up"], "quit": "Ctrl+q" }
+//
+// or from a RON file (ending in .ron), written as a struct or a map, with
+// comments and trailing commas allowed:
+//
+//     (
+//         forward: ["w", "Up"], // Arrows too
+//         quit: "Ctrl+q",
+//     )
+//
+// Keys are named as they appear on the keyboard ("w", "Up", "F4", "Space",
+// "Enter", "Esc", "Home", ...) with optional "Ctrl+", "Shift+" and "Alt+"
+// prefixes. Actions missing from the file keep their default keys.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::RaycastError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Forward,
+    Backward,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Fire,
+    Use,
+    Quit,
+    Vignette,
+    NextMap,
+    PreviousMap,
+    Reset,
+    TeleportPrompt,
+    FloorGrid,
+    Editor,
+    Spectate,
+    PhotoMode,
+    SavePhoto,
+    ExportSvg,
+    PauseDay,
+    EarlierInDay,
+    LaterInDay,
+    Measure,
+    ClearMarkers,
+    Dither,
+    Flashlight,
+    Minimap,
+    Automap,
+    Hud,
+    FpsCounter,
+    SwapStrafeTurn,
+    Help,
+}
+
+// Every action with its name in bindings files, its description in the help
+// overlay and its default keys
+const ACTIONS: [(Action, &str, &str, &[&str]); 33] = [
+    (Action::Forward, "forward", "Move forward", &["w", "Up"]),
+    (Action::Backward, "backward", "Move backward", &["s", "Down"]),
+    (Action::StrafeLeft, "strafe_left", "Strafe left", &["a"]),
+    (Action::StrafeRight, "strafe_right", "Strafe right", &["d"]),
+    (Action::TurnLeft, "turn_left", "Turn left", &["Left"]),
+    (Action::TurnRight, "turn_right", "Turn right", &["Right"]),
+    (Action::Fire, "fire", "Interact / drop marker", &["Space"]),
+    (Action::Use, "use", "Open door / push wall", &["e"]),
+    (Action::Quit, "quit", "Quit", &["q", "Esc"]),
+    (Action::Vignette, "vignette", "Toggle vignette", &["v"]),
+    (Action::NextMap, "next_map", "Next map", &["n"]),
+    (Action::PreviousMap, "previous_map", "Previous map", &["p"]),
+    (Action::Reset, "reset", "Back to spawn", &["Home"]),
+    (Action::TeleportPrompt, "teleport_prompt", "Teleport prompt", &["F4"]),
+    (Action::FloorGrid, "floor_grid", "Toggle floor grid", &["F6"]),
+    (Action::Editor, "editor", "Map editor", &["F7"]),
+    (Action::Spectate, "spectate", "Free camera", &["F8"]),
+    (Action::PhotoMode, "photo_mode", "Photo mode", &["F12"]),
+    (Action::SavePhoto, "save_photo", "Save photo (photo mode)", &["Enter"]),
+    (Action::ExportSvg, "export_svg", "Save map with route as SVG", &["F9"]),
+    (Action::PauseDay, "pause_day", "Pause time of day", &["t"]),
+    (Action::EarlierInDay, "earlier_in_day", "Earlier in the day", &["["]),
+    (Action::LaterInDay, "later_in_day", "Later in the day", &["]"]),
+    (Action::Measure, "measure", "Measurement mode", &["m"]),
+    (Action::ClearMarkers, "clear_markers", "Clear markers", &["c"]),
+    (Action::Dither, "dither", "Toggle dithering", &["b"]),
+    (Action::Flashlight, "flashlight", "Toggle flashlight", &["f"]),
+    (Action::Minimap, "minimap", "Toggle minimap", &["g"]),
+    (Action::Automap, "automap", "Automap", &["Tab"]),
+    (Action::Hud, "hud", "Toggle status bar", &["h"]),
+    (Action::FpsCounter, "fps_counter", "Toggle frame counter", &["F3"]),
+    (Action::SwapStrafeTurn, "swap_strafe_turn", "Swap strafe and turn", &["x"]),
+    (Action::Help, "help", "Show or hide this help", &["F1", "?"]),
+];
+
+impl Action {
+    // Movement and turning, which act every frame their key is reported
+    // rather than once per press
+    pub fn is_movement(self) -> bool {
+        matches!(
+            self,
+            Action::Forward
+                | Action::Backward
+                | Action::StrafeLeft
+                | Action::StrafeRight
+                | Action::TurnLeft
+                | Action::TurnRight
+        )
+    }
+}
+
+// A key together with the modifiers held with it
+type Key = (KeyCode, KeyModifiers);
+
+pub struct KeyBindings {
+    keys: HashMap<Key, Action>,
+    strafe_turn_swapped: bool, // Strafe keys turn and turn keys strafe
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut keys = HashMap::new();
+        for (action, _, _, specs) in ACTIONS {
+            for spec in specs {
+                keys.insert(parse_key(spec).expect("default key bindings are valid"), action);
+            }
+        }
+        KeyBindings {
+            keys,
+            strafe_turn_swapped: false,
+        }
+    }
+}
+
+impl KeyBindings {
+    // Load a bindings file, as RON if the path ends in .ron and as JSON
+    // otherwise
+    pub fn load(path: &Path) -> Result<Self, RaycastError> {
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension == "ron") {
+            Self::parse_ron(&text)
+        } else {
+            Self::parse(&text)
+        }
+    }
+
+    // Parse the contents of a JSON bindings file. Each action listed replaces
+    // all of its default keys; a key bound to several actions keeps the last.
+    pub fn parse(text: &str) -> Result<Self, RaycastError> {
+        Self::from_entries(parse_object(text, false)?)
+    }
+
+    // Parse the contents of a RON bindings file, the same way
+    pub fn parse_ron(text: &str) -> Result<Self, RaycastError> {
+        Self::from_entries(parse_object(text, true)?)
+    }
+
+    fn from_entries(entries: Vec<(String, Vec<String>)>) -> Result<Self, RaycastError> {
+        let mut bindings = KeyBindings::default();
+        for (name, specs) in entries {
+            let action = ACTIONS
+                .iter()
+                .find(|(_, action_name, _, _)| *action_name == name)
+                .map(|&(action, _, _, _)| action)
+                .ok_or_else(|| RaycastError::Config(format!("unknown action in key bindings: {}", name)))?;
+            bindings.keys.retain(|_, bound| *bound != action);
+            for spec in specs {
+                bindings.keys.insert(parse_key(&spec)?, action);
+            }
+        }
+        Ok(bindings)
+    }
+
+    // Swap the keys bound to strafing with those bound to turning, so with
+    // the defaults A/D turn and the arrow keys strafe
+    pub fn swap_strafe_turn(&mut self) {
+        for action in self.keys.values_mut() {
+            *action = match *action {
+                Action::StrafeLeft => Action::TurnLeft,
+                Action::StrafeRight => Action::TurnRight,
+                Action::TurnLeft => Action::StrafeLeft,
+                Action::TurnRight => Action::StrafeRight,
+                other => other,
+            };
+        }
+        self.strafe_turn_swapped = !self.strafe_turn_swapped;
+    }
+
+    pub fn strafe_turn_swapped(&self) -> bool {
+        self.strafe_turn_swapped
+    }
+
+    // Every action's description with the names of the keys bound to it,
+    // in a stable order, for showing the current controls
+    pub fn describe(&self) -> Vec<(&'static str, Vec<String>)> {
+        ACTIONS
+            .iter()
+            .map(|&(action, _, description, _)| {
+                let mut keys: Vec<String> = self
+                    .keys
+                    .iter()
+                    .filter(|(_, bound)| **bound == action)
+                    .map(|(&key, _)| key_name(key))
+                    .collect();
+                // Plain letters first, then named keys
+                keys.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+                (description, keys)
+            })
+            .collect()
+    }
+
+    // Action bound to a key press, if any
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        self.keys.get(&normalize(code, modifiers)).copied()
+    }
+}
+
+// Parse a key specification such as "w", "Ctrl+w" or "Shift+F4"
+fn parse_key(spec: &str) -> Result<Key, RaycastError> {
+    let invalid = || RaycastError::Config(format!("invalid key in key bindings: {:?}", spec));
+    let mut parts = spec.split('+');
+    let name = parts.next_back().filter(|name| !name.is_empty()).ok_or_else(invalid)?;
+
+    let mut modifiers = KeyModifiers::NONE;
+    for part in parts {
+        modifiers |= match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "shift" => KeyModifiers::SHIFT,
+            "alt" => KeyModifiers::ALT,
+            _ => return Err(invalid()),
+        };
+    }
+
+    let lower = name.to_ascii_lowercase();
+    let code = match lower.as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "insert" => KeyCode::Insert,
+        "delete" => KeyCode::Delete,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => lower
+                    .strip_prefix('f')
+                    .and_then(|number| number.parse().ok())
+                    .filter(|number| (1..=24).contains(number))
+                    .map(KeyCode::F)
+                    .ok_or_else(invalid)?,
+            }
+        }
+    };
+    Ok(normalize(code, modifiers))
+}
+
+// Name of a key in the form bindings files use, e.g. "Ctrl+w" or "F4"
+fn key_name((code, modifiers): Key) -> String {
+    let mut name = String::new();
+    for (modifier, prefix) in [
+        (KeyModifiers::CONTROL, "Ctrl+"),
+        (KeyModifiers::ALT, "Alt+"),
+        (KeyModifiers::SHIFT, "Shift+"),
+    ] {
+        if modifiers.contains(modifier) {
+            name.push_str(prefix);
+        }
+    }
+    match code {
+        KeyCode::Char(' ') => name.push_str("Space"),
+        KeyCode::Char(c) => name.push(c),
+        KeyCode::F(number) => name.push_str(&format!("F{}", number)),
+        // The remaining named keys print as they are spelled in bindings files
+        other => name.push_str(&format!("{:?}", other)),
+    }
+    name
+}
+
+// Fold the different ways terminals report shifted keys into one form:
+// letters become lowercase with Shift, and other characters carry their
+// shift in the character itself. Modifiers bindings can't name are dropped.
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> Key {
+    let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::SHIFT | KeyModifiers::ALT);
+    let code = match code {
+        KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+            if c.is_ascii_uppercase() {
+                modifiers |= KeyModifiers::SHIFT;
+            }
+            KeyCode::Char(c.to_ascii_lowercase())
+        }
+        KeyCode::Char(c) => {
+            modifiers -= KeyModifiers::SHIFT;
+            KeyCode::Char(c)
+        }
+        other => other,
+    };
+    (code, modifiers)
+}
+
+// Parse a JSON object, or with `ron` a RON struct or map, whose values are
+// strings or arrays of strings, the only shapes a bindings file uses.
+// Entries are returned in file order.
+fn parse_object(text: &str, ron: bool) -> Result<Vec<(String, Vec<String>)>, RaycastError> {
+    let mut reader = Reader {
+        chars: text.chars().peekable(),
+        line: 1,
+        ron,
+    };
+    let mut entries = Vec::new();
+
+    // A RON struct may be named, as in `Bindings(...)`, and names its fields
+    // without quotes
+    let is_struct = ron && {
+        reader.identifier();
+        reader.eat('(')
+    };
+    let close = if is_struct {
+        ')'
+    } else {
+        reader.expect('{')?;
+        '}'
+    };
+    if !reader.eat(close) {
+        loop {
+            let name = if is_struct { reader.field()? } else { reader.string()? };
+            reader.expect(':')?;
+            entries.push((name, reader.strings()?));
+            if reader.eat(close) {
+                break;
+            }
+            reader.expect(',')?;
+            if reader.trailing(close) {
+                break;
+            }
+        }
+    }
+    reader.skip_whitespace();
+    if reader.chars.next().is_some() {
+        return Err(reader.error("end of file"));
+    }
+    Ok(entries)
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize, // 1-based, for error messages
+    ron: bool, // Allow RON's comments, trailing commas and field names
+}
+
+impl Reader<'_> {
+    fn error(&self, expected: &str) -> RaycastError {
+        RaycastError::Config(format!("key bindings line {}: expected {}", self.line, expected))
+    }
+
+    // Skip whitespace and, in RON, comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.chars.peek() {
+                Some(&c) if c.is_whitespace() => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    self.chars.next();
+                }
+                Some('/') if self.ron => {
+                    let mut ahead = self.chars.clone();
+                    ahead.next();
+                    let end = match ahead.next() {
+                        Some('/') => "\n",
+                        Some('*') => "*/",
+                        _ => return,
+                    };
+                    self.chars.nth(1);
+                    self.skip_past(end);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // Skip to just after the end of a comment, or to the end of the file
+    fn skip_past(&mut self, end: &str) {
+        let mut last = '\0';
+        for c in self.chars.by_ref() {
+            if c == '\n' {
+                self.line += 1;
+            }
+            if (end == "\n" && c == '\n') || (end == "*/" && last == '*' && c == '/') {
+                return;
+            }
+            last = c;
+        }
+    }
+
+    // Consume `c` if it is the next non-whitespace character
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&c).is_some()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), RaycastError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", c)))
+        }
+    }
+
+    // Consume `close` after a comma if trailing commas are allowed and it
+    // comes next
+    fn trailing(&mut self, close: char) -> bool {
+        self.ron && self.eat(close)
+    }
+
+    // Letters, digits and underscores, possibly none
+    fn identifier(&mut self) -> String {
+        self.skip_whitespace();
+        let mut identifier = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            identifier.push(c);
+        }
+        identifier
+    }
+
+    // A RON struct field name
+    fn field(&mut self) -> Result<String, RaycastError> {
+        Some(self.identifier())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| self.error("an action name"))
+    }
+
+    fn string(&mut self) -> Result<String, RaycastError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.chars.next() {
+                    Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                    _ => return Err(self.error("one of \\\" \\\\ \\/ after '\\'")),
+                },
+                Some('\n') | None => return Err(self.error("closing '\"'")),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    // A string, or an array of strings
+    fn strings(&mut self) -> Result<Vec<String>, RaycastError> {
+        if !self.eat('[') {
+            return Ok(vec![self.string()?]);
+        }
+        let mut strings = Vec::new();
+        if self.eat(']') {
+            return Ok(strings);
+        }
+        loop {
+            strings.push(self.string()?);
+            if self.eat(']') {
+                return Ok(strings);
+            }
+            self.expect(',')?;
+            if self.trailing(']') {
+                return Ok(strings);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ron_bindings_match_json_ones() {
+        let json = KeyBindings::parse(r#"{ "forward": ["Ctrl+w", "Up"], "quit": "Shift+q" }"#).unwrap();
+        let ron = KeyBindings::parse_ron(
+            "Bindings(\n    // Arrows too\n    forward: [\"Ctrl+w\", \"Up\",],\n    /* capital */ quit: \"Shift+q\",\n)\n",
+        )
+        .unwrap();
+        assert_eq!(ron.keys, json.keys);
+        let map = KeyBindings::parse_ron(r#"{ "quit": "Shift+q", }"#).unwrap();
+        assert_eq!(map.keys, KeyBindings::parse(r#"{ "quit": "Shift+q" }"#).unwrap().keys);
+        assert_eq!(ron.action(KeyCode::Char('w'), KeyModifiers::CONTROL), Some(Action::Forward));
+        assert_eq!(ron.action(KeyCode::Char('w'), KeyModifiers::NONE), None);
+    }
+
+    #[test]
+    fn json_bindings_reject_ron_syntax() {
+        assert!(KeyBindings::parse(r#"{ "quit": "q", }"#).is_err());
+        assert!(KeyBindings::parse("(quit: \"q\")").is_err());
+        assert!(KeyBindings::parse_ron("(jump: \"q\")").is_err());
+    }
+}
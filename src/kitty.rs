@@ -0,0 +1,75 @@
+// Kitty graphics protocol: frames sent to the terminal as RGB images that
+// it scales to cover the screen, on terminals that support it (kitty,
+// WezTerm). Each frame replaces the last one's image in place.
+
+use std::fmt::Write as _;
+
+use crate::palette::Rgb;
+
+pub const SCALE: usize = 2; // Screen pixels per rendered pixel each way, to keep frames fast
+const IMAGE_ID: u32 = 1; // Every frame reuses one image and placement, so old frames are dropped
+const CHUNK_SIZE: usize = 4096; // Most base64 data sent in one escape sequence
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Whether the environment names a terminal known to support the protocol
+pub fn detect() -> bool {
+    std::env::var("TERM").is_ok_and(|term| term == "xterm-kitty")
+        || std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || std::env::var("TERM_PROGRAM").is_ok_and(|program| program == "WezTerm")
+}
+
+// Encode a frame buffer as an image covering the character cells it was
+// rendered for. The cursor is left on the image's last row, as it is after
+// a frame of text rows.
+pub fn encode(frame_buffer: &[Vec<Rgb>], cell_width: usize, cell_height: usize) -> String {
+    let height = frame_buffer.len();
+    let width = frame_buffer.first().map_or(0, Vec::len);
+    let columns = (width * SCALE).div_ceil(cell_width.max(1));
+    let rows = (height * SCALE).div_ceil(cell_height.max(1));
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for &(r, g, b) in frame_buffer.iter().flatten() {
+        pixels.extend_from_slice(&[r, g, b]);
+    }
+    let data = base64(&pixels);
+
+    let mut output = String::with_capacity(data.len() + data.len() / CHUNK_SIZE * 8 + 64);
+    // The cursor stays put (C=1) and replies are suppressed (q=2)
+    let _ = write!(
+        output,
+        "\x1b_Ga=T,f=24,s={},v={},c={},r={},i={},p={},C=1,q=2",
+        width, height, columns, rows, IMAGE_ID, IMAGE_ID
+    );
+    let mut chunks: Vec<&[u8]> = data.as_bytes().chunks(CHUNK_SIZE).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    for (index, chunk) in chunks.iter().enumerate() {
+        if index > 0 {
+            output.push_str("\x1b_G");
+        }
+        let more = if index + 1 < chunks.len() { 1 } else { 0 };
+        let _ = write!(output, "{}m={};", if index > 0 { "" } else { "," }, more);
+        output.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        output.push_str("\x1b\\");
+    }
+    if rows > 1 {
+        let _ = write!(output, "\x1b[{}B", rows - 1);
+    }
+    output
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let value = group.iter().enumerate().fold(0u32, |value, (i, &byte)| value | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                output.push(BASE64[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
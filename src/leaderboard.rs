@@ -0,0 +1,80 @@
+// Best completion time for each map, kept in a small text file so records
+// survive between runs. Maps are identified by a hash of their cells, so an
+// edited map starts a fresh record. Each line of the file holds a map hash in
+// hex and the best time in seconds.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::RaycastError;
+
+pub const DEFAULT_LEADERBOARD_FILE: &str = "best-times.txt";
+
+pub struct Leaderboard {
+    path: PathBuf,
+    best: HashMap<u64, f64>, // Seconds, by map hash
+}
+
+impl Leaderboard {
+    // Read the records from `path`. A missing file is an empty leaderboard;
+    // lines that can't be parsed are skipped with a warning rather than
+    // failing the whole file.
+    pub fn load(path: &Path) -> Self {
+        let mut best = HashMap::new();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                eprintln!("warning: could not read {}: {}", path.display(), err);
+                String::new()
+            }
+        };
+
+        for (index, line) in text.lines().enumerate() {
+            let record = line.split_once(' ').and_then(|(hash, seconds)| {
+                let hash = u64::from_str_radix(hash, 16).ok()?;
+                let seconds: f64 = seconds.trim().parse().ok()?;
+                (seconds.is_finite() && seconds > 0.0).then_some((hash, seconds))
+            });
+            match record {
+                Some((hash, seconds)) => {
+                    best.insert(hash, seconds);
+                }
+                None if line.trim().is_empty() => {}
+                None => eprintln!("warning: {} line {}: ignoring malformed record", path.display(), index + 1),
+            }
+        }
+
+        Leaderboard {
+            path: path.to_path_buf(),
+            best,
+        }
+    }
+
+    pub fn best(&self, map_hash: u64) -> Option<f64> {
+        self.best.get(&map_hash).copied()
+    }
+
+    // Record a completion time. Returns true, after saving the file, if it
+    // beats the previous best for the map.
+    pub fn record(&mut self, map_hash: u64, seconds: f64) -> Result<bool, RaycastError> {
+        if self.best(map_hash).is_some_and(|best| best <= seconds) {
+            return Ok(false);
+        }
+        self.best.insert(map_hash, seconds);
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<(), RaycastError> {
+        let mut records: Vec<_> = self.best.iter().collect();
+        records.sort_by_key(|&(hash, _)| *hash);
+        let text: String = records
+            .into_iter()
+            .map(|(hash, seconds)| format!("{:016x} {:.3}\n", hash, seconds))
+            .collect();
+        std::fs::write(&self.path, text)
+            .map_err(|err| RaycastError::Save(format!("{}: {}", self.path.display(), err)))
+    }
+}
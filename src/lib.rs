@@ -0,0 +1,45 @@
+// Terminal raycasting engine. The `raycast-tui` binary drives it from the
+// keyboard and mouse; the pieces here can also be used on their own.
+
+pub mod analog;
+pub mod audio;
+pub mod automap;
+pub mod bench;
+pub mod campaign;
+pub mod daycycle;
+pub mod destructible;
+pub mod door;
+pub mod editor;
+pub mod effect;
+pub mod enemy;
+pub mod environment;
+pub mod error;
+pub mod event;
+pub mod generate;
+pub mod hazard;
+pub mod keybindings;
+pub mod kitty;
+pub mod leaderboard;
+pub mod light;
+pub mod map;
+pub mod mapfile;
+pub mod mirror;
+pub mod palette;
+pub mod pushwall;
+pub mod quality;
+pub mod raycaster;
+pub mod render;
+pub mod sixel;
+pub mod sprite;
+pub mod svg;
+pub mod terminfo;
+pub mod texture;
+pub mod viewmodel;
+pub mod watch;
+pub mod window;
+pub mod wolf3d;
+pub mod world;
+
+pub use error::RaycastError;
+pub use event::GameEvent;
+pub use raycaster::{Player, RayHit, Raycaster, Sprite};
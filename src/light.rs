@@ -0,0 +1,37 @@
+// Point lights: lamps placed in a map, or a lantern carried by the player,
+// that brighten the walls, floors, ceilings and sprites around them on top
+// of the cells' light levels. They are worked out wherever a surface is
+// drawn, so a light that moves changes the view from one frame to the next.
+
+pub const DEFAULT_RADIUS: f64 = 4.0;
+pub const DEFAULT_INTENSITY: f64 = 0.8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64, // Distance in cells at which the light has faded out
+    pub intensity: f64, // Brightness added right at the light; 1 is a fully lit cell
+}
+
+impl PointLight {
+    pub fn new(x: f64, y: f64) -> Self {
+        PointLight {
+            x,
+            y,
+            radius: DEFAULT_RADIUS,
+            intensity: DEFAULT_INTENSITY,
+        }
+    }
+
+    // Brightness added at a world point, falling off smoothly to nothing at
+    // the radius
+    pub fn brightness_at(&self, x: f64, y: f64) -> f64 {
+        let distance_sq = (x - self.x).powi(2) + (y - self.y).powi(2);
+        if distance_sq >= self.radius * self.radius {
+            return 0.0;
+        }
+        let falloff = 1.0 - distance_sq.sqrt() / self.radius;
+        self.intensity * falloff * falloff
+    }
+}
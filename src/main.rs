@@ -1,378 +1,1337 @@
 use crossterm::{
-    cursor::{Hide, Show},
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
-    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, size},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::io::{self, stdout, Write};
+use std::io::{self, stdout, IsTerminal, Read, Write};
+use std::process::ExitCode;
 use std::time::{Duration, Instant};
 
-const MAP_WIDTH: usize = 24;
-const MAP_HEIGHT: usize = 24;
-const FOV: f64 = 0.66; // Field of view
-const MOVE_SPEED: f64 = 0.05;
-const ROTATION_SPEED: f64 = 0.03;
+use raycast_tui::analog::ResponseCurve;
+use raycast_tui::audio::AudioCues;
+use raycast_tui::bench::{self, DEFAULT_SWEEP_SIZES};
+use raycast_tui::campaign::Campaign;
+use raycast_tui::daycycle::{DayCycle, DEFAULT_DAY_LENGTH, DEFAULT_START_HOUR};
+use raycast_tui::automap::Automap;
+use raycast_tui::editor::Editor;
+use raycast_tui::effect::ScreenEffect;
+use raycast_tui::environment::Environment;
+use raycast_tui::generate::{self, Generator};
+use raycast_tui::keybindings::{Action, KeyBindings};
+use raycast_tui::leaderboard::{Leaderboard, DEFAULT_LEADERBOARD_FILE};
+use raycast_tui::light::PointLight;
+use raycast_tui::map::{Map, MAP_HEIGHT, MAP_WIDTH};
+use raycast_tui::mapfile::MapFile;
+use raycast_tui::quality::AdaptiveQuality;
+use raycast_tui::raycaster::{
+    light_direction, SplitView, DEFAULT_BACKGROUND_COLOR, DEFAULT_CONTACT_DAMAGE, DEFAULT_HEALTH,
+    DEFAULT_HEAD_BOB, DEFAULT_LETTERBOX_COLOR, DEFAULT_LIGHT_ANGLE, FOV,
+};
+use raycast_tui::render::{self, ColorDepth, RenderMode};
+use raycast_tui::svg;
+use raycast_tui::texture::{GeneratedTexture, ImageTexture};
+use raycast_tui::watch::FileWatcher;
+use raycast_tui::wolf3d;
+use raycast_tui::world::World;
+use raycast_tui::{GameEvent, Player, RaycastError, Raycaster};
 
-// Map: 1 = wall, 0 = empty space
-const MAP: &[&str] = &[
-    "111111111111111111111111",
-    "100000000011000000000001",
-    "100000000011000000000001",
-    "100000000011000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "100000000000000000000001",
-    "111111111111111111111111",
-];
+const BENCH_DURATION: Duration = Duration::from_secs(1); // Time spent rendering each size
+const DAY_SCRUB_HOURS: f64 = 0.5; // Time of day skipped per [ or ] press
+const HELP_WIDTH: usize = 64; // Widest the help panel gets, in columns
+const HELP_KEYS_COLUMN: usize = 26; // Where key names start on each help line
+const RECORD_NOTICE_TIME: Duration = Duration::from_secs(5); // How long "New record!" stays up
+const RELOAD_NOTICE_TIME: Duration = Duration::from_secs(3); // How long a map reload is reported
+const INTERSTITIAL_TIME: Duration = Duration::from_secs(2); // How long the level card shows between levels
+const LEVEL_FADE_TIME: Duration = Duration::from_millis(600); // Fade to black after finishing a level
+const HELP_COLORS: &str = "\x1b[38;5;252;48;5;236m"; // Light gray on dark gray
 
-struct Player {
-    x: f64,
-    y: f64,
-    angle: f64,
+struct Options {
+    mouse: bool,
+    watch: bool, // Reload the level when its map file changes
+    info: bool, // Print map statistics before starting
+    svg_file: Option<String>, // Write the map as an SVG drawing here before starting
+    play: bool, // Start the game after printing info or writing the SVG
+    grid: bool, // Cell-by-cell movement with 90° turns
+    spawn_point: Option<usize>, // Which of the map's spawn points to start at, from 1
+    spawn: Option<(f64, f64)>, // Starting position, overriding the map's spawn
+    facing: Option<f64>, // Starting angle in degrees
+    inline: bool, // Render in the normal screen instead of the alternate screen
+    bells: bool, // Ring the terminal bell when sprites are near but off-screen
+    fixed_resolution: Option<(usize, usize)>, // Render at this pixel size and scale to fit
+    letterbox_color: u8,
+    fog: Environment, // Fog range and color for maps that don't set their own
+    background_color: u8, // Color where rays leave the map without hitting a wall
+    ramps: bool, // Draw sloped and raised floor regions
+    textures: bool, // Procedural wall textures
+    light_angle: Option<f64>, // Direction of the light shading walls, in degrees
+    lantern: bool, // Carry a point light around with the player
+    flashlight: bool, // Start with the flashlight on
+    raised_ceilings: bool, // Draw the built-in map's raised ceilings
+    minimap: bool, // Start with the minimap shown
+    hud: bool, // Start with the status bar shown
+    fps_counter: bool, // Start with the frame counter shown
+    weapon: bool, // Draw the weapon held in front of the player
+    head_bob: f64, // Head-bob height as a fraction of the view's height
+    texture_files: Vec<(u8, String)>, // Image textures as (material, path)
+    floor_texture_file: Option<String>, // Image or generated texture across the floor
+    ceiling_texture_file: Option<String>,
+    render_mode: Option<RenderMode>, // How frames are drawn; detected if not given
+    color_depth: Option<ColorDepth>, // Colors sent to the terminal; detected if not given
+    mirror: bool, // Mirror the view left to right
+    flip: bool, // Turn the view upside down
+    bench_sweep: bool, // Benchmark rendering at several sizes and exit
+    bench_sizes: Vec<(usize, usize)>,
+    health: f64, // Starting and maximum player health
+    damage: f64, // Health lost per enemy contact
+    auto_quality: bool, // Lower render quality automatically when frames run slow
+    split: bool, // Side-by-side comparison view
+    split_fov: f64,
+    split_camera: Option<(f64, f64, f64)>, // Fixed (x, y, angle) for the right half
+    max_dda_steps: Option<usize>, // Cells a ray may cross before it counts as a miss
+    response_curve: ResponseCurve, // Shaping of analog stick deflections into speed
+    keys: Option<String>, // Key bindings file
+    map_files: Vec<String>, // Levels to play in order instead of the built-in maps; `-` reads stdin
+    stdin_map: Option<String>, // Text of the map piped in, read before taking over the terminal
+    edit_file: Option<String>, // Map opened in the editor by the `edit` subcommand
+    campaign_file: Option<String>, // Campaign manifest listing the levels instead
+    campaign: Option<Campaign>, // Loaded from campaign_file when the game starts
+    wolf_level: usize, // Level to import from a Wolfenstein 3D GAMEMAPS file, 0-based
+    generator: Option<Generator>, // Build a level procedurally instead of loading one
+    world: bool, // Roam an endless world streamed in chunks
+    generate_size: (usize, usize),
+    seed: Option<u64>, // Generator seed; taken from the clock if not given
+    day_length: Option<f64>, // Seconds per day of the time-of-day cycle
+    time_of_day: Option<f64>, // Starting hour of the cycle
+    day_paused: bool, // Start with the clock stopped
+    dither: bool, // Ordered dithering of color gradients
+    swap_strafe_turn: bool, // Start with the strafe and turn keys swapped
 }
 
-struct Raycaster {
-    player: Player,
-    last_width: usize,
-    last_height: usize,
+// F4 debug prompt for teleporting to typed coordinates
+struct TeleportPrompt {
+    input: String,
+    error: Option<String>, // Problem with the last submitted input
 }
 
-impl Raycaster {
-    fn new() -> Self {
-        Raycaster {
-            player: Player {
-                x: 2.0,
-                y: 2.0,
-                angle: 0.0,
-            },
-            last_width: 0,
-            last_height: 0,
-        }
-    }
+// Card shown between levels, over the dimmed view of the next one
+struct Interstitial {
+    lines: Vec<String>,
+    until: Instant,
+}
 
-    fn get_map_value(&self, x: usize, y: usize) -> u8 {
-        if x < MAP_WIDTH && y < MAP_HEIGHT {
-            MAP[y].as_bytes()[x] - b'0'
-        } else {
-            1
-        }
-    }
+// A finished level fading to black before the next one loads
+struct LevelEnd {
+    record: Result<bool, RaycastError>, // Whether the run was a new best
+    until: Instant,
+}
 
-    fn cast_ray(&self, ray_angle: f64) -> f64 {
-        let sin = ray_angle.sin();
-        let cos = ray_angle.cos();
-        
-        let x = self.player.x;
-        let y = self.player.y;
-        
-        let delta_x = if cos.abs() < 0.0001 { 1e30 } else { (1.0 / cos).abs() };
-        let delta_y = if sin.abs() < 0.0001 { 1e30 } else { (1.0 / sin).abs() };
-        
-        let step_x = if cos < 0.0 { -1 } else { 1 };
-        let step_y = if sin < 0.0 { -1 } else { 1 };
-        
-        let mut map_x = x.floor() as i32;
-        let mut map_y = y.floor() as i32;
-        
-        let mut side_dist_x = if cos < 0.0 {
-            (x - map_x as f64) * delta_x
-        } else {
-            (map_x as f64 + 1.0 - x) * delta_x
-        };
-        let mut side_dist_y = if sin < 0.0 {
-            (y - map_y as f64) * delta_y
-        } else {
-            (map_y as f64 + 1.0 - y) * delta_y
+// Help overlay listing the current key bindings and toggle states
+struct HelpOverlay {
+    scroll: usize, // First line shown when the list doesn't fit
+}
+
+// How the game loop ended
+enum Outcome {
+    Quit,
+    GameOver,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(args: I) -> Result<Self, RaycastError> {
+        let mut options = Options {
+            mouse: true,
+            watch: true,
+            info: false,
+            svg_file: None,
+            play: false,
+            grid: false,
+            spawn_point: None,
+            spawn: None,
+            facing: None,
+            inline: false,
+            bells: false,
+            fixed_resolution: None,
+            letterbox_color: DEFAULT_LETTERBOX_COLOR,
+            fog: Environment::default(),
+            background_color: DEFAULT_BACKGROUND_COLOR,
+            ramps: false,
+            textures: true,
+            light_angle: Some(DEFAULT_LIGHT_ANGLE),
+            lantern: false,
+            flashlight: false,
+            raised_ceilings: false,
+            minimap: false,
+            hud: false,
+            fps_counter: false,
+            weapon: false,
+            head_bob: DEFAULT_HEAD_BOB,
+            texture_files: Vec::new(),
+            floor_texture_file: None,
+            ceiling_texture_file: None,
+            render_mode: None,
+            color_depth: None,
+            mirror: false,
+            flip: false,
+            bench_sweep: false,
+            bench_sizes: DEFAULT_SWEEP_SIZES.to_vec(),
+            health: DEFAULT_HEALTH,
+            damage: DEFAULT_CONTACT_DAMAGE,
+            auto_quality: false,
+            split: false,
+            split_fov: FOV,
+            split_camera: None,
+            max_dda_steps: None,
+            response_curve: ResponseCurve::Linear,
+            keys: None,
+            map_files: Vec::new(),
+            stdin_map: None,
+            edit_file: None,
+            campaign_file: None,
+            campaign: None,
+            wolf_level: 0,
+            generator: None,
+            world: false,
+            generate_size: (MAP_WIDTH, MAP_HEIGHT),
+            seed: None,
+            day_length: None,
+            time_of_day: None,
+            day_paused: false,
+            dither: false,
+            swap_strafe_turn: false,
         };
         
-        let mut hit = false;
-        let mut side = false;
-        
-        while !hit {
-            if side_dist_x < side_dist_y {
-                side_dist_x += delta_x;
-                map_x += step_x;
-                side = false;
-            } else {
-                side_dist_y += delta_y;
-                map_y += step_y;
-                side = true;
-            }
-            
-            if map_x < 0 || map_x >= MAP_WIDTH as i32 || map_y < 0 || map_y >= MAP_HEIGHT as i32 {
-                break;
-            }
-            
-            if self.get_map_value(map_x as usize, map_y as usize) == 1 {
-                hit = true;
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| RaycastError::Config(format!("{} requires a value", name)))
+            };
+            match arg.as_str() {
+                "--no-mouse" => options.mouse = false,
+                "--no-watch" => options.watch = false,
+                "--info" => options.info = true,
+                "--export-svg" => options.svg_file = Some(value("--export-svg")?),
+                "--play" => options.play = true,
+                "--grid" => options.grid = true,
+                "--spawn-point" => {
+                    let value = value("--spawn-point")?;
+                    let index = value
+                        .parse()
+                        .ok()
+                        .filter(|&index| index >= 1)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid spawn point (expected 1 or more): {}", value)))?;
+                    options.spawn_point = Some(index);
+                }
+                "--spawn" => {
+                    let value = value("--spawn")?;
+                    let spawn = value
+                        .split_once(',')
+                        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+                        .filter(|&(x, y): &(f64, f64)| x.is_finite() && y.is_finite())
+                        .ok_or_else(|| RaycastError::Config(format!("invalid spawn (expected X,Y): {}", value)))?;
+                    options.spawn = Some(spawn);
+                }
+                "--facing" => {
+                    let value = value("--facing")?;
+                    let degrees = value
+                        .parse()
+                        .ok()
+                        .filter(|degrees: &f64| degrees.is_finite())
+                        .ok_or_else(|| RaycastError::Config(format!("invalid angle: {}", value)))?;
+                    options.facing = Some(degrees);
+                }
+                "--inline" => options.inline = true,
+                "--bells" => options.bells = true,
+                "--ramps" => options.ramps = true,
+                "--auto-quality" => options.auto_quality = true,
+                "--split" => options.split = true,
+                "--split-fov" => {
+                    let value = value("--split-fov")?;
+                    let degrees = value
+                        .parse()
+                        .ok()
+                        .filter(|degrees: &f64| (1.0..180.0).contains(degrees))
+                        .ok_or_else(|| RaycastError::Config(format!("invalid field of view: {}", value)))?;
+                    options.split_fov = (degrees / 2.0).to_radians().tan();
+                }
+                "--split-camera" => {
+                    let value = value("--split-camera")?;
+                    let parts = value
+                        .split(',')
+                        .map(|part| part.parse().ok().filter(|part: &f64| part.is_finite()))
+                        .collect::<Option<Vec<f64>>>();
+                    let Some(&[x, y, angle]) = parts.as_deref() else {
+                        return Err(RaycastError::Config(format!(
+                            "invalid camera (expected X,Y,ANGLE): {}",
+                            value
+                        )));
+                    };
+                    options.split_camera = Some((x, y, angle.to_radians()));
+                }
+                "--no-textures" => options.textures = false,
+                "--lantern" => options.lantern = true,
+                "--flashlight" => options.flashlight = true,
+                "--raised-ceilings" => options.raised_ceilings = true,
+                "--minimap" => options.minimap = true,
+                "--hud" => options.hud = true,
+                "--fps" => options.fps_counter = true,
+                "--weapon" => options.weapon = true,
+                "--no-shading" => options.light_angle = None,
+                "--light-angle" => {
+                    let value = value("--light-angle")?;
+                    let degrees = value
+                        .parse()
+                        .ok()
+                        .filter(|degrees: &f64| degrees.is_finite())
+                        .ok_or_else(|| RaycastError::Config(format!("invalid angle: {}", value)))?;
+                    options.light_angle = Some(degrees);
+                }
+                "--texture" => {
+                    let value = value("--texture")?;
+                    let (material, path) = value
+                        .split_once('=')
+                        .and_then(|(material, path)| Some((material.parse::<u8>().ok()?, path)))
+                        .filter(|&(material, _)| (1..=9).contains(&material))
+                        .ok_or_else(|| {
+                            RaycastError::Config(format!("invalid texture (expected MATERIAL=PATH): {}", value))
+                        })?;
+                    if cfg!(not(feature = "image-textures")) && GeneratedTexture::from_name(path).is_none() {
+                        return Err(RaycastError::Config(
+                            "--texture with an image file requires building with the image-textures feature".to_string(),
+                        ));
+                    }
+                    options.texture_files.push((material, path.to_string()));
+                }
+                "--floor-texture" | "--ceiling-texture" => {
+                    let path = value(&arg)?;
+                    if cfg!(not(feature = "image-textures")) && GeneratedTexture::from_name(&path).is_none() {
+                        return Err(RaycastError::Config(format!(
+                            "{} with an image file requires building with the image-textures feature",
+                            arg
+                        )));
+                    }
+                    if arg == "--floor-texture" {
+                        options.floor_texture_file = Some(path);
+                    } else {
+                        options.ceiling_texture_file = Some(path);
+                    }
+                }
+                "--keys" => options.keys = Some(value("--keys")?),
+                "--day-cycle" => {
+                    let value = value("--day-cycle")?;
+                    let seconds = value
+                        .parse()
+                        .ok()
+                        .filter(|&seconds: &f64| seconds > 0.0)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid day length: {}", value)))?;
+                    options.day_length = Some(seconds);
+                }
+                "--time-of-day" => {
+                    let value = value("--time-of-day")?;
+                    let hour = value
+                        .parse()
+                        .ok()
+                        .filter(|hour: &f64| (0.0..24.0).contains(hour))
+                        .ok_or_else(|| RaycastError::Config(format!("invalid hour (expected 0-24): {}", value)))?;
+                    options.time_of_day = Some(hour);
+                }
+                "--wolf-level" => {
+                    let value = value("--wolf-level")?;
+                    options.wolf_level = value
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|level| level.checked_sub(1))
+                        .ok_or_else(|| RaycastError::Config(format!("invalid level: {}", value)))?;
+                }
+                "--campaign" => options.campaign_file = Some(value("--campaign")?),
+                "--generate" => options.generator = Some(value("--generate")?.parse()?),
+                "--world" => options.world = true,
+                "--size" => {
+                    let value = value("--size")?;
+                    options.generate_size =
+                        parse_size(&value).ok_or_else(|| RaycastError::Config(format!("invalid size: {}", value)))?;
+                }
+                "--seed" => {
+                    let value = value("--seed")?;
+                    options.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| RaycastError::Config(format!("invalid seed: {}", value)))?,
+                    );
+                }
+                "--day-paused" => options.day_paused = true,
+                "--dither" => options.dither = true,
+                "--swap-strafe-turn" => options.swap_strafe_turn = true,
+                "--fixed-res" => {
+                    let value = value("--fixed-res")?;
+                    let resolution = parse_size(&value)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid resolution: {}", value)))?;
+                    options.fixed_resolution = Some(resolution);
+                }
+                "--render-mode" => {
+                    options.render_mode = match value("--render-mode")?.as_str() {
+                        "auto" => None,
+                        mode => Some(mode.parse()?),
+                    };
+                }
+                "--ascii" => options.render_mode = Some(RenderMode::Ascii),
+                "--colors" => {
+                    options.color_depth = match value("--colors")?.as_str() {
+                        "auto" => None,
+                        depth => Some(depth.parse()?),
+                    };
+                }
+                "--mirror" => options.mirror = true,
+                "--flip" => options.flip = true,
+                "--bench-sweep" => options.bench_sweep = true,
+                "--bench-sizes" => {
+                    let value = value("--bench-sizes")?;
+                    options.bench_sizes = value
+                        .split(',')
+                        .map(|size| {
+                            parse_size(size).ok_or_else(|| RaycastError::Config(format!("invalid size: {}", size)))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                "--letterbox-color" => {
+                    let value = value("--letterbox-color")?;
+                    options.letterbox_color = value
+                        .parse()
+                        .map_err(|_| RaycastError::Config(format!("invalid color: {}", value)))?;
+                }
+                "--fog-start" | "--fog-end" => {
+                    let value = value(&arg)?;
+                    let distance = value
+                        .parse()
+                        .ok()
+                        .filter(|distance: &f64| distance.is_finite() && *distance >= 0.0)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid fog distance: {}", value)))?;
+                    if arg == "--fog-start" {
+                        options.fog.fog_start = distance;
+                    } else {
+                        options.fog.fog_end = distance;
+                    }
+                }
+                "--fog-color" => {
+                    let value = value("--fog-color")?;
+                    options.fog.fog_color = value
+                        .parse()
+                        .map_err(|_| RaycastError::Config(format!("invalid color: {}", value)))?;
+                }
+                "--health" => {
+                    let value = value("--health")?;
+                    options.health = value
+                        .parse()
+                        .ok()
+                        .filter(|&health: &f64| health > 0.0)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid health: {}", value)))?;
+                }
+                "--head-bob" => {
+                    let value = value("--head-bob")?;
+                    options.head_bob = value
+                        .parse()
+                        .ok()
+                        .filter(|amount: &f64| (0.0..=0.5).contains(amount))
+                        .ok_or_else(|| RaycastError::Config(format!("invalid head-bob: {}", value)))?;
+                }
+                "--no-head-bob" => options.head_bob = 0.0,
+                "--damage" => {
+                    let value = value("--damage")?;
+                    options.damage = value
+                        .parse()
+                        .ok()
+                        .filter(|&damage: &f64| damage >= 0.0)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid damage: {}", value)))?;
+                }
+                "--background-color" => {
+                    let value = value("--background-color")?;
+                    options.background_color = value
+                        .parse()
+                        .map_err(|_| RaycastError::Config(format!("invalid color: {}", value)))?;
+                }
+                "--max-dda-steps" => {
+                    let value = value("--max-dda-steps")?;
+                    let steps = value
+                        .parse()
+                        .ok()
+                        .filter(|&steps: &usize| steps > 0)
+                        .ok_or_else(|| RaycastError::Config(format!("invalid step limit: {}", value)))?;
+                    options.max_dda_steps = Some(steps);
+                }
+                "--response-curve" => options.response_curve = value("--response-curve")?.parse()?,
+                // `edit MAP` opens a map in the editor, creating it if it
+                // doesn't exist
+                "edit" if options.edit_file.is_none() && options.map_files.is_empty() => {
+                    let path = value("edit")
+                        .ok()
+                        .filter(|path| path != "-")
+                        .ok_or_else(|| RaycastError::Config("edit requires a map file".to_string()))?;
+                    let editable = !path.ends_with(".tmx") && !path.ends_with(".png");
+                    if !editable || wolf3d::is_gamemaps(std::path::Path::new(&path)) {
+                        return Err(RaycastError::Config(format!(
+                            "{}: only plain text and .toml maps can be edited",
+                            path
+                        )));
+                    }
+                    options.edit_file = Some(path);
+                }
+                _ if arg == "-" || !arg.starts_with('-') => options.map_files.push(arg),
+                _ => return Err(RaycastError::Config(format!("unknown argument: {}", arg))),
             }
         }
+        let sources = [
+            options.generator.is_some(),
+            !options.map_files.is_empty(),
+            options.campaign_file.is_some(),
+            options.edit_file.is_some(),
+            options.world,
+        ];
+        if sources.into_iter().filter(|&given| given).count() > 1 {
+            return Err(RaycastError::Config(
+                "only one of map files, --campaign, --generate, --world and edit can be used".to_string(),
+            ));
+        }
+        if options.fog.fog_end <= options.fog.fog_start && options.fog.fog_end != 0.0 {
+            return Err(RaycastError::Config(format!(
+                "--fog-end ({}) must be farther than --fog-start ({})",
+                options.fog.fog_end, options.fog.fog_start
+            )));
+        }
         
-        let perp_wall_dist = if !side {
-            side_dist_x - delta_x
-        } else {
-            side_dist_y - delta_y
-        };
-        
-        perp_wall_dist
+        Ok(options)
     }
+}
 
-    fn render(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
-        let (screen_width, screen_height) = size()?;
-        let screen_width = screen_width as usize;
-        let screen_height = screen_height as usize;
-        
-        // Clear screen if size changed (handles terminal resize)
-        if screen_width != self.last_width || screen_height != self.last_height {
-            execute!(stdout, Clear(ClearType::All))?;
-            self.last_width = screen_width;
-            self.last_height = screen_height;
+// Load or generate textures for their materials and the floor and ceiling.
+// A texture that fails to load is reported and keeps the built-in look.
+fn load_textures(raycaster: &mut Raycaster, options: &Options) {
+    let open = |path: &String| match ImageTexture::open(std::path::Path::new(path)) {
+        Ok(texture) => Some(texture),
+        Err(err) => {
+            eprintln!("warning: {}, using the built-in colors", err);
+            None
         }
-        
-        // Build frame buffer with double vertical resolution (2 pixels per character)
-        let double_height = screen_height * 2;
-        let mut frame_buffer = vec![vec![0u8; screen_width]; double_height];
-        
-        // Calculate all columns
-        for x in 0..screen_width {
-            let camera_x = 2.0 * x as f64 / screen_width as f64 - 1.0;
-            let ray_angle = self.player.angle + (camera_x * FOV).atan();
-            
-            let perp_wall_dist = self.cast_ray(ray_angle);
-            
-            // Use double height for calculations
-            let line_height = (double_height as f64 / perp_wall_dist.max(0.1)) as usize;
-            let draw_start = ((double_height as i32 - line_height as i32) / 2).max(0);
-            let draw_end = ((double_height as i32 + line_height as i32) / 2).min(double_height as i32);
-            
-            // Get 256-color code for wall based on distance
-            let wall_color = self.distance_to_color(perp_wall_dist);
-            
-            for y in 0..double_height {
-                let y_i32 = y as i32;
-                if y_i32 >= draw_start && y_i32 < draw_end {
-                    frame_buffer[y][x] = wall_color;
-                } else if y_i32 < draw_start {
-                    // Ceiling - darker gradient based on distance from center
-                    let dist_from_center = (draw_start - y_i32) as f64 / double_height as f64;
-                    frame_buffer[y][x] = self.ceiling_color(dist_from_center);
-                } else {
-                    // Floor - darker gradient based on distance from center
-                    let dist_from_center = (y_i32 - draw_end) as f64 / double_height as f64;
-                    frame_buffer[y][x] = self.floor_color(dist_from_center);
+    };
+    raycaster.floor_texture = options.floor_texture_file.as_ref().and_then(open);
+    raycaster.ceiling_texture = options.ceiling_texture_file.as_ref().and_then(open);
+    for (material, path) in &options.texture_files {
+        match ImageTexture::open(std::path::Path::new(path)) {
+            Ok(texture) => {
+                let index = *material as usize;
+                if raycaster.image_textures.len() <= index {
+                    raycaster.image_textures.resize_with(index + 1, || None);
                 }
+                raycaster.image_textures[index] = Some(texture);
             }
+            Err(err) => eprintln!("warning: {}, using the built-in wall color", err),
         }
-        
-        // Build output string using half-block characters for double resolution
-        // Use ▀ (upper half) and ▄ (lower half) to get 2 pixels per character
-        let mut output = String::with_capacity(screen_width * screen_height * 30);
-        output.push_str("\x1b[H"); // Move cursor to home (0,0) without clearing
-        
-        let mut current_fg = 0u8;
-        let mut current_bg = 0u8;
-        
-        for y in 0..screen_height {
-            let upper_y = y * 2;
-            let lower_y = y * 2 + 1;
-            
-            for x in 0..screen_width {
-                let upper_color = frame_buffer[upper_y][x];
-                let lower_color = if lower_y < double_height {
-                    frame_buffer[lower_y][x]
-                } else {
-                    frame_buffer[upper_y][x] // Fallback if out of bounds
-                };
-                
-                // Set foreground (upper half) and background (lower half) colors
-                if upper_color != current_fg || lower_color != current_bg {
-                    output.push_str(&format!("\x1b[38;5;{}m\x1b[48;5;{}m", upper_color, lower_color));
-                    current_fg = upper_color;
-                    current_bg = lower_color;
-                }
-                
-                // Use upper half block character (▀) - shows upper color as foreground, lower as background
-                output.push('▀');
-            }
-            
-            // Reset color at end of line and move to next
-            if y < screen_height - 1 {
-                output.push_str("\x1b[0m\r\n");
-                current_fg = 0;
-                current_bg = 0;
-            }
-        }
-        
-        // Reset color and write everything at once
-        output.push_str("\x1b[0m");
-        write!(stdout, "{}", output)?;
-        stdout.flush()?;
-        
-        Ok(())
+    }
+}
+
+// Parse teleport prompt input: "x y" or "x y angle", with the angle in degrees
+fn parse_teleport(input: &str) -> Result<(f64, f64, Option<f64>), RaycastError> {
+    let values = input
+        .split_whitespace()
+        .map(|value| value.parse().ok().filter(|value: &f64| value.is_finite()))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| RaycastError::Config(format!("not a number in {:?}", input)))?;
+    match values[..] {
+        [x, y] => Ok((x, y, None)),
+        [x, y, angle] => Ok((x, y, Some(angle.to_radians()))),
+        _ => Err(RaycastError::Config("expected x y [angle]".to_string())),
+    }
+}
+
+// Draw the teleport prompt over the bottom row of the frame
+fn draw_prompt(stdout: &mut io::Stdout, prompt: &TeleportPrompt, raycaster: &Raycaster) -> io::Result<()> {
+    let (_, rows) = terminal::size()?;
+    let player = &raycaster.player;
+    let mut line = format!(
+        "teleport x y [angle]: {}_  (now {:.2} {:.2} {:.0})",
+        prompt.input,
+        player.x,
+        player.y,
+        player.angle.to_degrees()
+    );
+    if let Some(error) = &prompt.error {
+        line.push_str(&format!("  error: {}", error));
+    }
+    // Reset colors first so the cleared line doesn't keep the frame's background
+    write!(stdout, "\x1b[0m")?;
+    execute!(stdout, MoveTo(0, rows.saturating_sub(1)), Clear(ClearType::CurrentLine))?;
+    write!(stdout, "{}", line)?;
+    stdout.flush()
+}
+
+// Lines of the help overlay, wrapped to `width` columns: every action with
+// its keys, then the current state of the toggles
+fn help_lines(bindings: &KeyBindings, raycaster: &Raycaster, width: usize) -> Vec<String> {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let mut entries: Vec<(String, String)> = bindings
+        .describe()
+        .into_iter()
+        .map(|(description, keys)| {
+            let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys.join(", ") };
+            (description.to_string(), keys)
+        })
+        .collect();
+    entries.push((String::new(), String::new()));
+    let lateral = if bindings.strafe_turn_swapped() { "turn" } else { "strafe" };
+    entries.push(("A / D".to_string(), lateral.to_string()));
+    entries.push(("Vignette".to_string(), on_off(raycaster.vignette).to_string()));
+    entries.push(("Dithering".to_string(), on_off(raycaster.dither).to_string()));
+    entries.push(("Flashlight".to_string(), on_off(raycaster.flashlight).to_string()));
+    entries.push(("Minimap".to_string(), on_off(raycaster.minimap).to_string()));
+    entries.push(("Status bar".to_string(), on_off(raycaster.hud).to_string()));
+    entries.push(("Frame counter".to_string(), on_off(raycaster.fps_counter).to_string()));
+    entries.push(("Floor grid".to_string(), on_off(raycaster.floor_grid).to_string()));
+    entries.push(("Measuring".to_string(), on_off(raycaster.measuring).to_string()));
+    if let Some(cycle) = &raycaster.day_cycle {
+        let hour = cycle.hour.floor();
+        let state = if cycle.running { "running" } else { "paused" };
+        let time = format!("{:02}:{:02} ({})", hour, ((cycle.hour - hour) * 60.0).floor(), state);
+        entries.push(("Time of day".to_string(), time));
     }
     
-    // Convert distance to 256-color code for walls
-    // Uses warm color gradient for better visual appeal
-    fn distance_to_color(&self, distance: f64) -> u8 {
-        // Clamp distance to reasonable range (0.1 to 15.0)
-        let clamped_dist = distance.max(0.1).min(15.0);
-        
-        // Use logarithmic scale for better depth perception
-        let log_dist = (clamped_dist + 1.0f64).ln();
-        let max_log = (15.0f64 + 1.0f64).ln();
-        let normalized = 1.0 - (log_dist / max_log);
-        
-        // Use warm color palette: bright yellow/orange for close, dark red for far
-        // Colors 220-226 are warm yellows/oranges, 88-94 are dark reds
-        if normalized > 0.5 {
-            // Close walls: bright warm colors (220-226)
-            let warm = 220.0 + ((normalized - 0.5) * 12.0);
-            warm.max(220.0).min(226.0) as u8
-        } else {
-            // Far walls: dark red/brown (88-94)
-            let dark = 88.0 + (normalized * 12.0);
-            dark.max(88.0).min(94.0) as u8
+    let mut lines = Vec::new();
+    for (label, value) in entries {
+        // Values that don't fit wrap onto lines indented to the keys column
+        let column = HELP_KEYS_COLUMN.min(width / 2);
+        let mut line = format!("{:<label_width$} ", label, label_width = column - 1);
+        for (index, word) in value.split(' ').enumerate() {
+            if index > 0 && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(line);
+                line = " ".repeat(column);
+            } else if index > 0 {
+                line.push(' ');
+            }
+            line.push_str(word);
         }
+        lines.push(line);
     }
-    
-    // Ceiling color gradient - sky blue tones
-    fn ceiling_color(&self, dist_from_center: f64) -> u8 {
-        // Lighter blue near horizon, darker blue at top
-        let normalized = dist_from_center.min(1.0);
-        // Use sky blue colors: 39-45 range (bright to medium blue)
-        let blue_shade = 39.0 + (normalized * 6.0);
-        blue_shade.max(39.0).min(45.0) as u8
+    lines
+}
+
+// Draw the card between levels centered over the view, its lines centered
+// in a panel with a blank row above and below
+fn draw_interstitial(stdout: &mut io::Stdout, interstitial: &Interstitial) -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let width = interstitial.lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) + 4;
+    let width = width.min(columns as usize);
+    let left = (columns as usize - width) / 2;
+    let top = (rows as usize).saturating_sub(interstitial.lines.len() + 2) / 2;
+    // A blank row above and below the text
+    let panel = std::iter::once("")
+        .chain(interstitial.lines.iter().map(String::as_str))
+        .chain(std::iter::once(""));
+    for (row, line) in panel.enumerate() {
+        let text: String = line.chars().take(width).collect();
+        execute!(stdout, MoveTo(left as u16, (top + row) as u16))?;
+        write!(stdout, "{}{:^width$}\x1b[0m", HELP_COLORS, text, width = width)?;
+    }
+    stdout.flush()
+}
+
+// Draw the help panel centered over the view, scrolled so the requested
+// line is at the top when the list is taller than the terminal
+fn draw_help(stdout: &mut io::Stdout, help: &mut HelpOverlay, bindings: &KeyBindings, raycaster: &Raycaster) -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let (columns, rows) = (columns as usize, rows as usize);
+    let width = columns.saturating_sub(4).min(HELP_WIDTH);
+    // The title and footer take a row each
+    if width < 20 || rows < 5 {
+        return Ok(());
     }
+    let lines = help_lines(bindings, raycaster, width - 2);
+    let shown = lines.len().min(rows - 4);
+    help.scroll = help.scroll.min(lines.len() - shown);
     
-    // Floor color gradient - dark stone/concrete
-    fn floor_color(&self, dist_from_center: f64) -> u8 {
-        // Darker as we go down
-        let normalized = dist_from_center.min(1.0);
-        // Use dark gray/stone colors: 238-244 range (dark to medium gray)
-        let gray_shade = 238.0 + (normalized * 6.0);
-        gray_shade.max(238.0).min(244.0) as u8
+    let footer = if shown < lines.len() {
+        format!(
+            "lines {}-{} of {}, Up/Down to scroll",
+            help.scroll + 1,
+            help.scroll + shown,
+            lines.len()
+        )
+    } else {
+        "F1 or Esc to close".to_string()
+    };
+    let panel = std::iter::once("Controls".to_string())
+        .chain(lines.into_iter().skip(help.scroll).take(shown))
+        .chain(std::iter::once(footer));
+    
+    let left = (columns - width) / 2;
+    let top = (rows - shown - 2) / 2;
+    for (row, line) in panel.enumerate() {
+        let text: String = line.chars().take(width - 2).collect();
+        execute!(stdout, MoveTo(left as u16, (top + row) as u16))?;
+        write!(stdout, "{} {:<inner$} \x1b[0m", HELP_COLORS, text, inner = width - 2)?;
     }
+    stdout.flush()
+}
 
-    fn update(&mut self, keys: &[KeyCode]) {
-        let mut move_x = 0.0;
-        let mut move_y = 0.0;
-        let mut rotate = 0.0;
-        
-        for key in keys {
-            match key {
-                KeyCode::Char('w') | KeyCode::Up => {
-                    move_x += self.player.angle.cos() * MOVE_SPEED;
-                    move_y += self.player.angle.sin() * MOVE_SPEED;
-                }
-                KeyCode::Char('s') | KeyCode::Down => {
-                    move_x -= self.player.angle.cos() * MOVE_SPEED;
-                    move_y -= self.player.angle.sin() * MOVE_SPEED;
-                }
-                KeyCode::Char('a') => {
-                    move_x += self.player.angle.sin() * MOVE_SPEED;
-                    move_y -= self.player.angle.cos() * MOVE_SPEED;
-                }
-                KeyCode::Char('d') => {
-                    move_x -= self.player.angle.sin() * MOVE_SPEED;
-                    move_y += self.player.angle.cos() * MOVE_SPEED;
-                }
-                KeyCode::Left => {
-                    rotate -= ROTATION_SPEED;
-                }
-                KeyCode::Right => {
-                    rotate += ROTATION_SPEED;
-                }
-                _ => {}
-            }
+// Save the current view, without overlays, as a PPM image in the working
+// directory named after the current time
+fn save_photo(raycaster: &mut Raycaster) -> Result<(), RaycastError> {
+    let (columns, rows) = terminal::size()?;
+    let render_mode = raycaster.render_mode;
+    let size = (render_mode.pixel_columns(columns as usize), render_mode.pixel_rows(rows as usize));
+    let mut file = io::BufWriter::new(std::fs::File::create(format!("photo-{}.ppm", timestamp()))?);
+    match raycaster.color_depth {
+        ColorDepth::TrueColor => render::write_ppm_rgb(&raycaster.render_frame_rgb(size.0, size.1), &mut file)?,
+        ColorDepth::Indexed | ColorDepth::Basic => render::write_ppm(&raycaster.render_frame(size.0, size.1), &mut file)?,
+    }
+    file.flush()?;
+    Ok(())
+}
+
+// Save the map as an SVG drawing with the route the player has taken, in
+// the working directory and named after the current time
+fn export_svg(raycaster: &Raycaster) -> Result<(), RaycastError> {
+    std::fs::write(format!("map-{}.svg", timestamp()), svg::map_svg(&raycaster.map_file(), raycaster.explored()))?;
+    Ok(())
+}
+
+// Save the edited map as text in the working directory, named after the
+// current time
+fn export_map(raycaster: &Raycaster) -> Result<(), RaycastError> {
+    std::fs::write(format!("map-{}.txt", timestamp()), raycaster.map_text())?;
+    Ok(())
+}
+
+// Save the edited map back to the file given to `edit`, returning the
+// message shown in the editor. Maps that couldn't be played aren't saved.
+fn save_map(raycaster: &Raycaster, path: &str) -> String {
+    let map = raycaster.map_file();
+    match map.validate().and_then(|()| map.save(std::path::Path::new(path))) {
+        Ok(()) => format!("saved {}", path),
+        Err(err) => format!("not saved: {}", err),
+    }
+}
+
+// Map to edit from a file, or an empty room of the given size enclosed by
+// walls if the file doesn't exist yet
+fn open_for_editing(path: &std::path::Path, (width, height): (usize, usize)) -> Result<MapFile, RaycastError> {
+    if path.exists() {
+        return MapFile::load(path);
+    }
+    if width < 3 || height < 3 {
+        return Err(RaycastError::Config(format!("new maps must be at least 3 x 3, not {} x {}", width, height)));
+    }
+    let mut walls = Map::filled(width, height, b'1');
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            walls.set(x, y, b'0');
         }
-        
-        // Collision detection
-        let new_x = self.player.x + move_x;
-        let new_y = self.player.y + move_y;
-        
-        if new_x >= 0.0
-            && new_x < MAP_WIDTH as f64
-            && new_y >= 0.0
-            && new_y < MAP_HEIGHT as f64
-        {
-            let map_x = new_x.floor() as usize;
-            let map_y = new_y.floor() as usize;
-            
-            if self.get_map_value(map_x, map_y) == 0 {
-                self.player.x = new_x;
-                self.player.y = new_y;
+    }
+    Ok(MapFile {
+        name: path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+        ..MapFile::from_walls(walls)
+    })
+}
+
+// Load level `level` (0-based) of the game: the next level of the campaign
+// or map file in the list, starting over after the last, a level generated
+// with the seed moved on by `level`, or the next built-in map. A Wolfenstein
+// 3D GAMEMAPS file moves on to its next level each time the list comes round
+// to it. The map being edited is reloaded as last saved. Endless worlds
+// have no exits, so only ever have a first level.
+fn load_level(raycaster: &mut Raycaster, options: &Options, level: usize) -> Result<(), RaycastError> {
+    if options.world {
+        raycaster.load_world(World::new(options.seed.unwrap_or(0)));
+    } else if let Some(path) = &options.edit_file {
+        raycaster.load_map(open_for_editing(std::path::Path::new(path), options.generate_size)?);
+    } else if let Some(campaign) = &options.campaign {
+        raycaster.load_map(campaign.levels[level % campaign.levels.len()].load()?);
+    } else if let Some(generator) = options.generator {
+        let (width, height) = options.generate_size;
+        let seed = options.seed.unwrap_or(0).wrapping_add(level as u64);
+        raycaster.load_map(generate::generate(generator, width, height, seed)?);
+    } else if options.map_files.is_empty() {
+        raycaster.load_builtin(level);
+    } else {
+        let path = std::path::Path::new(&options.map_files[level % options.map_files.len()]);
+        let map = if path.as_os_str() == "-" {
+            let mut map = MapFile::parse_text(options.stdin_map.as_deref().unwrap_or_default())?;
+            if map.name.is_empty() {
+                map.name = "stdin".to_string();
             }
+            map
+        } else if wolf3d::is_gamemaps(path) {
+            wolf3d::load(path, options.wolf_level + level / options.map_files.len())?
+        } else {
+            MapFile::load(path)?
+        };
+        raycaster.load_map(map);
+    }
+    // The chosen spawn point holds for every level that has one with that
+    // number, so reloading a level keeps it; the rest start at their first
+    if let Some(index) = options.spawn_point {
+        raycaster.choose_spawn(index - 1);
+    }
+    Ok(())
+}
+
+// File level `level` (0-based) is loaded from, if it comes from one
+fn level_file(options: &Options, level: usize) -> Option<std::path::PathBuf> {
+    if let Some(path) = &options.edit_file {
+        Some(path.into())
+    } else if let Some(campaign) = &options.campaign {
+        Some(campaign.levels[level % campaign.levels.len()].map.clone())
+    } else if options.generator.is_some() || options.world || options.map_files.is_empty() {
+        None
+    } else {
+        // A map piped in on stdin can't change
+        let path = &options.map_files[level % options.map_files.len()];
+        (path != "-").then(|| path.into())
+    }
+}
+
+// Watcher for the current level's file, unless watching is turned off
+fn watch_level(options: &Options, level: usize) -> Option<FileWatcher> {
+    level_file(options, level)
+        .filter(|_| options.watch)
+        .map(|path| FileWatcher::new(&path))
+}
+
+// Load the current level again after its file changed, keeping the player
+// where they were if that is still inside the map. A file that can't be
+// loaded, perhaps because it is only half saved, leaves the level as it was.
+fn reload_level(raycaster: &mut Raycaster, options: &Options, level: usize) {
+    let (x, y, angle) = (raycaster.player.x, raycaster.player.y, raycaster.player.angle);
+    match load_level(raycaster, options, level) {
+        Ok(()) => {
+            // Outside a map that has shrunk, the player starts at the spawn
+            // point, and inside a new wall they are moved out of it
+            let cell = |x: f64, y: f64| (x.floor(), y.floor());
+            let notice = match raycaster.teleport(x, y, angle) {
+                Ok(()) if cell(raycaster.player.x, raycaster.player.y) != cell(x, y) => {
+                    "Map reloaded, moved out of a wall".to_string()
+                }
+                Ok(()) => "Map reloaded".to_string(),
+                Err(err) => format!("Map reloaded, back at the spawn point: {}", err),
+            };
+            raycaster.show_notice(&notice, RELOAD_NOTICE_TIME);
         }
-        
-        self.player.angle += rotate;
-        
-        // Normalize angle
-        while self.player.angle < 0.0 {
-            self.player.angle += 2.0 * std::f64::consts::PI;
+        Err(err) => raycaster.show_notice(&format!("Reload failed: {}", err), RELOAD_NOTICE_TIME),
+    }
+}
+
+// Lines of the card introducing level `level` (0-based), which has just been
+// loaded. Campaigns number their levels from the start of the campaign and
+// announce when it has been finished.
+fn interstitial_lines(raycaster: &Raycaster, options: &Options, level: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let number = match &options.campaign {
+        Some(campaign) => {
+            let index = level % campaign.levels.len();
+            lines.push(if index == 0 { format!("{} complete!", campaign.name) } else { campaign.name.clone() });
+            format!("Level {} of {}", index + 1, campaign.levels.len())
         }
-        while self.player.angle >= 2.0 * std::f64::consts::PI {
-            self.player.angle -= 2.0 * std::f64::consts::PI;
+        None => format!("Level {}", level + 1),
+    };
+    lines.push(number);
+    lines.push(raycaster.map_name().to_string());
+    lines
+}
+
+// Seconds since the Unix epoch, for naming saved files
+fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// Parse a size given as WIDTHxHEIGHT, both non-zero
+fn parse_size(value: &str) -> Option<(usize, usize)> {
+    value
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .filter(|&(w, h)| w > 0 && h > 0)
+}
+
+// Undo the terminal setup from main. In inline mode the last frame is left
+// in place and the cursor moves below it.
+fn restore_terminal(stdout: &mut io::Stdout, options: &Options) -> io::Result<()> {
+    if options.mouse {
+        execute!(stdout, DisableMouseCapture)?;
+    }
+    if options.inline {
+        write!(stdout, "\r\n")?;
+        execute!(stdout, Show)?;
+    } else {
+        execute!(stdout, Show, LeaveAlternateScreen)?;
+    }
+    terminal::disable_raw_mode()
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
         }
     }
 }
 
-fn main() -> io::Result<()> {
+fn run() -> Result<(), RaycastError> {
+    let mut options = Options::parse(std::env::args().skip(1))?;
     let mut stdout = stdout();
     
-    terminal::enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen, Hide)?;
+    if options.bench_sweep {
+        let results = bench::sweep(&options.bench_sizes, BENCH_DURATION);
+        print!("{}", bench::format_table(&results));
+        return Ok(());
+    }
+    
+    let mut bindings = match &options.keys {
+        Some(path) => KeyBindings::load(std::path::Path::new(path))?,
+        None => KeyBindings::default(),
+    };
+    if options.swap_strafe_turn {
+        bindings.swap_strafe_turn();
+    }
     
+    // Create the raycaster before taking over the screen so spawn warnings stay visible
     let mut raycaster = Raycaster::new();
+    raycaster.raised_ceilings = options.raised_ceilings;
+    options.seed.get_or_insert_with(timestamp);
+    if let Some(path) = &options.campaign_file {
+        options.campaign = Some(Campaign::load(std::path::Path::new(path))?);
+    }
+    // A piped-in map is read in full now: once the game starts, crossterm
+    // takes its input from /dev/tty instead, as stdin isn't a terminal
+    if options.map_files.iter().any(|path| path == "-") {
+        let mut stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            return Err(RaycastError::Config("- reads a map from stdin, but none was piped in".to_string()));
+        }
+        let mut text = String::new();
+        stdin
+            .read_to_string(&mut text)
+            .map_err(|err| RaycastError::Config(format!("map from stdin: {}", err)))?;
+        options.stdin_map = Some(text);
+    }
+    load_level(&mut raycaster, &options, 0)?;
+    if let Some(index) = options.spawn_point {
+        if index > raycaster.spawn_points().len() {
+            let count = raycaster.spawn_points().len();
+            return Err(RaycastError::Config(format!("spawn point {} doesn't exist (the map has {})", index, count)));
+        }
+    }
+    if options.spawn.is_some() || options.facing.is_some() {
+        let (x, y) = options.spawn.unwrap_or((raycaster.player.x, raycaster.player.y));
+        let angle = options.facing.map_or(raycaster.player.angle, f64::to_radians);
+        raycaster.teleport(x, y, angle)?;
+        raycaster.spawn = (raycaster.player.x, raycaster.player.y, raycaster.player.angle);
+    }
+    if options.grid {
+        raycaster.set_grid_mode(true);
+    }
+    
+    if options.info {
+        print!("{}", raycaster.map_info());
+    }
+    if let Some(path) = &options.svg_file {
+        std::fs::write(path, svg::map_svg(&raycaster.map_file(), &[]))
+            .map_err(|err| RaycastError::Config(format!("{}: {}", path, err)))?;
+    }
+    if (options.info || options.svg_file.is_some()) && !options.play {
+        return Ok(());
+    }
+    
+    raycaster.inline = options.inline;
+    raycaster.fixed_resolution = options.fixed_resolution;
+    raycaster.letterbox_color = options.letterbox_color;
+    raycaster.default_fog = options.fog;
+    raycaster.background_color = options.background_color;
+    raycaster.ramps = options.ramps;
+    raycaster.textures = options.textures;
+    raycaster.light_direction = options.light_angle.map(light_direction);
+    raycaster.carried_light = options.lantern.then(|| PointLight::new(0.0, 0.0));
+    raycaster.flashlight = options.flashlight;
+    raycaster.minimap = options.minimap;
+    raycaster.hud = options.hud;
+    raycaster.fps_counter = options.fps_counter;
+    raycaster.weapon = options.weapon;
+    raycaster.head_bob = options.head_bob;
+    raycaster.render_mode = options.render_mode.unwrap_or_else(RenderMode::detect);
+    raycaster.color_depth = options.color_depth.unwrap_or_else(ColorDepth::detect);
+    raycaster.mirror = options.mirror;
+    raycaster.flip = options.flip;
+    raycaster.max_dda_steps = options.max_dda_steps;
+    raycaster.response_curve = options.response_curve;
+    raycaster.dither = options.dither;
+    if options.day_length.is_some() || options.time_of_day.is_some() || options.day_paused {
+        let mut cycle = DayCycle::new(
+            options.time_of_day.unwrap_or(DEFAULT_START_HOUR),
+            options.day_length.unwrap_or(DEFAULT_DAY_LENGTH),
+        );
+        cycle.running = !options.day_paused;
+        raycaster.day_cycle = Some(cycle);
+    }
+    if options.split {
+        raycaster.split = Some(SplitView {
+            camera: options.split_camera.map(|(x, y, angle)| Player {
+                x,
+                y,
+                angle,
+                health: options.health,
+            }),
+            fov: options.split_fov,
+        });
+    }
+    raycaster.player.health = options.health;
+    raycaster.max_health = options.health;
+    load_textures(&mut raycaster, &options);
+    let mut leaderboard = Leaderboard::load(std::path::Path::new(DEFAULT_LEADERBOARD_FILE));
+    
+    terminal::enable_raw_mode()?;
+    if !options.inline {
+        execute!(stdout, EnterAlternateScreen)?;
+    }
+    execute!(stdout, Hide)?;
+    if options.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    
+    // Always restore the terminal, even if the game loop failed, so the error
+    // message is readable
+    let result = game_loop(&mut stdout, &mut raycaster, &mut bindings, &mut leaderboard, &options);
+    restore_terminal(&mut stdout, &options)?;
+    if let Outcome::GameOver = result? {
+        println!("Game over: you were caught.");
+    }
+    Ok(())
+}
+
+fn game_loop(
+    stdout: &mut io::Stdout,
+    raycaster: &mut Raycaster,
+    bindings: &mut KeyBindings,
+    leaderboard: &mut Leaderboard,
+    options: &Options,
+) -> Result<Outcome, RaycastError> {
+    let mut audio_cues = AudioCues::new();
     let mut last_frame = Instant::now();
     let frame_duration = Duration::from_millis(16); // ~60 FPS
+    let mut quality = AdaptiveQuality::new(frame_duration);
+    let mut prompt: Option<TeleportPrompt> = None;
+    // The `edit` subcommand starts in the editor
+    let mut editor = options.edit_file.as_ref().map(|_| Editor::new(raycaster));
+    let mut help: Option<HelpOverlay> = None;
+    let mut automap: Option<Automap> = None;
+    let mut level = 0; // Index of the level being played, counting from 0
+    let mut watcher = watch_level(options, level);
+    let mut interstitial: Option<Interstitial> = None;
+    let mut level_end: Option<LevelEnd> = None;
+    if options.auto_quality {
+        raycaster.quality_level = Some(quality.level());
+    }
     
     loop {
-        let mut keys_pressed = Vec::new();
+        let mut actions = Vec::new();
         
         // Non-blocking event polling
         while event::poll(Duration::from_millis(0))? {
-            if let Event::Key(KeyEvent {
-                code,
-                kind: KeyEventKind::Press,
-                ..
-            }) = event::read()?
-            {
-                match code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
-                        execute!(stdout, Show, LeaveAlternateScreen)?;
-                        terminal::disable_raw_mode()?;
-                        return Ok(());
+            match event::read()? {
+                // Any key dismisses the level card early, and the quit key
+                // still quits
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if interstitial.is_some() => {
+                    if bindings.action(code, modifiers) == Some(Action::Quit) {
+                        return Ok(Outcome::Quit);
+                    }
+                    interstitial = None;
+                }
+                // The teleport prompt takes all key presses while it is open
+                Event::Key(KeyEvent {
+                    code,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if prompt.is_some() => {
+                    let Some(open) = &mut prompt else { continue };
+                    match code {
+                        KeyCode::Esc => prompt = None,
+                        KeyCode::Backspace => {
+                            open.input.pop();
+                        }
+                        KeyCode::Enter => {
+                            let result = parse_teleport(&open.input).and_then(|(x, y, angle)| {
+                                raycaster.teleport(x, y, angle.unwrap_or(raycaster.player.angle))
+                            });
+                            match result {
+                                Ok(()) => prompt = None,
+                                Err(err) => open.error = Some(err.to_string()),
+                            }
+                        }
+                        KeyCode::Char(c) => open.input.push(c),
+                        _ => {}
+                    }
+                }
+                // So does the help overlay, which only scrolls
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if help.is_some() => {
+                    let Some(open) = &mut help else { continue };
+                    match (code, bindings.action(code, modifiers)) {
+                        (KeyCode::Up, _) => open.scroll = open.scroll.saturating_sub(1),
+                        (KeyCode::Down, _) => open.scroll += 1,
+                        (KeyCode::PageUp, _) => open.scroll = open.scroll.saturating_sub(10),
+                        (KeyCode::PageDown, _) => open.scroll += 10,
+                        (KeyCode::Esc, _) | (_, Some(Action::Help)) => help = None,
+                        (_, Some(Action::Quit)) => return Ok(Outcome::Quit),
+                        _ => {}
                     }
-                    _ => keys_pressed.push(code),
+                    raycaster.dimmed = help.is_some();
                 }
+                // So does the map editor, apart from the keys to leave it or quit
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if editor.is_some() => {
+                    let Some(open) = &mut editor else { continue };
+                    let action = bindings.action(code, modifiers);
+                    match code {
+                        KeyCode::Esc => editor = None,
+                        KeyCode::Char('e') => match &options.edit_file {
+                            Some(path) => {
+                                open.status = Some(save_map(raycaster, path));
+                                // Saving isn't an outside change to reload
+                                if let Some(watcher) = &mut watcher {
+                                    watcher.mark_seen();
+                                }
+                            }
+                            None => export_map(raycaster)?,
+                        },
+                        _ if open.handle_key(code, raycaster) => {}
+                        _ if action == Some(Action::Editor) => editor = None,
+                        _ if action == Some(Action::Quit) => return Ok(Outcome::Quit),
+                        _ => {}
+                    }
+                    if editor.is_none() {
+                        // Edits may have walled the player in
+                        raycaster.nudge_out_of_wall();
+                    }
+                }
+                // And the automap, which scrolls and zooms
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if automap.is_some() => {
+                    let Some(open) = &mut automap else { continue };
+                    let action = bindings.action(code, modifiers);
+                    match code {
+                        KeyCode::Esc => automap = None,
+                        _ if open.handle_key(code, raycaster) => {}
+                        _ if action == Some(Action::Automap) => automap = None,
+                        _ if action == Some(Action::Quit) => return Ok(Outcome::Quit),
+                        _ => {}
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => match bindings.action(code, modifiers) {
+                    Some(Action::Quit) => return Ok(Outcome::Quit),
+                    Some(Action::TeleportPrompt) => {
+                        prompt = Some(TeleportPrompt {
+                            input: String::new(),
+                            error: None,
+                        });
+                    }
+                    Some(Action::Vignette) => raycaster.vignette = !raycaster.vignette,
+                    Some(Action::NextMap) => raycaster.cycle_map(1),
+                    Some(Action::PreviousMap) => raycaster.cycle_map(-1),
+                    Some(Action::Reset) => raycaster.reset_to_spawn(),
+                    Some(Action::FloorGrid) => raycaster.floor_grid = !raycaster.floor_grid,
+                    Some(Action::Editor) if !raycaster.photo_mode() => editor = Some(Editor::new(raycaster)),
+                    Some(Action::Automap) if !raycaster.photo_mode() => automap = Some(Automap::new(raycaster)),
+                    Some(Action::Spectate) if !raycaster.photo_mode() => {
+                        raycaster.set_spectating(!raycaster.spectating());
+                    }
+                    Some(Action::PhotoMode) => raycaster.set_photo_mode(!raycaster.photo_mode()),
+                    Some(Action::SavePhoto) if raycaster.photo_mode() => save_photo(raycaster)?,
+                    Some(Action::ExportSvg) => export_svg(raycaster)?,
+                    Some(Action::Fire) if raycaster.measuring => {
+                        raycaster.drop_marker();
+                    }
+                    Some(Action::Fire) if !raycaster.photo_mode() && !raycaster.spectating() => {
+                        raycaster.fire();
+                    }
+                    Some(Action::Use) if !raycaster.photo_mode() && !raycaster.spectating() => {
+                        raycaster.activate();
+                    }
+                    Some(Action::Measure) => raycaster.measuring = !raycaster.measuring,
+                    Some(Action::ClearMarkers) => raycaster.clear_markers(),
+                    Some(Action::Dither) => raycaster.dither = !raycaster.dither,
+                    Some(Action::Flashlight) => raycaster.flashlight = !raycaster.flashlight,
+                    Some(Action::Minimap) => raycaster.minimap = !raycaster.minimap,
+                    Some(Action::Hud) => raycaster.hud = !raycaster.hud,
+                    Some(Action::FpsCounter) => raycaster.fps_counter = !raycaster.fps_counter,
+                    Some(Action::SwapStrafeTurn) => bindings.swap_strafe_turn(),
+                    Some(Action::Help) => {
+                        help = Some(HelpOverlay { scroll: 0 });
+                        raycaster.dimmed = true;
+                    }
+                    Some(Action::PauseDay) => {
+                        if let Some(cycle) = &mut raycaster.day_cycle {
+                            cycle.running = !cycle.running;
+                        }
+                    }
+                    Some(Action::EarlierInDay) => {
+                        if let Some(cycle) = &mut raycaster.day_cycle {
+                            cycle.scrub(-DAY_SCRUB_HOURS);
+                        }
+                    }
+                    Some(Action::LaterInDay) => {
+                        if let Some(cycle) = &mut raycaster.day_cycle {
+                            cycle.scrub(DAY_SCRUB_HOURS);
+                        }
+                    }
+                    Some(action) if action.is_movement() => actions.push(action),
+                    _ => {}
+                },
+                Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Down(MouseButton::Left),
+                    column,
+                    row,
+                    ..
+                }) if options.mouse && editor.is_some() => {
+                    let (columns, rows) = terminal::size()?;
+                    let mode = raycaster.render_mode;
+                    let size = (mode.pixel_columns(columns as usize), mode.pixel_rows(rows as usize));
+                    let pixel = (column as usize * mode.pixel_columns(1), row as usize * mode.pixel_rows(1));
+                    if let Some(open) = &mut editor {
+                        open.click(pixel, size, raycaster);
+                    }
+                }
+                Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Down(MouseButton::Left),
+                    column,
+                    ..
+                }) if options.mouse && automap.is_none() && !raycaster.photo_mode() && !raycaster.spectating() => {
+                    raycaster.fire_at_column(column as usize);
+                }
+                _ => {}
             }
         }
         
-        raycaster.update(&keys_pressed);
-        raycaster.render(&mut stdout)?;
+        // Changes to the level's file are picked up once the editor is closed
+        if editor.is_none() && watcher.as_mut().is_some_and(FileWatcher::changed) {
+            reload_level(raycaster, options, level);
+        }
+        
+        // The world stays frozen while the level card, teleport prompt,
+        // editor, automap or help is open, or a finished level fades out
+        if interstitial.as_ref().is_some_and(|card| Instant::now() >= card.until) {
+            interstitial = None;
+        }
+        if interstitial.is_none()
+            && level_end.is_none()
+            && prompt.is_none()
+            && editor.is_none()
+            && automap.is_none()
+            && help.is_none()
+        {
+            raycaster.update(&actions);
+        }
+        for event in raycaster.drain_events() {
+            match event {
+                GameEvent::EnemyContact { .. } => {
+                    raycaster.take_damage(options.damage, Instant::now());
+                }
+                GameEvent::LevelComplete { seconds } => {
+                    let record = leaderboard.record(raycaster.map_hash(), seconds);
+                    raycaster.add_effect(ScreenEffect::Fade { color: (0, 0, 0) }, LEVEL_FADE_TIME);
+                    level_end = Some(LevelEnd {
+                        record,
+                        until: Instant::now() + LEVEL_FADE_TIME,
+                    });
+                }
+                _ => {}
+            }
+        }
+        match level_end.take() {
+            Some(end) if Instant::now() < end.until => level_end = Some(end),
+            Some(end) => {
+                raycaster.clear_effects();
+                // Move on to the next level; if it can't be loaded, say why
+                // and play this one again
+                level += 1;
+                if let Err(err) = load_level(raycaster, options, level) {
+                    level -= 1;
+                    raycaster.reset_to_spawn();
+                    raycaster.show_notice(&err.to_string(), RECORD_NOTICE_TIME);
+                } else {
+                    watcher = watch_level(options, level);
+                    match end.record {
+                        Ok(true) => raycaster.show_notice("New record!", RECORD_NOTICE_TIME),
+                        Ok(false) => {}
+                        Err(err) => raycaster.show_notice(&err.to_string(), RECORD_NOTICE_TIME),
+                    }
+                    interstitial = Some(Interstitial {
+                        lines: interstitial_lines(raycaster, options, level),
+                        until: Instant::now() + INTERSTITIAL_TIME,
+                    });
+                }
+            }
+            None => {}
+        }
+        raycaster.best_time = leaderboard.best(raycaster.map_hash());
+        raycaster.dimmed = help.is_some() || interstitial.is_some();
+        match (&editor, &automap) {
+            (Some(editor), _) => raycaster.render_editor(stdout, editor)?,
+            (None, Some(automap)) => raycaster.render_automap(stdout, automap)?,
+            (None, None) => raycaster.render(stdout)?,
+        }
+        if let Some(prompt) = &prompt {
+            draw_prompt(stdout, prompt, raycaster)?;
+        }
+        if let Some(help) = &mut help {
+            draw_help(stdout, help, bindings, raycaster)?;
+        }
+        if let Some(interstitial) = &interstitial {
+            draw_interstitial(stdout, interstitial)?;
+        }
+        if raycaster.is_game_over() {
+            return Ok(Outcome::GameOver);
+        }
+        if options.bells && audio_cues.update(&raycaster.player, &raycaster.billboards(), Instant::now()) {
+            write!(stdout, "\x07")?;
+            stdout.flush()?;
+        }
         
         // Frame rate limiting
         let elapsed = last_frame.elapsed();
+        if options.auto_quality && quality.record(elapsed) {
+            let settings = quality.settings();
+            raycaster.resolution_scale = settings.resolution_scale;
+            raycaster.ramps = options.ramps && settings.ramps;
+            raycaster.textures = options.textures && settings.textures;
+            raycaster.quality_level = Some(quality.level());
+        }
         if elapsed < frame_duration {
             std::thread::sleep(frame_duration - elapsed);
         }
         last_frame = Instant::now();
     }
 }
-
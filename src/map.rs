@@ -0,0 +1,344 @@
+// Map grids. The built-in levels are MAP_WIDTH x MAP_HEIGHT; maps loaded from
+// files or generated at startup can be any size.
+
+use crate::destructible::DESTRUCTIBLE_CELL;
+use crate::door::DOOR_CELL;
+use crate::mirror::MIRROR_CELL;
+use crate::pushwall::PUSH_WALL_CELL;
+use crate::window::WINDOW_CELL;
+use crate::RaycastError;
+
+// Floor cell that ends the level when the player steps onto it
+pub const EXIT_CELL: u8 = b'>';
+
+// Size of the built-in maps and their layers
+pub const MAP_WIDTH: usize = 24;
+pub const MAP_HEIGHT: usize = 24;
+
+// Map: 0 = empty space, 1-9 = wall material (1 = brick, 2 = checker, 3 = stripes,
+// 4 = glass, 5 = water curtain that can be walked through),
+// / and \ = brick wall running diagonally corner to corner through the cell,
+// - and | = thin wall across the middle of the cell along the x or y axis,
+// A-Z = teleporter, linked to the other cell with the same letter,
+// + = sliding door, * = secret push-wall, % = destructible wall,
+// = = window barred by a see-through grate, @ = mirror,
+// > = exit to the next level
+pub const MAP: &[&str] = &[
+    "111111111111111111111111",
+    "1/00000000220000000000\\1",
+    "100000000022000000000001",
+    "100000000022000000A00001",
+    "100000000000000040000001",
+    "100000000000000040000001",
+    "100000000000000040000001",
+    "100000000000000040000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000000000000000000003",
+    "100000000000000000000003",
+    "100000000000000050000003",
+    "100000000000000050000003",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100A00000000000000000001",
+    "10000000000000000000>001",
+    "1\\00000000000000000000/1",
+    "111111111111111111111111",
+];
+
+// Ceiling heights: 0 = normal, 1 = raised (walls drawn taller above the horizon)
+pub const CEILING_MAP: &[&str] = &[
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+    "000000000000111111111111",
+];
+
+// Floor heights (cosmetic, only drawn with --ramps): 0 = flat,
+// r = ramp rising towards +x, p = platform at PLATFORM_HEIGHT
+pub const FLOOR_MAP: &[&str] = &[
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "0000rrpppp00000000000000",
+    "0000rrpppp00000000000000",
+    "0000rrpppp00000000000000",
+    "0000rrpppp00000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+    "000000000000000000000000",
+];
+
+// Sprites placed in the map: (x, y, 256-color code)
+pub const SPRITES: &[(f64, f64, u8)] = &[
+    (6.5, 6.5, 130),   // Barrel
+    (15.5, 8.5, 130),  // Barrel
+    (12.5, 18.5, 28),  // Plant
+];
+
+// Enemy patrol routes; each enemy starts at the first waypoint and loops
+// through the rest. A single waypoint makes a stationary guard.
+pub const ENEMY_PATROLS: &[&[(f64, f64)]] = &[
+    &[(4.5, 10.5), (19.5, 10.5)],
+    &[(15.5, 15.5), (20.5, 20.5), (15.5, 20.5)],
+    &[(20.5, 3.5)],
+];
+
+// Demo map: a grid of rooms joined by doorways, with a long corridor
+pub const ROOMS_MAP: &[&str] = &[
+    "111111111111111111111111",
+    "100000100000001000000001",
+    "100000100000001000000001",
+    "100000000000001000000001",
+    "100000100000000000000001",
+    "100000100000001000000001",
+    "111511144110111111101111",
+    "100000002000000020000001",
+    "100000002000000020000001",
+    "100000000000000000000001",
+    "100000002000000020000001",
+    "111110111112111111101111",
+    "100000000020000000000001",
+    "100000000020000000000001",
+    "100000000000000000000001",
+    "100000000020000000000001",
+    "133333333323333303333331",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "133333333333333303333331",
+    "100000100000001000000001",
+    "100000000000000000000001",
+    "1000001000000010000000>1",
+    "111111111111111111111111",
+];
+
+pub const ROOMS_SPRITES: &[(f64, f64, u8)] = &[
+    (4.5, 1.5, 130),   // Barrel
+    (22.5, 7.5, 130),  // Barrel
+    (1.5, 12.5, 28),   // Plant
+    (21.5, 21.5, 28),  // Plant
+];
+
+pub const ROOMS_PATROLS: &[&[(f64, f64)]] = &[
+    &[(2.5, 17.5), (21.5, 17.5)],
+    &[(12.5, 8.5), (20.5, 9.5), (12.5, 9.5)],
+];
+
+// Demo map: an open arena with pillars and cut corners
+pub const ARENA_MAP: &[&str] = &[
+    "111111111111111111111111",
+    "1/0000000000>000000000\\1",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100022000000000000220001",
+    "100022000000000000220001",
+    "100000000000000000000001",
+    "100000000033330000000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000003000000300000001",
+    "100000003000000300000001",
+    "100000003000000300000001",
+    "100000003000000300000001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "100000000033330000000001",
+    "100000000000000000000001",
+    "100022000000000000220001",
+    "100022000000000000220001",
+    "100000000000000000000001",
+    "100000000000000000000001",
+    "1\\00000000000000000000/1",
+    "111111111111111111111111",
+];
+
+pub const ARENA_PATROLS: &[&[(f64, f64)]] = &[
+    &[(6.5, 6.5), (17.5, 6.5), (17.5, 17.5), (6.5, 17.5)],
+    &[(17.5, 17.5), (6.5, 17.5), (6.5, 6.5), (17.5, 6.5)],
+];
+
+// A complete built-in level: the wall grid, its optional layers and what
+// stands in it. All built-in maps are MAP_WIDTH x MAP_HEIGHT.
+pub struct BuiltinMap {
+    pub name: &'static str,
+    pub walls: &'static [&'static str],
+    pub ceiling: Option<&'static [&'static str]>,
+    pub floor: Option<&'static [&'static str]>,
+    pub sprites: &'static [(f64, f64, u8)],
+    pub enemy_patrols: &'static [&'static [(f64, f64)]],
+    pub spawn: (f64, f64, f64), // (x, y, facing in radians)
+}
+
+// Built-in maps, cycled with n/p. The first is loaded on startup.
+pub const BUILTIN_MAPS: &[BuiltinMap] = &[
+    BuiltinMap {
+        name: "Courtyard",
+        walls: MAP,
+        ceiling: Some(CEILING_MAP),
+        floor: Some(FLOOR_MAP),
+        sprites: SPRITES,
+        enemy_patrols: ENEMY_PATROLS,
+        spawn: (2.0, 2.0, 0.0),
+    },
+    BuiltinMap {
+        name: "Rooms",
+        walls: ROOMS_MAP,
+        ceiling: None,
+        floor: None,
+        sprites: ROOMS_SPRITES,
+        enemy_patrols: ROOMS_PATROLS,
+        spawn: (2.5, 2.5, 0.0),
+    },
+    BuiltinMap {
+        name: "Arena",
+        walls: ARENA_MAP,
+        ceiling: None,
+        floor: None,
+        sprites: &[],
+        enemy_patrols: ARENA_PATROLS,
+        spawn: (12.0, 21.5, -std::f64::consts::FRAC_PI_2),
+    },
+];
+
+// Wall grid of any size, one map character per cell, stored row by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct Map {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Map {
+    // A `width` x `height` map with every cell set to `cell`
+    pub fn filled(width: usize, height: usize, cell: u8) -> Self {
+        Map {
+            width,
+            height,
+            cells: vec![cell; width * height],
+        }
+    }
+
+    // Build a map from rows of map characters. Returns None if there are no
+    // rows or they aren't all the same non-zero length.
+    pub fn from_rows<R: AsRef<[u8]>>(rows: &[R]) -> Option<Self> {
+        let width = rows.first()?.as_ref().len();
+        if width == 0 || rows.iter().any(|row| row.as_ref().len() != width) {
+            return None;
+        }
+        Some(Map {
+            width,
+            height: rows.len(),
+            cells: rows.iter().flat_map(|row| row.as_ref().iter().copied()).collect(),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Whether a cell lies inside the map
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    // Map character at a cell, or None outside the map
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        (x < self.width && y < self.height).then(|| self.cells[y * self.width + x])
+    }
+
+    // Replace the map character at a cell. Returns false outside the map.
+    pub fn set(&mut self, x: usize, y: usize, cell: u8) -> bool {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = cell;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.cells.chunks(self.width)
+    }
+
+    // Every cell with its (x, y) position, row by row
+    pub fn cells(&self) -> impl Iterator<Item = ((usize, usize), u8)> + '_ {
+        let width = self.width;
+        self.cells.iter().enumerate().map(move |(index, &cell)| ((index % width, index / width), cell))
+    }
+}
+
+// Whether a character can appear in a wall grid
+fn is_map_char(c: u8) -> bool {
+    matches!(c, b'0'..=b'9' | b'/' | b'\\' | b'-' | b'|' | b'A'..=b'Z' | DOOR_CELL | PUSH_WALL_CELL | DESTRUCTIBLE_CELL | WINDOW_CELL | MIRROR_CELL | EXIT_CELL)
+}
+
+// Parse a wall grid in the same format as the built-in maps: rows of map
+// characters, all the same length. Trailing blank lines are ignored.
+pub fn parse_walls(text: &str) -> Result<Map, RaycastError> {
+    let rows: Vec<&str> = text.trim_end().lines().map(|row| row.trim_end_matches('\r')).collect();
+    let Some(width) = rows.first().map(|row| row.len()).filter(|&width| width > 0) else {
+        return Err(RaycastError::MapParse {
+            line: 1,
+            reason: "map is empty".to_string(),
+        });
+    };
+    for (index, row) in rows.iter().enumerate() {
+        let line = index + 1;
+        if let Some(column) = row.bytes().position(|c| !is_map_char(c)) {
+            let c = row[column..].chars().next().unwrap_or(' ');
+            return Err(RaycastError::MapParse {
+                line,
+                reason: format!("unexpected character {:?} in column {}", c, column + 1),
+            });
+        }
+        if row.len() != width {
+            return Err(RaycastError::MapParse {
+                line,
+                reason: format!("row is {} cells wide, but the first row is {}", row.len(), width),
+            });
+        }
+    }
+    Ok(Map::from_rows(&rows).expect("rows were checked to be the same length"))
+}
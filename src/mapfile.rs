@@ -0,0 +1,1086 @@
+// Map files. A plain text file holds just the wall grid; a `.toml` file can
+// also name the map, place spawn points and pick wall colors:
+//
+//     name = "Cellar"
+//     walls = """
+//     111111111111111111111111
+//     ...
+//     """
+//     heights = """  # Optional: 1-9 makes a wall that many tenths tall, 0 full
+//     000000000000000000000000
+//     ...
+//     """
+//     sectors = """  # Optional: a-z marks a cell's sector, 0 none
+//     000000000000000000000000
+//     ...
+//     """
+//     light = """  # Optional: light level 0-9 then a-f (15, the brightest)
+//     ffffffffffffffffffffffff
+//     ...
+//     """
+//     hazards = """  # Optional: l lava, a acid, 0 safe floor
+//     000000000000000000000000
+//     ...
+//     """
+//
+//     [spawn]
+//     x = 2.5
+//     y = 2.5
+//     angle = 90  # Degrees; 0 faces +x
+//
+//     [[spawn]]  # Repeated for more than one spawn point; the first is the default
+//     x = 20.5
+//     y = 2.5
+//
+//     [[sprite]]  # Repeated for each sprite standing in the map
+//     x = 4.5
+//     y = 6.5
+//     kind = "pillar"  # orb, pillar or barrel
+//     color = 94  # Optional 256-color code
+//
+//     [[light]]  # Repeated for each lamp lighting its surroundings
+//     x = 10.5
+//     y = 3.5
+//     radius = 5  # Optional, cells before it fades out (default 4)
+//     intensity = 1  # Optional, brightness added at the lamp (default 0.8)
+//
+//     [colors]
+//     1 = 130  # Material = 256-color code
+//
+//     [textures]
+//     1 = "textures/brick.png"  # Material = image, relative to the map file
+//     floor = "stone"  # Generated textures need no file
+//     ceiling = "textures/planks.png"
+//
+//     [teleporters]
+//     A = 90  # Facing in degrees on arriving at either end
+//     B = [0, 180]  # On arriving at the first and second B, in reading order
+//
+//     [sector_colors]
+//     a = [34, 117]  # Floor and ceiling 256-color codes of sector a
+//     b = [34, "sky"]  # An outdoor sector, open to the sky
+//
+//     [environment]
+//     fog = 0.1  # Fraction of the view lost to fog per cell of distance
+//     fog_start = 4  # Or fog that begins 4 cells away and hides everything
+//     fog_end = 12   # from 12 cells on
+//     fog_color = 52  # 256-color code
+//     ambient = 0.8  # Overall brightness; 1 is normal
+//     sky = 124  # Base colors of the ceiling and floor gradients
+//     floor = 94
+//
+// Only this subset of TOML is understood. Maps made in the Tiled editor can
+// be imported from `.tmx` files, and maps drawn in a paint program from
+// `.png` images.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::door::DOOR_CELL;
+use crate::environment::Environment;
+use crate::hazard::Hazard;
+use crate::map::{parse_walls, Map, EXIT_CELL};
+use crate::light::{PointLight, DEFAULT_INTENSITY as DEFAULT_LIGHT_INTENSITY, DEFAULT_RADIUS as DEFAULT_LIGHT_RADIUS};
+use crate::raycaster::Sprite;
+use crate::sprite::SpriteKind;
+use crate::texture::{GeneratedTexture, Translucency};
+use crate::RaycastError;
+
+// Lowest value of the strong channel and highest value of the other two in
+// the pixels marking the spawn point (red) and exits (green) in image maps,
+// so slightly off colors from a paint program still count
+#[cfg(feature = "image-maps")]
+const MARKER_PIXEL_BRIGHT: u8 = 200;
+#[cfg(feature = "image-maps")]
+const MARKER_PIXEL_DIM: u8 = 64;
+
+pub const MAX_LIGHT_LEVEL: u8 = 15; // Light level of fully lit cells
+
+// Colors of a sector's floor and ceiling as 256-color codes. Outdoor sectors
+// have no ceiling and show the sky above them instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectorColors {
+    pub floor: u8,
+    pub ceiling: Option<u8>, // None for an outdoor sector
+}
+
+pub struct MapFile {
+    pub name: String,
+    pub walls: Map,
+    // Places the player can start: (x, y, facing in radians). The first is
+    // used unless another is chosen.
+    pub spawns: Vec<(f64, f64, f64)>,
+    pub sprites: Vec<Sprite>, // Decorations standing in the map
+    pub lights: Vec<PointLight>, // Lamps brightening the surfaces around them
+    pub wall_colors: Vec<(u8, u8)>, // (material, 256-color code)
+    // Images drawn on walls of a material: (material, image file). Paths are
+    // relative to the map file once it has been loaded.
+    pub textures: Vec<(u8, PathBuf)>,
+    pub floor_texture: Option<PathBuf>, // Image drawn across the floor, one copy per cell
+    pub ceiling_texture: Option<PathBuf>,
+    // Facing in radians on arriving at each end of a teleporter pair: the
+    // letter, then the first and second cell with it in reading order.
+    // Teleporters not listed keep the player's facing.
+    pub teleporter_facing: Vec<(u8, [f64; 2])>,
+    // Walls shorter than full height: (cell, fraction of full height). They
+    // can be seen over but not walked through.
+    pub wall_heights: Vec<((usize, usize), f64)>,
+    // Cells belonging to a sector, which has its own floor and ceiling
+    // colors: (cell, sector letter)
+    pub sectors: Vec<((usize, usize), u8)>,
+    pub sector_colors: Vec<(u8, SectorColors)>, // (sector letter, its colors)
+    // Cells lit less than fully: (cell, light level from 0, darkest, to
+    // MAX_LIGHT_LEVEL)
+    pub light_levels: Vec<((usize, usize), u8)>,
+    pub hazards: Vec<((usize, usize), Hazard)>, // Floor cells that hurt to stand on
+    pub environment: Environment,
+}
+
+impl MapFile {
+    // Read a map file by its extension: .toml, Tiled's .tmx, a .png image, or
+    // anything else as a plain wall grid. Maps without a name are named after
+    // the file.
+    pub fn load(path: &Path) -> Result<Self, RaycastError> {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        if extension == Some("png") {
+            #[cfg(feature = "image-maps")]
+            {
+                let map = Self::load_image(path)?;
+                map.validate()?;
+                return Ok(map);
+            }
+            #[cfg(not(feature = "image-maps"))]
+            return Err(RaycastError::Config(
+                "PNG maps require building with the image-maps feature".to_string(),
+            ));
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| RaycastError::Config(format!("map {}: {}", path.display(), err)))?;
+        let mut map = match extension {
+            Some("toml") => Self::parse_toml(&text)?,
+            Some("tmx") => Self::parse_tmx(&text)?,
+            _ => Self::from_walls(parse_walls(&text)?),
+        };
+        if map.name.is_empty() {
+            map.name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        }
+        // Generated textures are named rather than found next to the map
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let planes = map.floor_texture.iter_mut().chain(&mut map.ceiling_texture);
+        for texture in map.textures.iter_mut().map(|(_, texture)| texture).chain(planes) {
+            if texture.to_str().and_then(GeneratedTexture::from_name).is_none() {
+                *texture = directory.join(&*texture);
+            }
+        }
+        map.validate()?;
+        Ok(map)
+    }
+
+    // Read a map that didn't come from a file, such as one piped in, so has
+    // no extension to go by: TOML if it sets `walls`, otherwise a plain wall
+    // grid. Maps without a name are left unnamed.
+    pub fn parse_text(text: &str) -> Result<Self, RaycastError> {
+        let is_toml = text
+            .lines()
+            .filter_map(|line| strip_comment(line).split_once('='))
+            .any(|(key, _)| key.trim() == "walls");
+        let map = if is_toml { Self::parse_toml(text)? } else { Self::from_walls(parse_walls(text)?) };
+        map.validate()?;
+        Ok(map)
+    }
+
+    // A map of just walls, with nothing else set
+    pub fn from_walls(walls: Map) -> Self {
+        MapFile {
+            name: String::new(),
+            walls,
+            spawns: Vec::new(),
+            sprites: Vec::new(),
+            lights: Vec::new(),
+            wall_colors: Vec::new(),
+            textures: Vec::new(),
+            floor_texture: None,
+            ceiling_texture: None,
+            teleporter_facing: Vec::new(),
+            wall_heights: Vec::new(),
+            sectors: Vec::new(),
+            sector_colors: Vec::new(),
+            light_levels: Vec::new(),
+            hazards: Vec::new(),
+            environment: Environment::default(),
+        }
+    }
+
+    // Write the map to a file: as TOML if the path ends in .toml, otherwise
+    // as a plain wall grid, which leaves out the name, spawn point, colors
+    // and teleporter facings
+    pub fn save(&self, path: &Path) -> Result<(), RaycastError> {
+        let text = if path.extension().is_some_and(|extension| extension == "toml") {
+            self.toml_in(path.parent().unwrap_or(Path::new("")))
+        } else {
+            walls_text(&self.walls)
+        };
+        std::fs::write(path, text).map_err(|err| RaycastError::Config(format!("map {}: {}", path.display(), err)))
+    }
+
+    // The map in the TOML format read by `parse_toml`
+    pub fn to_toml(&self) -> String {
+        self.toml_in(Path::new(""))
+    }
+
+    // The map as TOML for a file in `directory`, which texture paths are
+    // written relative to
+    fn toml_in(&self, directory: &Path) -> String {
+        // Quotes and backslashes can't be written in a basic string without escapes
+        let name: String = self.name.chars().filter(|&c| c != '"' && c != '\\').collect();
+        let mut text = format!("name = \"{}\"\nwalls = \"\"\"\n{}\"\"\"\n", name, walls_text(&self.walls));
+        if !self.wall_heights.is_empty() {
+            let heights = self
+                .wall_heights
+                .iter()
+                .map(|&(cell, height)| (cell, b'0' + (height * 10.0).round().clamp(1.0, 9.0) as u8));
+            text.push_str(&self.layer_text("heights", b'0', heights));
+        }
+        if !self.sectors.is_empty() {
+            text.push_str(&self.layer_text("sectors", b'0', self.sectors.iter().copied()));
+        }
+        if !self.light_levels.is_empty() {
+            let levels = self.light_levels.iter().map(|&(cell, level)| (cell, hex_digit(level)));
+            text.push_str(&self.layer_text("light", hex_digit(MAX_LIGHT_LEVEL), levels));
+        }
+        if !self.hazards.is_empty() {
+            let hazards = self.hazards.iter().map(|&(cell, hazard)| (cell, hazard.to_char()));
+            text.push_str(&self.layer_text("hazards", b'0', hazards));
+        }
+        let header = if self.spawns.len() > 1 { "[[spawn]]" } else { "[spawn]" };
+        for (x, y, angle) in &self.spawns {
+            text.push_str(&format!("\n{}\nx = {}\ny = {}\nangle = {}\n", header, x, y, angle.to_degrees().round()));
+        }
+        let header = if self.sprites.len() > 1 { "[[sprite]]" } else { "[sprite]" };
+        for sprite in &self.sprites {
+            text.push_str(&format!("\n{}\nx = {}\ny = {}\nkind = \"{}\"\n", header, sprite.x, sprite.y, sprite.kind.name()));
+            if sprite.color != sprite.kind.default_color() {
+                text.push_str(&format!("color = {}\n", sprite.color));
+            }
+        }
+        let header = if self.lights.len() > 1 { "[[light]]" } else { "[light]" };
+        for light in &self.lights {
+            text.push_str(&format!("\n{}\nx = {}\ny = {}\n", header, light.x, light.y));
+            if light.radius != DEFAULT_LIGHT_RADIUS {
+                text.push_str(&format!("radius = {}\n", light.radius));
+            }
+            if light.intensity != DEFAULT_LIGHT_INTENSITY {
+                text.push_str(&format!("intensity = {}\n", light.intensity));
+            }
+        }
+        if !self.wall_colors.is_empty() {
+            text.push_str("\n[colors]\n");
+            for (material, color) in &self.wall_colors {
+                text.push_str(&format!("{} = {}\n", material, color));
+            }
+        }
+        let planes = [("floor", &self.floor_texture), ("ceiling", &self.ceiling_texture)]
+            .into_iter()
+            .filter_map(|(key, texture)| Some((key.to_string(), texture.as_ref()?)));
+        let textures: Vec<(String, &PathBuf)> = self
+            .textures
+            .iter()
+            .map(|(material, texture)| (material.to_string(), texture))
+            .chain(planes)
+            .collect();
+        if !textures.is_empty() {
+            text.push_str("\n[textures]\n");
+            for (key, texture) in textures {
+                let texture = texture.strip_prefix(directory).unwrap_or(texture);
+                text.push_str(&format!("{} = \"{}\"\n", key, texture.to_string_lossy().replace('\\', "/")));
+            }
+        }
+        if !self.teleporter_facing.is_empty() {
+            text.push_str("\n[teleporters]\n");
+            for (letter, [first, second]) in &self.teleporter_facing {
+                let (first, second) = (first.to_degrees().round(), second.to_degrees().round());
+                text.push_str(&format!("{} = [{}, {}]\n", *letter as char, first, second));
+            }
+        }
+        if !self.sector_colors.is_empty() {
+            text.push_str("\n[sector_colors]\n");
+            for (letter, colors) in &self.sector_colors {
+                let ceiling = colors.ceiling.map_or("\"sky\"".to_string(), |ceiling| ceiling.to_string());
+                text.push_str(&format!("{} = [{}, {}]\n", *letter as char, colors.floor, ceiling));
+            }
+        }
+        let environment = &self.environment;
+        let default = Environment::default();
+        if *environment != default {
+            text.push_str("\n[environment]\n");
+            if environment.fog_density != default.fog_density {
+                text.push_str(&format!("fog = {}\n", environment.fog_density));
+            }
+            if environment.fog_start != default.fog_start {
+                text.push_str(&format!("fog_start = {}\n", environment.fog_start));
+            }
+            if environment.fog_end != default.fog_end {
+                text.push_str(&format!("fog_end = {}\n", environment.fog_end));
+            }
+            if environment.fog_color != default.fog_color {
+                text.push_str(&format!("fog_color = {}\n", environment.fog_color));
+            }
+            if environment.ambient != default.ambient {
+                text.push_str(&format!("ambient = {}\n", environment.ambient));
+            }
+            if let Some(color) = environment.sky_color {
+                text.push_str(&format!("sky = {}\n", color));
+            }
+            if let Some(color) = environment.floor_color {
+                text.push_str(&format!("floor = {}\n", color));
+            }
+        }
+        text
+    }
+
+    // A grid the size of the walls as a multi-line string, with the given
+    // characters in their cells and `blank` everywhere else
+    fn layer_text(&self, key: &str, blank: u8, cells: impl Iterator<Item = ((usize, usize), u8)>) -> String {
+        let mut layer = Map::filled(self.walls.width(), self.walls.height(), blank);
+        for ((x, y), cell) in cells {
+            layer.set(x, y, cell);
+        }
+        format!("{} = \"\"\"\n{}\"\"\"\n", key, walls_text(&layer))
+    }
+
+    // Check that the map can be played: its border is solid so the player
+    // can't walk off the edge, teleporters come in pairs, and the spawn points
+    // and sprites are in open cells
+    pub fn validate(&self) -> Result<(), RaycastError> {
+        let invalid = |(x, y): (usize, usize), reason: String| RaycastError::MapInvalid {
+            row: y + 1,
+            column: x + 1,
+            reason,
+        };
+        let cells = || self.walls.cells();
+        let (width, height) = (self.walls.width(), self.walls.height());
+        let edge = |(x, y): (usize, usize)| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        if let Some((position, cell)) = cells().find(|&(position, cell)| edge(position) && is_open(cell)) {
+            return Err(invalid(
+                position,
+                format!("border cell {:?} is open; the map must be enclosed by walls", cell as char),
+            ));
+        }
+
+        let mut teleporters: HashMap<u8, Vec<(usize, usize)>> = HashMap::new();
+        for (position, cell) in cells().filter(|(_, cell)| cell.is_ascii_uppercase()) {
+            teleporters.entry(cell).or_default().push(position);
+        }
+        if let Some((position, letter)) = cells().find(|(_, cell)| teleporters.get(cell).is_some_and(|at| at.len() != 2)) {
+            return Err(invalid(
+                position,
+                format!("teleporter {} appears {} times (expected a pair)", letter as char, teleporters[&letter].len()),
+            ));
+        }
+
+        for &(x, y, _) in &self.spawns {
+            let cell = (x.floor() as usize, y.floor() as usize);
+            let open = self.walls.get(cell.0, cell.1).is_some_and(is_open);
+            if x < 0.0 || y < 0.0 || !open {
+                return Err(invalid(cell, format!("spawn ({:.2}, {:.2}) is inside a wall", x, y)));
+            }
+        }
+        for sprite in &self.sprites {
+            let cell = (sprite.x.floor() as usize, sprite.y.floor() as usize);
+            if !self.walls.get(cell.0, cell.1).is_some_and(is_open) {
+                return Err(invalid(cell, format!("sprite ({:.2}, {:.2}) is inside a wall", sprite.x, sprite.y)));
+            }
+        }
+        for light in &self.lights {
+            let cell = (light.x.floor() as usize, light.y.floor() as usize);
+            if !self.walls.get(cell.0, cell.1).is_some_and(is_open) {
+                return Err(invalid(cell, format!("light ({:.2}, {:.2}) is inside a wall", light.x, light.y)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse_toml(text: &str) -> Result<Self, RaycastError> {
+        let mut name = String::new();
+        let mut walls = None;
+        let mut heights = None;
+        let mut sectors = None;
+        let mut light = None;
+        let mut hazards = None;
+        let mut sector_colors = Vec::new();
+        let mut environment = Environment::default();
+        let mut fog_line = 0; // Line of the last fog_start or fog_end
+        // Spawn points as read: (line of the table, x, y, facing)
+        let mut spawns: Vec<(usize, Option<f64>, Option<f64>, f64)> = Vec::new();
+        let mut sprites: Vec<SpriteTable> = Vec::new();
+        let mut lights: Vec<LightTable> = Vec::new();
+        let mut wall_colors = Vec::new();
+        let mut textures = Vec::new();
+        let (mut floor_texture, mut ceiling_texture) = (None, None);
+        let mut teleporter_facing = Vec::new();
+        let mut table = "";
+
+        let mut lines = text.lines().enumerate().map(|(index, text)| (index + 1, text));
+        while let Some((line, text)) = lines.next() {
+            let error = |reason: String| RaycastError::MapParse { line, reason };
+            let content = strip_comment(text).trim();
+            if content.is_empty() {
+                continue;
+            }
+            if let Some(header) = content.strip_prefix('[') {
+                // Spawn points, sprites and lights can be written as arrays of tables
+                let header = match header.strip_prefix('[').and_then(|header| header.strip_suffix("]]")) {
+                    Some(header) => Some(header).filter(|header| matches!(header.trim(), "spawn" | "sprite" | "light")),
+                    None => header.strip_suffix(']'),
+                };
+                table = header
+                    .map(str::trim)
+                    .filter(|header| {
+                        let tables = [
+                            "spawn",
+                            "sprite",
+                            "light",
+                            "colors",
+                            "textures",
+                            "teleporters",
+                            "sector_colors",
+                            "environment",
+                        ];
+                        tables.contains(header)
+                    })
+                    .ok_or_else(|| error(format!("unknown table {}", content)))?;
+                match table {
+                    "spawn" => spawns.push((line, None, None, 0.0)),
+                    "sprite" => sprites.push(SpriteTable {
+                        line,
+                        x: None,
+                        y: None,
+                        kind: SpriteKind::default(),
+                        color: None,
+                    }),
+                    "light" => lights.push(LightTable {
+                        line,
+                        x: None,
+                        y: None,
+                        radius: DEFAULT_LIGHT_RADIUS,
+                        intensity: DEFAULT_LIGHT_INTENSITY,
+                    }),
+                    _ => {}
+                }
+                continue;
+            }
+
+            let (key, value) = content
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error("expected key = value".to_string()))?;
+            match (table, key) {
+                ("", "name") => name = parse_string(value).ok_or_else(|| error("expected a quoted name".to_string()))?,
+                ("", "walls") => {
+                    let grid = read_grid(key, value, &mut lines).map_err(error)?;
+                    // Report grid errors at their line in the file
+                    walls = Some(parse_walls(&grid).map_err(|err| match err {
+                        RaycastError::MapParse { line: row, reason } => RaycastError::MapParse {
+                            line: line + row,
+                            reason,
+                        },
+                        err => err,
+                    })?);
+                }
+                ("", "heights") => {
+                    let grid = read_grid(key, value, &mut lines).map_err(error)?;
+                    heights = Some(Layer::parse(key, line, &grid, b'0', u8::is_ascii_digit, "digits 0-9")?);
+                }
+                ("", "sectors") => {
+                    let grid = read_grid(key, value, &mut lines).map_err(error)?;
+                    let is_sector = |cell: &u8| *cell == b'0' || cell.is_ascii_lowercase();
+                    sectors = Some(Layer::parse(key, line, &grid, b'0', is_sector, "0 or a-z")?);
+                }
+                ("", "light") => {
+                    let grid = read_grid(key, value, &mut lines).map_err(error)?;
+                    let is_level = |cell: &u8| cell.is_ascii_digit() || (b'a'..=b'f').contains(cell);
+                    light = Some(Layer::parse(key, line, &grid, hex_digit(MAX_LIGHT_LEVEL), is_level, "0-9 or a-f")?);
+                }
+                ("", "hazards") => {
+                    let grid = read_grid(key, value, &mut lines).map_err(error)?;
+                    let is_hazard = |cell: &u8| *cell == b'0' || Hazard::from_char(*cell).is_some();
+                    hazards = Some(Layer::parse(key, line, &grid, b'0', is_hazard, "0, l or a")?);
+                }
+                ("spawn", "x" | "y" | "angle") => {
+                    let number: f64 = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f64| number.is_finite())
+                        .ok_or_else(|| error(format!("invalid number: {}", value)))?;
+                    let spawn = spawns.last_mut().expect("a spawn table was opened");
+                    match key {
+                        "x" => spawn.1 = Some(number),
+                        "y" => spawn.2 = Some(number),
+                        _ => spawn.3 = number.to_radians(),
+                    }
+                }
+                ("sprite", "x" | "y") => {
+                    let number: f64 = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f64| number.is_finite())
+                        .ok_or_else(|| error(format!("invalid number: {}", value)))?;
+                    let sprite = sprites.last_mut().expect("a sprite table was opened");
+                    match key {
+                        "x" => sprite.x = Some(number),
+                        _ => sprite.y = Some(number),
+                    }
+                }
+                ("sprite", "kind") => {
+                    let kind = parse_string(value)
+                        .as_deref()
+                        .and_then(SpriteKind::from_name)
+                        .ok_or_else(|| error(format!("invalid sprite kind (expected orb, pillar or barrel): {}", value)))?;
+                    sprites.last_mut().expect("a sprite table was opened").kind = kind;
+                }
+                ("sprite", "color") => {
+                    let color = value
+                        .parse()
+                        .map_err(|_| error(format!("invalid color: {}", value)))?;
+                    sprites.last_mut().expect("a sprite table was opened").color = Some(color);
+                }
+                ("light", "x" | "y") => {
+                    let number: f64 = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f64| number.is_finite())
+                        .ok_or_else(|| error(format!("invalid number: {}", value)))?;
+                    let light = lights.last_mut().expect("a light table was opened");
+                    match key {
+                        "x" => light.x = Some(number),
+                        _ => light.y = Some(number),
+                    }
+                }
+                ("light", "radius" | "intensity") => {
+                    let number = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f64| number.is_finite() && *number > 0.0)
+                        .ok_or_else(|| error(format!("invalid {} (expected more than 0): {}", key, value)))?;
+                    let light = lights.last_mut().expect("a light table was opened");
+                    match key {
+                        "radius" => light.radius = number,
+                        _ => light.intensity = number,
+                    }
+                }
+                ("colors", material) => {
+                    let material = material
+                        .parse()
+                        .ok()
+                        .filter(|material| (1..=9).contains(material))
+                        .ok_or_else(|| error(format!("invalid material (expected 1-9): {}", material)))?;
+                    let color = value
+                        .parse()
+                        .map_err(|_| error(format!("invalid color: {}", value)))?;
+                    wall_colors.push((material, color));
+                }
+                ("textures", "floor" | "ceiling") => {
+                    let path = parse_string(value).ok_or_else(|| error("expected a quoted file name".to_string()))?;
+                    let texture = if key == "floor" { &mut floor_texture } else { &mut ceiling_texture };
+                    *texture = Some(PathBuf::from(path));
+                }
+                ("textures", material) => {
+                    let material = material
+                        .parse()
+                        .ok()
+                        .filter(|material| (1..=9).contains(material))
+                        .ok_or_else(|| error(format!("invalid material (expected 1-9): {}", material)))?;
+                    let path = parse_string(value).ok_or_else(|| error("expected a quoted file name".to_string()))?;
+                    textures.push((material, PathBuf::from(path)));
+                }
+                ("teleporters", letter) => {
+                    let letter = Some(letter.as_bytes())
+                        .filter(|letter| letter.len() == 1 && letter[0].is_ascii_uppercase())
+                        .ok_or_else(|| error(format!("invalid teleporter (expected A-Z): {}", letter)))?[0];
+                    let degrees: Vec<f64> = value
+                        .strip_prefix('[')
+                        .and_then(|list| list.strip_suffix(']'))
+                        .unwrap_or(value)
+                        .split(',')
+                        .map(|number| number.trim().parse().ok().filter(|number: &f64| number.is_finite()))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| error(format!("invalid facing: {}", value)))?;
+                    let facing = match degrees[..] {
+                        [both] => [both, both],
+                        [first, second] => [first, second],
+                        _ => return Err(error(format!("expected one or two facings: {}", value))),
+                    };
+                    teleporter_facing.push((letter, facing.map(f64::to_radians)));
+                }
+                ("environment", "fog" | "fog_start" | "fog_end" | "ambient") => {
+                    let number = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f64| number.is_finite() && *number >= 0.0)
+                        .ok_or_else(|| error(format!("invalid {} (expected 0 or more): {}", key, value)))?;
+                    match key {
+                        "fog" => environment.fog_density = number,
+                        "fog_start" => {
+                            environment.fog_start = number;
+                            fog_line = line;
+                        }
+                        "fog_end" => {
+                            environment.fog_end = number;
+                            fog_line = line;
+                        }
+                        _ => environment.ambient = number,
+                    }
+                }
+                ("environment", "fog_color" | "sky" | "floor") => {
+                    let color = value
+                        .parse()
+                        .map_err(|_| error(format!("invalid color: {}", value)))?;
+                    match key {
+                        "fog_color" => environment.fog_color = color,
+                        "sky" => environment.sky_color = Some(color),
+                        _ => environment.floor_color = Some(color),
+                    }
+                }
+                ("sector_colors", letter) => {
+                    let letter = Some(letter.as_bytes())
+                        .filter(|letter| letter.len() == 1 && letter[0].is_ascii_lowercase())
+                        .ok_or_else(|| error(format!("invalid sector (expected a-z): {}", letter)))?[0];
+                    // A ceiling of "sky" leaves the sector open to the sky
+                    let colors: Vec<Option<u8>> = value
+                        .strip_prefix('[')
+                        .and_then(|list| list.strip_suffix(']'))
+                        .map(|list| {
+                            list.split(',')
+                                .map(|color| match color.trim() {
+                                    "\"sky\"" => Some(None),
+                                    color => color.parse().ok().map(Some),
+                                })
+                                .collect()
+                        })
+                        .unwrap_or(None)
+                        .ok_or_else(|| error(format!("invalid colors: {}", value)))?;
+                    let [Some(floor), ceiling] = colors[..] else {
+                        return Err(error(format!("expected a floor and a ceiling color: {}", value)));
+                    };
+                    sector_colors.push((letter, SectorColors { floor, ceiling }));
+                }
+                _ => return Err(error(format!("unknown key {}", key))),
+            }
+        }
+
+        let walls = walls.ok_or_else(|| RaycastError::MapParse {
+            line: text.lines().count(),
+            reason: "missing walls".to_string(),
+        })?;
+        if let Some((letter, _)) = teleporter_facing
+            .iter()
+            .find(|&&(letter, _)| !walls.cells().any(|(_, cell)| cell == letter))
+        {
+            return Err(RaycastError::Config(format!("teleporter {} has a facing but isn't in the map", *letter as char)));
+        }
+        let mut wall_heights = Vec::new();
+        for layer in heights.iter().chain(&sectors).chain(&light).chain(&hazards) {
+            layer.check_size(&walls)?;
+        }
+        for ((x, y), digit) in heights.iter().flat_map(Layer::cells) {
+            if !has_height(walls.get(x, y).unwrap_or(b'0')) {
+                return Err(RaycastError::MapInvalid {
+                    row: y + 1,
+                    column: x + 1,
+                    reason: "only solid walls can be shorter than full height".to_string(),
+                });
+            }
+            wall_heights.push(((x, y), (digit - b'0') as f64 / 10.0));
+        }
+        let sectors: Vec<_> = sectors.iter().flat_map(Layer::cells).collect();
+        if let Some(&(_, letter)) = sectors
+            .iter()
+            .find(|&&(_, letter)| !sector_colors.iter().any(|&(colored, _)| colored == letter))
+        {
+            return Err(RaycastError::Config(format!("sector {} has no colors", letter as char)));
+        }
+        if let Some((letter, _)) = sector_colors
+            .iter()
+            .find(|&&(letter, _)| !sectors.iter().any(|&(_, cell)| cell == letter))
+        {
+            return Err(RaycastError::Config(format!("sector {} has colors but isn't in the map", *letter as char)));
+        }
+        let light_levels = light
+            .iter()
+            .flat_map(Layer::cells)
+            .map(|(cell, digit)| (cell, (digit as char).to_digit(16).unwrap_or(0) as u8))
+            .collect();
+        let mut hazard_cells = Vec::new();
+        for ((x, y), c) in hazards.iter().flat_map(Layer::cells) {
+            if !is_open(walls.get(x, y).unwrap_or(b'1')) {
+                return Err(RaycastError::MapInvalid {
+                    row: y + 1,
+                    column: x + 1,
+                    reason: "hazards can only be on open floor".to_string(),
+                });
+            }
+            hazard_cells.extend(Hazard::from_char(c).map(|hazard| ((x, y), hazard)));
+        }
+        let spawns = spawns
+            .into_iter()
+            .map(|(line, x, y, angle)| {
+                let (Some(x), Some(y)) = (x, y) else {
+                    return Err(RaycastError::MapParse { line, reason: "spawn needs both x and y".to_string() });
+                };
+                if !(0.0..walls.width() as f64).contains(&x) || !(0.0..walls.height() as f64).contains(&y) {
+                    return Err(RaycastError::Config(format!("spawn ({}, {}) is outside the map", x, y)));
+                }
+                Ok((x, y, angle))
+            })
+            .collect::<Result<_, _>>()?;
+        if environment.fog_end != 0.0 && environment.fog_end <= environment.fog_start {
+            return Err(RaycastError::MapParse {
+                line: fog_line,
+                reason: format!(
+                    "fog_end ({}) must be farther than fog_start ({})",
+                    environment.fog_end, environment.fog_start
+                ),
+            });
+        }
+        let sprites = sprites
+            .into_iter()
+            .map(|SpriteTable { line, x, y, kind, color }| {
+                let (Some(x), Some(y)) = (x, y) else {
+                    return Err(RaycastError::MapParse { line, reason: "sprite needs both x and y".to_string() });
+                };
+                if !(0.0..walls.width() as f64).contains(&x) || !(0.0..walls.height() as f64).contains(&y) {
+                    return Err(RaycastError::Config(format!("sprite ({}, {}) is outside the map", x, y)));
+                }
+                let color = color.unwrap_or_else(|| kind.default_color());
+                Ok(Sprite { x, y, color, kind })
+            })
+            .collect::<Result<_, _>>()?;
+        let lights = lights
+            .into_iter()
+            .map(|LightTable { line, x, y, radius, intensity }| {
+                let (Some(x), Some(y)) = (x, y) else {
+                    return Err(RaycastError::MapParse { line, reason: "light needs both x and y".to_string() });
+                };
+                if !(0.0..walls.width() as f64).contains(&x) || !(0.0..walls.height() as f64).contains(&y) {
+                    return Err(RaycastError::Config(format!("light ({}, {}) is outside the map", x, y)));
+                }
+                Ok(PointLight { x, y, radius, intensity })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(MapFile {
+            name,
+            walls,
+            spawns,
+            sprites,
+            lights,
+            wall_colors,
+            textures,
+            floor_texture,
+            ceiling_texture,
+            teleporter_facing,
+            wall_heights,
+            sectors,
+            sector_colors,
+            light_levels,
+            hazards: hazard_cells,
+            environment,
+        })
+    }
+
+    // Read a map drawn as an image, one pixel per cell: dark pixels are
+    // walls, light pixels are floor, red pixels mark spawn points (in reading
+    // order) and green pixels mark exits.
+    // Requires the `image-maps` feature.
+    #[cfg(feature = "image-maps")]
+    pub fn load_image(path: &Path) -> Result<Self, RaycastError> {
+        let image = image::open(path)
+            .map_err(|err| RaycastError::Config(format!("map {}: {}", path.display(), err)))?
+            .to_rgb8();
+        let mut spawns = Vec::new();
+        let rows: Vec<Vec<u8>> = image
+            .rows()
+            .enumerate()
+            .map(|(y, row)| {
+                row.enumerate()
+                    .map(|(x, &image::Rgb([r, g, b]))| {
+                        if r >= MARKER_PIXEL_BRIGHT && g <= MARKER_PIXEL_DIM && b <= MARKER_PIXEL_DIM {
+                            spawns.push((x as f64 + 0.5, y as f64 + 0.5, 0.0));
+                            return b'0';
+                        }
+                        if g >= MARKER_PIXEL_BRIGHT && r <= MARKER_PIXEL_DIM && b <= MARKER_PIXEL_DIM {
+                            return EXIT_CELL;
+                        }
+                        let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+                        if luma < 128 { b'1' } else { b'0' }
+                    })
+                    .collect()
+            })
+            .collect();
+        let walls = Map::from_rows(&rows)
+            .ok_or_else(|| RaycastError::Config(format!("map {}: image is empty", path.display())))?;
+        Ok(MapFile {
+            name: path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+            spawns,
+            ..MapFile::from_walls(walls)
+        })
+    }
+
+    // Import a Tiled map. The first tile layer, which must be CSV encoded,
+    // becomes the wall grid: empty tiles are floor and tile N of the tileset
+    // is material N. Each object named "spawn" adds a spawn point, facing the
+    // way given by an optional "angle" property in degrees.
+    pub fn parse_tmx(text: &str) -> Result<Self, RaycastError> {
+        let error = |offset: usize, reason: String| RaycastError::MapParse {
+            line: text[..offset].lines().count().max(1),
+            reason,
+        };
+        let (map_offset, map) = find_tag(text, 0, "map").ok_or_else(|| error(0, "no <map> element".to_string()))?;
+        let number = |tag: &str, name: &str| -> Result<f64, RaycastError> {
+            attribute(tag, name)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| error(map_offset, format!("<map> needs a numeric {} attribute", name)))
+        };
+        let (width, height) = (number(map, "width")? as usize, number(map, "height")? as usize);
+        if width == 0 || height == 0 {
+            return Err(error(map_offset, "map has no tiles".to_string()));
+        }
+        let tile_size = (number(map, "tilewidth")?, number(map, "tileheight")?);
+        let first_gid = find_tag(text, 0, "tileset")
+            .and_then(|(_, tileset)| attribute(tileset, "firstgid")?.parse().ok())
+            .unwrap_or(1);
+
+        let (data_offset, data) = find_tag(text, 0, "data").ok_or_else(|| error(0, "no tile layer".to_string()))?;
+        if attribute(data, "encoding") != Some("csv") || attribute(data, "compression").is_some() {
+            return Err(error(data_offset, "only CSV encoded tile layers are supported".to_string()));
+        }
+        let start = data_offset + data.len() + 2;
+        let end = text[start..]
+            .find("</data>")
+            .map(|length| start + length)
+            .ok_or_else(|| error(data_offset, "tile layer is never closed".to_string()))?;
+        let mut cells = Vec::with_capacity(width * height);
+        for tile in text[start..end].split(',').map(str::trim) {
+            // The top bits of a tile ID flag flipped tiles; the look doesn't matter here
+            let gid = tile
+                .parse::<u32>()
+                .map(|gid| gid & 0x1fff_ffff)
+                .map_err(|_| error(data_offset, format!("invalid tile ID: {}", tile)))?;
+            let cell = match gid.checked_sub(first_gid) {
+                _ if gid == 0 => b'0',
+                Some(index @ 0..=8) => b'1' + index as u8,
+                _ => return Err(error(data_offset, format!("tile {} has no wall material (tiles 1-9 are walls)", gid))),
+            };
+            cells.push(cell);
+        }
+        if cells.len() != width * height {
+            return Err(error(
+                data_offset,
+                format!("tile layer has {} tiles (expected {})", cells.len(), width * height),
+            ));
+        }
+
+        let mut spawns = Vec::new();
+        let mut offset = 0;
+        while let Some((object_offset, object)) = find_tag(text, offset, "object") {
+            offset = object_offset + object.len() + 2;
+            if attribute(object, "name") != Some("spawn") {
+                continue;
+            }
+            let position = |name: &str, size: f64| {
+                attribute(object, name)
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .map(|pixels| pixels / size)
+                    .ok_or_else(|| error(object_offset, format!("spawn object needs a numeric {} attribute", name)))
+            };
+            let (x, y) = (position("x", tile_size.0)?, position("y", tile_size.1)?);
+            if !(0.0..width as f64).contains(&x) || !(0.0..height as f64).contains(&y) {
+                return Err(error(object_offset, format!("spawn ({:.2}, {:.2}) is outside the map", x, y)));
+            }
+            // Properties live inside the object element, unless it closes itself
+            let body = if object.ends_with('/') {
+                ""
+            } else {
+                let rest = &text[offset..];
+                &rest[..rest.find("</object>").unwrap_or(rest.len())]
+            };
+            let angle = std::iter::successors(find_tag(body, 0, "property"), |&(at, tag)| {
+                find_tag(body, at + tag.len(), "property")
+            })
+            .find(|(_, property)| attribute(property, "name") == Some("angle"))
+            .and_then(|(_, property)| attribute(property, "value")?.parse::<f64>().ok())
+            .unwrap_or(0.0);
+            spawns.push((x, y, angle.to_radians()));
+        }
+
+        Ok(MapFile {
+            spawns,
+            ..MapFile::from_walls(
+                Map::from_rows(&cells.chunks(width).collect::<Vec<_>>()).expect("tile count was checked"),
+            )
+        })
+    }
+}
+
+// The first `<name ...>` start tag at or after `from`, as its byte offset and
+// the text between `<` and `>`
+fn find_tag<'a>(text: &'a str, from: usize, name: &str) -> Option<(usize, &'a str)> {
+    let pattern = format!("<{}", name);
+    let mut from = from;
+    loop {
+        let start = from + text[from..].find(&pattern)?;
+        let after = &text[start + pattern.len()..];
+        // Skip longer names sharing the prefix, such as <objectgroup> for <object>
+        if after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            let end = after.find('>')?;
+            return Some((start, &text[start + 1..start + pattern.len() + end]));
+        }
+        from = start + pattern.len();
+    }
+}
+
+// Value of an XML attribute in a start tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=\"", name);
+    let mut from = 0;
+    loop {
+        let start = from + tag[from..].find(&pattern)?;
+        let value = &tag[start + pattern.len()..];
+        // Require whitespace before the name so "x" doesn't match inside "offsetx"
+        if tag[..start].ends_with(char::is_whitespace) {
+            return value.find('"').map(|end| &value[..end]);
+        }
+        from = start + pattern.len();
+    }
+}
+
+// Rows of a wall grid, one per line
+fn walls_text(walls: &Map) -> String {
+    walls.rows().map(|row| format!("{}\n", String::from_utf8_lossy(row))).collect()
+}
+
+// A `[[sprite]]` table as read from a TOML map, before its position has
+// been checked
+struct SpriteTable {
+    line: usize, // Line of the table header in the file
+    x: Option<f64>,
+    y: Option<f64>,
+    kind: SpriteKind,
+    color: Option<u8>, // None for the kind's usual color
+}
+
+// A `[[light]]` table as read from a TOML map, before its position has
+// been checked
+struct LightTable {
+    line: usize, // Line of the table header in the file
+    x: Option<f64>,
+    y: Option<f64>,
+    radius: f64,
+    intensity: f64,
+}
+
+// A grid read from a TOML map that lays extra information over the walls,
+// such as wall heights. Its characters have been checked but not its size.
+struct Layer<'a> {
+    key: &'a str,
+    line: usize, // Line of the key in the file
+    rows: Vec<Vec<u8>>,
+    blank: u8, // Character for cells with nothing to add
+}
+
+impl<'a> Layer<'a> {
+    fn parse(
+        key: &'a str,
+        line: usize,
+        grid: &str,
+        blank: u8,
+        valid: impl Fn(&u8) -> bool,
+        expected: &str,
+    ) -> Result<Self, RaycastError> {
+        let rows: Vec<Vec<u8>> = grid.lines().map(|row| row.as_bytes().to_vec()).collect();
+        if let Some(row) = rows.iter().position(|row| !row.iter().all(&valid)) {
+            return Err(RaycastError::MapParse {
+                line: line + row + 1,
+                reason: format!("{} must be {}", key, expected),
+            });
+        }
+        Ok(Layer { key, line, rows, blank })
+    }
+
+    fn check_size(&self, walls: &Map) -> Result<(), RaycastError> {
+        if self.rows.len() != walls.height() || self.rows.iter().any(|row| row.len() != walls.width()) {
+            return Err(RaycastError::MapParse {
+                line: self.line,
+                reason: format!("{} must be {}x{} like the walls", self.key, walls.width(), walls.height()),
+            });
+        }
+        Ok(())
+    }
+
+    // Cells with anything but the blank character
+    fn cells(&self) -> impl Iterator<Item = ((usize, usize), u8)> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &cell)| ((x, y), cell)))
+            .filter(|&(_, cell)| cell != self.blank)
+    }
+}
+
+// Lowercase hexadecimal digit for a light level
+fn hex_digit(level: u8) -> u8 {
+    char::from_digit(level.into(), 16).map_or(b'0', |digit| digit as u8)
+}
+
+// Lines of a multi-line string value up to its closing quotes, trimmed
+fn read_grid<'a>(key: &str, value: &str, lines: &mut impl Iterator<Item = (usize, &'a str)>) -> Result<String, String> {
+    if value != "\"\"\"" {
+        return Err(format!("{} must be a multi-line string starting with \"\"\"", key));
+    }
+    let mut grid = String::new();
+    for (_, row) in lines.by_ref() {
+        if row.trim() == "\"\"\"" {
+            return Ok(grid);
+        }
+        grid.push_str(row.trim());
+        grid.push('\n');
+    }
+    Err(format!("{} string is never closed", key))
+}
+
+// Whether a map character can be given a height: a plain solid wall
+pub(crate) fn has_height(cell: u8) -> bool {
+    matches!(cell, b'1'..=b'9') && !is_open(cell)
+}
+
+// Whether the player can walk through a map character, shared by map
+// checks and the raycaster. Doors count as open; the raycaster also checks
+// whether each one is.
+pub(crate) fn is_open(cell: u8) -> bool {
+    match cell {
+        b'0' | b'A'..=b'Z' | DOOR_CELL | EXIT_CELL => true,
+        b'1'..=b'9' => Translucency::for_material(cell - b'0').is_some_and(|translucency| !translucency.solid),
+        _ => false,
+    }
+}
+
+// A line with any `#` comment removed, ignoring `#` inside quotes
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Contents of a basic quoted string without escapes
+pub(crate) fn parse_string(value: &str) -> Option<String> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .filter(|value| !value.contains(['"', '\\']))
+        .map(str::to_string)
+}
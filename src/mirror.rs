@@ -0,0 +1,76 @@
+// Mirrors: wall cells whose faces reflect the view. A ray that strikes one
+// bounces off and carries on from where it struck, so the view shows the
+// geometry opposite the mirror, appearing as far away as the whole path the
+// ray took. Facing mirrors would bounce rays forever, so bouncing stops
+// after MAX_BOUNCES and the last mirror is drawn as a plain wall.
+
+pub const MIRROR_CELL: u8 = b'@';
+
+pub const MAX_BOUNCES: usize = 4; // Reflections of reflections shown at most
+
+// Path of a ray as it bounces off mirrors, as legs that each start at a
+// world point, once the ray has travelled some distance, and head off in a
+// straight line
+#[derive(Clone, Debug)]
+pub struct RayPath {
+    legs: Vec<Leg>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Leg {
+    start: f64, // Distance travelled before the leg
+    origin: (f64, f64),
+    direction: (f64, f64), // Unit vector
+}
+
+impl RayPath {
+    pub fn new(origin: (f64, f64), angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        RayPath {
+            legs: vec![Leg {
+                start: 0.0,
+                origin,
+                direction: (cos, sin),
+            }],
+        }
+    }
+
+    // World point a distance along the path
+    pub fn point(&self, distance: f64) -> (f64, f64) {
+        let leg = self
+            .legs
+            .iter()
+            .rev()
+            .find(|leg| leg.start <= distance)
+            .unwrap_or(&self.legs[0]);
+        let along = distance - leg.start;
+        (leg.origin.0 + leg.direction.0 * along, leg.origin.1 + leg.direction.1 * along)
+    }
+
+    // Bounce the path off a mirror it strikes a distance along, whose face
+    // has the given unit normal
+    pub fn bounce(&mut self, distance: f64, normal: (f64, f64)) {
+        let origin = self.point(distance);
+        let (dx, dy) = self.direction();
+        let dot = dx * normal.0 + dy * normal.1;
+        self.legs.push(Leg {
+            start: distance,
+            origin,
+            direction: (dx - 2.0 * dot * normal.0, dy - 2.0 * dot * normal.1),
+        });
+    }
+
+    // Direction the path heads off in after its last bounce, as a unit vector
+    pub fn direction(&self) -> (f64, f64) {
+        self.legs[self.legs.len() - 1].direction
+    }
+
+    pub fn bounces(&self) -> usize {
+        self.legs.len() - 1
+    }
+
+    // Distance to the first mirror the path bounced off, if any
+    pub fn first_bounce(&self) -> Option<f64> {
+        self.legs.get(1).map(|leg| leg.start)
+    }
+}
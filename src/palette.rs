@@ -0,0 +1,154 @@
+// Conversions between the xterm 256-color palette and RGB, used to shade
+// palette colors without leaving the 256-color output format.
+
+// A 24-bit color as (red, green, blue)
+pub type Rgb = (u8, u8, u8);
+
+// RGB values of the 16 system colors (xterm defaults)
+const SYSTEM_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// Brightness bands of the 16-color fallback, by a color's strongest channel
+const BASIC_DARKEST: u8 = 60; // Colored pixels dimmer than this are drawn black
+const BASIC_BRIGHTEST: u8 = 200; // and brighter ones use the bright system colors
+const BASIC_GRAY_CHROMA: u8 = 40; // Colors with channels closer than this are grays
+
+// Channel intensities of the 6x6x6 color cube (indices 16-231)
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+const CUBE_STEP: f64 = 40.0; // Between the cube's levels above the first
+const GRAY_STEP: f64 = 10.0; // Between the grayscale ramp's levels
+
+pub fn to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => SYSTEM_COLORS[index as usize],
+        16..=231 => {
+            let cube = index - 16;
+            (
+                CUBE_LEVELS[(cube / 36) as usize],
+                CUBE_LEVELS[(cube / 6 % 6) as usize],
+                CUBE_LEVELS[(cube % 6) as usize],
+            )
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+// Nearest color in the cube or grayscale ramp. System colors are skipped
+// because terminals often remap them.
+pub fn from_rgb(r: u8, g: u8, b: u8) -> u8 {
+    // Nearest of CUBE_LEVELS, the lower one on a tie
+    let nearest_level = |c: u8| match c {
+        0..=47 => 0,
+        48..=115 => 1,
+        _ => 2 + (c as usize - 116) / CUBE_STEP as usize,
+    };
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+    let cube_rgb = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23);
+    let gray_index = 232 + gray_step;
+    let gray = (8 + gray_step * 10) as u8;
+    
+    if distance_sq((r, g, b), (gray, gray, gray)) < distance_sq((r, g, b), cube_rgb) {
+        gray_index as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+// Nearest color after nudging by an ordered-dither threshold (0.0-1.0) up
+// to half a step of the grayscale ramp or color cube either way, so a
+// gradient running between two palette colors mixes them in the pattern
+// of the thresholds instead of banding
+pub fn from_rgb_dithered((r, g, b): Rgb, threshold: f64) -> u8 {
+    let gray = r.max(g).max(b) - r.min(g).min(b) < BASIC_GRAY_CHROMA;
+    let step = if gray { GRAY_STEP } else { CUBE_STEP };
+    let nudge = (threshold - 0.5) * step;
+    let channel = |c: u8| (c as f64 + nudge).round().clamp(0.0, 255.0) as u8;
+    from_rgb(channel(r), channel(g), channel(b))
+}
+
+// The system color for a palette color on a 16-color terminal. Matching
+// by nearest RGB would turn most shaded colors black, so this keeps their
+// hue and sorts them into brightness bands instead: the bright variant, the
+// normal one, or black once too dark to tell apart. Grays step through
+// black, dark gray, light gray and white.
+pub fn to_basic(index: u8) -> u8 {
+    if index < 16 {
+        return index;
+    }
+    let (r, g, b) = to_rgb(index);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    if max - min < BASIC_GRAY_CHROMA {
+        return match luma((r, g, b)) {
+            brightness if brightness < 0.15 => 0,
+            brightness if brightness < 0.55 => 8,
+            brightness if brightness < 0.85 => 7,
+            _ => 15,
+        };
+    }
+    if max < BASIC_DARKEST {
+        return 0;
+    }
+    // Channels close to the strongest one count towards the hue, as bits
+    // red 1, green 2 and blue 4 in the order of the system colors
+    let strong = |channel: u8| (channel as u32 * 10 >= max as u32 * 6) as u8;
+    let hue = strong(r) | strong(g) << 1 | strong(b) << 2;
+    if max >= BASIC_BRIGHTEST {
+        hue + 8
+    } else {
+        hue
+    }
+}
+
+// Scale the brightness of a palette color by `factor` (1.0 = unchanged)
+pub fn scale(index: u8, factor: f64) -> u8 {
+    let (r, g, b) = scale_rgb(to_rgb(index), factor);
+    from_rgb(r, g, b)
+}
+
+// Scale the brightness of an RGB color by `factor`
+pub fn scale_rgb((r, g, b): Rgb, factor: f64) -> Rgb {
+    let channel = |c: u8| (c as f64 * factor).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+// Perceived brightness of an RGB color, from 0 (black) to 1 (white)
+pub fn luma((r, g, b): Rgb) -> f64 {
+    (0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64) / 255.0
+}
+
+// Mix one RGB color towards another, from 0.0 (all `from`) to 1.0 (all `to`)
+pub fn mix_rgb(from: Rgb, to: Rgb, amount: f64) -> Rgb {
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * amount).round() as u8;
+    (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+fn distance_sq(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+    dr * dr + dg * dg + db * db
+}
@@ -0,0 +1,82 @@
+// Secret push-walls. A push-wall looks like any other wall, but using it
+// slides it away from the player, a cell at a time, to reveal what lies
+// behind. Once it stops it is an ordinary wall.
+
+pub const PUSH_WALL_CELL: u8 = b'*';
+
+const SLIDE_FRAMES: u32 = 60; // Frames to slide one cell
+const PUSH_DISTANCE: u32 = 2; // Cells a push-wall slides before it stops
+
+#[derive(Clone, Copy, Debug)]
+pub struct PushWall {
+    pub cell: (i32, i32), // Cell the wall is sliding out of
+    pub direction: (i32, i32), // Unit step towards the cell it is sliding into
+    pub offset: f64, // How far into that cell it has slid, 0.0-1.0
+    cells_left: u32,
+}
+
+impl PushWall {
+    pub fn new(cell: (i32, i32), direction: (i32, i32)) -> Self {
+        PushWall {
+            cell,
+            direction,
+            offset: 0.0,
+            cells_left: PUSH_DISTANCE,
+        }
+    }
+
+    // Cell the wall is sliding into
+    pub fn next_cell(&self) -> (i32, i32) {
+        (self.cell.0 + self.direction.0, self.cell.1 + self.direction.1)
+    }
+
+    // Whether part of the wall is in a cell
+    pub fn covers(&self, cell: (i32, i32)) -> bool {
+        cell == self.cell || cell == self.next_cell()
+    }
+
+    // Whether the wall still has cells to slide once it reaches the next one
+    pub fn has_cells_left(&self) -> bool {
+        self.cells_left > 0
+    }
+
+    // Advance one frame. Returns true when the wall has slid fully into the
+    // next cell, which becomes the cell it is sliding out of.
+    pub fn advance(&mut self) -> bool {
+        self.offset += 1.0 / SLIDE_FRAMES as f64;
+        if self.offset < 1.0 {
+            return false;
+        }
+        self.cell = self.next_cell();
+        self.offset = 0.0;
+        self.cells_left -= 1;
+        true
+    }
+
+    // Distance along a ray from (x, y) with direction (cos, sin) to the
+    // sliding block, how far along the struck face it lands and the face's
+    // normal, using the slab method on the block's bounding square
+    pub fn intersect(&self, x: f64, y: f64, cos: f64, sin: f64) -> Option<(f64, f64, (f64, f64))> {
+        let left = self.cell.0 as f64 + self.direction.0 as f64 * self.offset;
+        let top = self.cell.1 as f64 + self.direction.1 as f64 * self.offset;
+        // Distances at which the ray enters and leaves the block along one axis
+        let slab = |origin: f64, direction: f64, min: f64| {
+            if direction == 0.0 {
+                return (min..=min + 1.0).contains(&origin).then_some((f64::NEG_INFINITY, f64::INFINITY));
+            }
+            let (a, b) = ((min - origin) / direction, (min + 1.0 - origin) / direction);
+            Some((a.min(b), a.max(b)))
+        };
+        let (near_x, far_x) = slab(x, cos, left)?;
+        let (near_y, far_y) = slab(y, sin, top)?;
+        let near = near_x.max(near_y);
+        if near < 0.0 || near > far_x.min(far_y) {
+            return None;
+        }
+        if near_x > near_y {
+            Some((near, y + near * sin - top, (-cos.signum(), 0.0)))
+        } else {
+            Some((near, x + near * cos - left, (0.0, -sin.signum())))
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// Adaptive render quality. Watches how long each frame takes to produce and
+// steps quality down when frames run over budget, then back up once there is
+// plenty of headroom. The gap between the two thresholds and a cooldown after
+// every change keep the level from oscillating.
+
+use std::time::Duration;
+
+const WINDOW: usize = 30; // Frames averaged for each decision
+const SLOW_FACTOR: f64 = 1.1; // Average above budget * this lowers quality
+const FAST_FACTOR: f64 = 0.6; // Average below budget * this raises quality
+const COOLDOWN_FRAMES: u32 = 60; // Frames ignored after a change while it settles
+
+// Settings for one quality level. The feature flags only allow a feature;
+// it is still drawn only if it was enabled in the first place.
+#[derive(Clone, Copy)]
+pub struct QualityLevel {
+    pub resolution_scale: f64,
+    pub ramps: bool,
+    pub textures: bool,
+}
+
+// Quality levels from best to cheapest
+pub const LEVELS: [QualityLevel; 4] = [
+    QualityLevel { resolution_scale: 1.0, ramps: true, textures: true },
+    QualityLevel { resolution_scale: 1.0, ramps: false, textures: true },
+    QualityLevel { resolution_scale: 0.75, ramps: false, textures: true },
+    QualityLevel { resolution_scale: 0.5, ramps: false, textures: false },
+];
+
+pub struct AdaptiveQuality {
+    budget: Duration, // Target time per frame
+    level: usize, // Index into LEVELS
+    samples: Vec<Duration>,
+    cooldown: u32,
+}
+
+impl AdaptiveQuality {
+    pub fn new(budget: Duration) -> Self {
+        AdaptiveQuality {
+            budget,
+            level: 0,
+            samples: Vec::with_capacity(WINDOW),
+            cooldown: 0,
+        }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn settings(&self) -> QualityLevel {
+        LEVELS[self.level]
+    }
+
+    // Record how long the last frame took. Returns true if the level changed.
+    pub fn record(&mut self, frame_time: Duration) -> bool {
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return false;
+        }
+
+        self.samples.push(frame_time);
+        if self.samples.len() < WINDOW {
+            return false;
+        }
+        let average = (self.samples.iter().sum::<Duration>() / WINDOW as u32).as_secs_f64();
+        self.samples.clear();
+
+        let budget = self.budget.as_secs_f64();
+        let level = if average > budget * SLOW_FACTOR {
+            (self.level + 1).min(LEVELS.len() - 1)
+        } else if average < budget * FAST_FACTOR {
+            self.level.saturating_sub(1)
+        } else {
+            self.level
+        };
+        if level == self.level {
+            return false;
+        }
+
+        self.level = level;
+        self.cooldown = COOLDOWN_FRAMES;
+        true
+    }
+}
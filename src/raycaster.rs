@@ -0,0 +1,4010 @@
+use crossterm::{
+    execute,
+    terminal::{size, Clear, ClearType},
+};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::analog::{Movement, ResponseCurve};
+use crate::automap::Automap;
+use crate::daycycle::DayCycle;
+use crate::destructible::{DestructibleWall, DESTRUCTIBLE_CELL};
+use crate::door::{Door, DOOR_CELL};
+use crate::editor::{self, Editor};
+use crate::effect::{EffectQueue, ScreenEffect};
+use crate::enemy::{Enemy, CHASE_RANGE, CONTACT_RANGE};
+use crate::environment::Environment;
+use crate::error::RaycastError;
+use crate::event::GameEvent;
+use crate::generate::Rng;
+use crate::keybindings::Action;
+use crate::light::PointLight;
+use crate::map::{Map, BUILTIN_MAPS, EXIT_CELL};
+use crate::mirror::{self, RayPath, MIRROR_CELL};
+use crate::hazard::{Hazard, TICK_FRAMES as HAZARD_TICK_FRAMES};
+use crate::mapfile::{has_height, is_open as is_open_cell, MapFile, SectorColors, MAX_LIGHT_LEVEL};
+use crate::palette::{self, Rgb};
+use crate::pushwall::{PushWall, PUSH_WALL_CELL};
+use crate::quality::LEVELS as QUALITY_LEVELS;
+use crate::render::{self, Cell, ColorDepth, RenderMode};
+use crate::sprite::SpriteKind;
+use crate::texture::{ImageTexture, Translucency, WallTexture, SHADES};
+use crate::viewmodel::{self, WalkCycle};
+use crate::window::{self, WINDOW_CELL};
+use crate::world::World;
+
+pub const FOV: f64 = 0.66; // Default field of view, as the camera plane's half-width at distance 1
+const MOVE_SPEED: f64 = 0.05;
+const FRAME_TIME: f64 = 1.0 / 60.0; // Seconds of play per update, matching the ~60 FPS game loop
+const ROTATION_SPEED: f64 = 0.03;
+const HIT_FLASH_DURATION: Duration = Duration::from_millis(150);
+const HIT_FLASH_COLOR: u8 = 231; // Bright white
+const TALL_CEILING_HEIGHT: f64 = 2.0; // Wall height multiplier for raised-ceiling cells
+const GRID_STEP_FRAMES: u32 = 12; // Frames per animated step/turn in grid movement mode
+pub const DEFAULT_LETTERBOX_COLOR: u8 = 16; // Black
+pub const DEFAULT_BACKGROUND_COLOR: u8 = 16; // Black
+const PLATFORM_HEIGHT: f64 = 0.25; // Raised floor height as a fraction of a wall
+pub const DEFAULT_HEALTH: f64 = 100.0;
+pub const DEFAULT_CONTACT_DAMAGE: f64 = 10.0; // Health lost per enemy contact
+const DAMAGE_COOLDOWN: Duration = Duration::from_millis(500); // Minimum time between contact damage
+const DAMAGE_FLASH_DURATION: Duration = Duration::from_millis(200);
+const DAMAGE_FLASH: ScreenEffect = ScreenEffect::Flash { color: (255, 0, 0), strength: 0.5 };
+const DAMAGE_SHAKE: f64 = 0.03; // Screen shake on taking damage, as a fraction of the view's height
+const DAMAGE_SHAKE_DURATION: Duration = Duration::from_millis(250);
+const TEXTURE_NOTICE_TIME: Duration = Duration::from_secs(5); // How long a texture that failed to load is reported
+const TELEPORTER_NOTICE_TIME: Duration = Duration::from_secs(5); // How long teleporters without a pair are reported
+const NUDGE_NOTICE_TIME: Duration = Duration::from_secs(3); // How long moving the player out of a wall is reported
+const TELEPORT_FLASH_DURATION: Duration = Duration::from_millis(300); // Fading flash after a teleport
+const HEALTH_BAR_COLOR: u8 = 196; // Red
+const HEALTH_BAR_EMPTY_COLOR: u8 = 52; // Dark red
+const DIAGONAL_MATERIAL: u8 = 1; // Diagonal walls are drawn as brick
+const THIN_WALL_MATERIAL: u8 = 3; // Thin walls are drawn as stripes, like railings
+const DOOR_MATERIAL: u8 = 3; // Door panels are drawn as stripes
+const DESTRUCTIBLE_MATERIAL: u8 = 2; // Destructible walls are drawn as checker
+const CRACKED_COLOR: u8 = 137; // Dusty tan of a cracked destructible wall
+const SKY_GRADIENT: (u8, u8) = (39, 45); // Ceiling colors from the horizon up
+const FLOOR_GRADIENT: (u8, u8) = (238, 244); // Dark to medium gray floor colors
+const PUSH_WALL_MATERIAL: u8 = 1; // Push-walls pass for ordinary brick
+const WINDOW_MATERIAL: u8 = 8; // Window bars are drawn in slate
+const MIRROR_MATERIAL: u8 = 8; // Mirrors past the last bounce are drawn in slate too
+const MIRROR_GAP: f64 = 0.001; // Rays bounce off a mirror from just in front of it, so they don't strike it again
+const USE_RANGE: f64 = 1.5; // How close a door or push-wall must be to use it
+const QUALITY_PIP_COLOR: u8 = 46; // Green
+const QUALITY_PIP_EMPTY_COLOR: u8 = 238; // Dark gray
+const TELEPORT_COOLDOWN_FRAMES: u32 = 30; // Frames after a teleport before another can trigger
+const EXPLORED_SPACING: f64 = 0.25; // Distance the player moves between points of the explored path
+const EXPLORED_JUMP: f64 = 1.0; // Moves longer than this in one frame, such as teleports, start a new run
+const TELEPORTER_COLOR: u8 = 201; // Magenta
+const EXIT_COLOR: u8 = 46; // Green
+const GRID_LINE_WIDTH: f64 = 0.03; // Half-width of floor grid lines at distance 1, in cells
+const GRID_LINE_BRIGHTNESS: f64 = 1.3; // Floor grid lines brighten the floor by this factor
+pub const DEFAULT_LIGHT_ANGLE: f64 = 45.0; // Direction the light travels in, degrees (0 = +x, 90 = +y)
+const AMBIENT_LIGHT: f64 = 0.6; // Brightness of walls facing away from the light
+const NORTH_SOUTH_BRIGHTNESS: f64 = 0.8; // North- and south-facing walls are darker than east and west ones
+const MAX_TRANSLUCENT_LAYERS: usize = 4; // Further see-through cells are drawn opaque
+const CORNER_TOLERANCE: f64 = 1e-9; // Grid crossings closer than this count as passing through a corner
+const MAX_SHORT_WALLS: usize = 8; // Further short walls are drawn full height
+const WALL_TOP_BRIGHTNESS: f64 = 0.7; // The tops of short walls are darker than their faces
+const SECTOR_HORIZON_BRIGHTNESS: f64 = 0.6; // Sector floors and ceilings darken to this at the horizon
+const DARKEST_LIGHT: f64 = 0.1; // Brightness of cells at light level 0
+const MAX_POINT_LIT_BRIGHTNESS: f64 = 1.5; // Point lights can't brighten anything past this
+const FLASHLIGHT_HALF_ANGLE: f64 = 0.35; // Radians either side of the facing the flashlight's cone covers
+const FLASHLIGHT_EDGE: f64 = 0.15; // Radians over which the cone's edge fades out
+const FLASHLIGHT_RANGE: f64 = 12.0; // Cells the beam reaches before fading out
+const FLASHLIGHT_NEAR: f64 = 0.3; // Points closer than this count as inside the cone
+const FLASHLIGHT_BRIGHTNESS: f64 = 1.4; // Brightness factor in the middle of the beam, close up
+const FLASHLIGHT_DARKNESS: f64 = 0.2; // Brightness factor outside the beam
+const VIGNETTE_START: f64 = 2.0; // Average center wall distance where the vignette begins
+const VIGNETTE_FULL: f64 = 0.6; // Average center wall distance for the strongest vignette
+const VIGNETTE_DARKEN: f64 = 0.7; // Darkening at the very corners at full strength
+const VIGNETTE_EASE: f64 = 0.4; // Seconds for the vignette to cover ~63% of a change
+// Cells a ray may step through before giving up. A ray starting inside the
+// map crosses at most this many cells before leaving it.
+pub(crate) const MARKER_COLOR: u8 = 93; // Purple, measurement markers
+const MARKER_WALL_GAP: f64 = 0.1; // Markers stand this far in front of the measured wall
+const CROSSHAIR_COLOR: u8 = 231; // Bright white
+const DIM_FACTOR: f64 = 0.4; // Brightness of the view behind an overlay
+const ANALOG_GRID_THRESHOLD: f64 = 0.5; // Deflection that counts as a key press in grid mode
+const SPECTATED_PLAYER_COLOR: u8 = 226; // Yellow, the player seen from a free camera
+const FPS_EASE: f64 = 0.5; // Seconds for the frame rate shown to cover ~63% of a change
+pub const DEFAULT_HEAD_BOB: f64 = 0.015; // Head-bob height as a fraction of the view's height
+const MINIMAP_SIZE: f64 = 0.3; // Side of the minimap as a fraction of the view's shorter side
+const MINIMAP_RADIUS: f64 = 5.0; // Cells shown each way from the player on the minimap
+const MINIMAP_FACING_LENGTH: f64 = 1.5; // Cells the minimap's facing line reaches
+const MINIMAP_BORDER_COLOR: u8 = 250; // Light gray
+const MINIMAP_OUTSIDE_COLOR: u8 = 16; // Black, beyond the edges of the map
+const MINIMAP_PLAYER_COLOR: u8 = 226; // Yellow
+const MINIMAP_FACING_COLOR: u8 = 231; // Bright white
+
+// Ordered dithering thresholds, as sixteenths, tiled across the screen
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// Wall colors from nearest to farthest: yellow through orange and red to
+// dark red, each step slightly darker than the last so there is no seam
+const WALL_RAMP: [u8; 10] = [226, 220, 214, 208, 202, 196, 160, 124, 88, 52];
+// The same ramp in system colors for 16-color terminals, where the shades
+// above would mostly fall into one band: bright yellow, yellow, bright red,
+// red, then dark gray
+const BASIC_WALL_RAMP: [u8; 10] = [11, 11, 3, 3, 9, 9, 1, 1, 8, 8];
+const FAR_WALL_BRIGHTNESS: f64 = 0.3; // Brightness of colored walls at the far end of the ramp
+const ASCII_FLOOR_DASHES: f64 = 0.5; // ASCII floors are dashes up to this far along the wall ramp, then dots
+
+// Base color of each wall material 1-9, so rooms and landmarks built from
+// different materials stand apart. Material 1 keeps the warm ramp above.
+const MATERIAL_COLORS: [Option<u8>; 9] = [
+    None,
+    Some(250), // Gray
+    Some(67), // Steel blue
+    Some(51), // Cyan, matching the glass tint
+    Some(27), // Blue, matching the water curtain
+    Some(71), // Moss green
+    Some(133), // Purple
+    Some(109), // Slate
+    Some(168), // Rose
+];
+
+#[derive(Clone)]
+pub struct Player {
+    pub x: f64,
+    pub y: f64,
+    pub angle: f64,
+    pub health: f64,
+}
+
+impl Player {
+    // Position of a world point relative to the player's view, as
+    // (distance ahead, distance to the right)
+    pub fn view_offset(&self, x: f64, y: f64) -> (f64, f64) {
+        let dx = x - self.x;
+        let dy = y - self.y;
+        let (sin, cos) = self.angle.sin_cos();
+        (dx * cos + dy * sin, dy * cos - dx * sin)
+    }
+}
+
+// Billboarded object standing on the floor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    pub x: f64,
+    pub y: f64,
+    pub color: u8,
+    pub kind: SpriteKind,
+}
+
+// A pixel of the walls, floor, ceiling or a sprite, recorded for output
+// modes that need more than its palette color: its exact color for
+// truecolor frames, and what it shows and how far away for ASCII frames.
+// `code` is the palette color written to the frame buffer for it, so pixels
+// drawn over since can be told apart.
+#[derive(Clone, Copy)]
+struct DrawnPixel {
+    code: u8,
+    color: Rgb,
+    surface: Surface,
+    distance: f64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Surface {
+    Ceiling,
+    Wall,
+    Floor,
+    Sprite,
+}
+
+type DrawnPixels = Vec<Vec<Option<DrawnPixel>>>;
+
+// A pixel of a composed frame: a 256-color code, or an RGB color for a
+// truecolor terminal
+trait Pixel: Copy {
+    fn from_code(code: u8) -> Self;
+    // The scene rendered at a size in pixels
+    fn render(raycaster: &mut Raycaster, width: usize, height: usize) -> Vec<Vec<Self>>;
+    fn scale(self, factor: f64) -> Self;
+}
+
+impl Pixel for u8 {
+    fn from_code(code: u8) -> Self {
+        code
+    }
+
+    fn render(raycaster: &mut Raycaster, width: usize, height: usize) -> Vec<Vec<Self>> {
+        raycaster.render_frame(width, height)
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        palette::scale(self, factor)
+    }
+}
+
+impl Pixel for Rgb {
+    fn from_code(code: u8) -> Self {
+        palette::to_rgb(code)
+    }
+
+    fn render(raycaster: &mut Raycaster, width: usize, height: usize) -> Vec<Vec<Self>> {
+        raycaster.render_frame_rgb(width, height)
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        palette::scale_rgb(self, factor)
+    }
+}
+
+impl Pixel for char {
+    fn from_code(code: u8) -> Self {
+        render::ascii_shade(palette::luma(palette::to_rgb(code)))
+    }
+
+    fn render(raycaster: &mut Raycaster, width: usize, height: usize) -> Vec<Vec<Self>> {
+        raycaster.render_frame_ascii(width, height)
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        render::ascii_shade(render::ascii_brightness(self) * factor)
+    }
+}
+
+// State saved while photo mode is active, restored when it ends
+struct PhotoMode {
+    player: Player,
+    floor_grid: bool,
+}
+
+// Second view drawn in the right half of the screen, to compare two cameras
+// or two render settings side by side
+pub struct SplitView {
+    pub camera: Option<Player>, // Fixed camera, or None to share the player's view
+    pub fov: f64,
+}
+
+// Result of casting a single ray into the map
+pub struct RayHit {
+    pub distance: f64,
+    pub map_x: i32,
+    pub map_y: i32,
+    pub hit: bool, // false if the ray left the map without hitting a wall
+    pub side: bool, // true if the wall face runs along the x axis (the ray crossed a y grid line); false for diagonals
+    pub wall_x: f64, // Fractional position along the wall face where the ray struck, 0.0-1.0
+    pub material: u8, // Map value of the wall cell, 0 if nothing was hit
+    pub normal: (f64, f64), // Unit normal of the wall face, pointing back towards the ray origin
+    pub translucent: Vec<TranslucentHit>, // See-through cells passed on the way, nearest first
+    pub short_walls: Vec<ShortWallHit>, // Walls the ray passed over on the way, nearest first
+}
+
+// See-through cell a ray passed through before reaching the wall
+pub struct TranslucentHit {
+    pub distance: f64,
+    pub map_x: i32,
+    pub map_y: i32,
+    pub material: u8,
+    pub wall_x: f64, // Fractional position along the face where the ray entered, 0.0-1.0
+    pub normal: (f64, f64), // Unit normal of the face, pointing back towards the ray origin
+}
+
+// Wall shorter than full height that a ray passed over before reaching the
+// wall, with enough to draw its face and top
+pub struct ShortWallHit {
+    pub distance: f64, // Where the ray entered the cell
+    pub exit: f64, // Where the ray left the cell
+    pub map_x: i32,
+    pub map_y: i32,
+    pub material: u8,
+    pub height: f64, // Fraction of full height
+    pub wall_x: f64, // Fractional position along the face where the ray struck, 0.0-1.0
+    pub normal: (f64, f64), // Unit normal of the face, pointing back towards the ray origin
+}
+
+// Wall segment cutting through a map cell: a 45° wall running corner to
+// corner, or a thin wall such as a fence across the middle. Both parts of the
+// cell are open; only the segment itself is solid.
+#[derive(Clone, Copy)]
+enum Segment {
+    Slash, // '/': bottom-left corner to top-right corner
+    Backslash, // '\': top-left corner to bottom-right corner
+    AlongX, // '-': thin wall from the west edge to the east edge
+    AlongY, // '|': thin wall from the north edge to the south edge
+}
+
+impl Segment {
+    fn from_cell(cell: u8) -> Option<Self> {
+        match cell {
+            b'/' => Some(Segment::Slash),
+            b'\\' => Some(Segment::Backslash),
+            b'-' => Some(Segment::AlongX),
+            b'|' => Some(Segment::AlongY),
+            _ => None,
+        }
+    }
+
+    fn is_diagonal(self) -> bool {
+        matches!(self, Segment::Slash | Segment::Backslash)
+    }
+
+    // Signed side of a point, in cell-local coordinates, relative to the segment
+    fn side(self, u: f64, v: f64) -> f64 {
+        match self {
+            Segment::Slash => u + v - 1.0,
+            Segment::Backslash => u - v,
+            Segment::AlongX => v - 0.5,
+            Segment::AlongY => u - 0.5,
+        }
+    }
+
+    // Distance along a unit ray from (u, v) to the segment's line, if not parallel
+    fn intersect(self, u: f64, v: f64, cos: f64, sin: f64) -> Option<f64> {
+        let rate = match self {
+            Segment::Slash => cos + sin,
+            Segment::Backslash => cos - sin,
+            Segment::AlongX => sin,
+            Segment::AlongY => cos,
+        };
+        if rate.abs() < 1e-9 {
+            return None;
+        }
+        Some(-self.side(u, v) / rate)
+    }
+
+    // Unit normal of the segment on the side of (u, v)
+    fn normal_towards(self, u: f64, v: f64) -> (f64, f64) {
+        let sign = self.side(u, v).signum();
+        let diagonal = sign * std::f64::consts::FRAC_1_SQRT_2;
+        match self {
+            Segment::Slash => (diagonal, diagonal),
+            Segment::Backslash => (diagonal, -diagonal),
+            Segment::AlongX => (0.0, sign),
+            Segment::AlongY => (sign, 0.0),
+        }
+    }
+}
+
+// In-progress animated step or turn in grid movement mode
+struct GridMove {
+    from_x: f64,
+    from_y: f64,
+    from_angle: f64,
+    to_x: f64,
+    to_y: f64,
+    to_angle: f64,
+    frame: u32,
+}
+
+// Screen shake set off by `shake`, fading out over its duration
+struct Shake {
+    intensity: f64,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Shake {
+    // Largest offset at a moment as a fraction of the view's height, easing
+    // out to nothing at the end
+    fn strength(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let remaining = (1.0 - elapsed / self.duration.as_secs_f64().max(f64::EPSILON)).max(0.0);
+        self.intensity * remaining * remaining
+    }
+}
+
+// Wall cell briefly highlighted after being interacted with
+struct HitFlash {
+    map_x: i32,
+    map_y: i32,
+    until: Instant,
+}
+
+
+pub struct Raycaster {
+    pub player: Player,
+    // Wall grid of the current map and the index of the built-in map it was
+    // loaded from. Editable at runtime.
+    map: Map,
+    // The wall grid as authored: as loaded, with only the editor's changes.
+    // Push-walls moved and walls destroyed during play change `map` but not
+    // this, so maps are saved as they were built.
+    authored_map: Map,
+    map_index: usize,
+    map_name: String,
+    // Hash of the map as loaded or edited, identifying it for best times.
+    // Push-walls sliding during play don't change it.
+    map_hash: u64,
+    // Where the Home key puts the player back, as (x, y, angle in radians)
+    pub spawn: (f64, f64, f64),
+    // Spawn points the map declares, and which one `spawn` was chosen from
+    spawn_points: Vec<(f64, f64, f64)>,
+    spawn_index: usize,
+    // The player hasn't moved since last put at the spawn point, so holding
+    // the reset key raises one event
+    at_spawn: bool,
+    last_width: usize,
+    last_height: usize,
+    // Ray angle offset from the player's facing for each screen column
+    column_angles: Vec<f64>,
+    hit_flash: Option<HitFlash>,
+    // Optional per-cell ceiling heights; uniform ceilings when None. Only
+    // built-in maps have them.
+    ceiling_map: Option<&'static [&'static str]>,
+    // Draw the built-in ceiling layer's raised ceilings, which are off
+    // unless asked for
+    pub raised_ceilings: bool,
+    // Grid movement mode and its current animation, if any
+    grid_mode: bool,
+    grid_move: Option<GridMove>,
+    // Inline mode redraws relative to the previous frame instead of the screen origin
+    pub inline: bool,
+    inline_rows: usize, // Rows drawn by the previous inline frame
+    pub sprites: Vec<Sprite>,
+    // Fixed internal resolution (pixels) scaled into the terminal, if enabled
+    pub fixed_resolution: Option<(usize, usize)>,
+    pub letterbox_color: u8,
+    // Screen columns covered by the 3D view in the last frame: (first, count)
+    view_columns: (usize, usize),
+    // Optional per-cell floor heights, drawn when ramps are enabled. Only
+    // built-in maps have them.
+    floor_map: Option<&'static [&'static str]>,
+    pub ramps: bool,
+    pub textures: bool, // Procedural wall textures per material
+    // Image textures indexed by material; these take precedence over the
+    // procedural patterns when textures are enabled
+    pub image_textures: Vec<Option<ImageTexture>>,
+    // Image textures the map names, indexed by material, and the files they
+    // came from. Those in `image_textures` are drawn instead.
+    map_textures: Vec<Option<ImageTexture>>,
+    texture_files: Vec<(u8, PathBuf)>,
+    // Images drawn across the floor and ceiling, one copy per cell, when
+    // textures are enabled; these take precedence over the map's
+    pub floor_texture: Option<ImageTexture>,
+    pub ceiling_texture: Option<ImageTexture>,
+    // The map's floor and ceiling images and the files they came from
+    map_floor_texture: Option<ImageTexture>,
+    map_ceiling_texture: Option<ImageTexture>,
+    floor_texture_file: Option<PathBuf>,
+    ceiling_texture_file: Option<PathBuf>,
+    pub render_mode: RenderMode,
+    // Colors sent to the terminal. Truecolor frames shade walls, floors and
+    // ceilings smoothly instead of in steps of the palette.
+    pub color_depth: ColorDepth,
+    // Pixels being recorded for the frame in progress, if it is a truecolor
+    // or ASCII frame
+    drawn_pixels: Option<DrawnPixels>,
+    pub enemies: Vec<Enemy>,
+    events: Vec<GameEvent>,
+    pub max_health: f64, // Full length of the HUD health bar
+    // When the player last took damage, for rate limiting
+    last_damage: Option<Instant>,
+    // Flashes and fades laid over the whole view
+    effects: EffectQueue,
+    // Screen shake in progress, and where its random offsets come from
+    shake: Option<Shake>,
+    shake_rng: Rng,
+    // Fraction of the terminal's resolution to render at, stretched to fill it
+    pub resolution_scale: f64,
+    // Adaptive quality level shown in the HUD, if adaptive quality is on
+    pub quality_level: Option<usize>,
+    // Linked teleporter cells; each cell maps to its partner
+    teleporters: HashMap<(i32, i32), (i32, i32)>,
+    teleport_cooldown: u32,
+    // Facing given to the player on arriving at a teleporter cell, where the
+    // map sets one
+    teleport_facing: HashMap<(i32, i32), f64>,
+    // Sliding door cells and how far open each one is
+    doors: HashMap<(i32, i32), Door>,
+    // Destructible wall cells and how many hits each has left
+    destructibles: HashMap<(i32, i32), DestructibleWall>,
+    // Walls shorter than full height, as a fraction of it
+    wall_heights: HashMap<(i32, i32), f64>,
+    // Sector letter of each cell in one, and each sector's floor and ceiling
+    // colors
+    sectors: HashMap<(i32, i32), u8>,
+    sector_colors: HashMap<u8, SectorColors>,
+    // Light level of each cell lit less than fully
+    light_levels: HashMap<(i32, i32), u8>,
+    pub point_lights: Vec<PointLight>, // Lamps placed in the map
+    // Light that follows the player around, ignoring its own position
+    pub carried_light: Option<PointLight>,
+    // Light a cone in front of the player and leave the rest dark
+    pub flashlight: bool,
+    // Top-down map of the cells around the player over a corner of the view
+    pub minimap: bool,
+    // Weapon held at the bottom of the view, swaying as the player walks
+    pub weapon: bool,
+    walk: WalkCycle,
+    // Height of the head-bob while walking as a fraction of the view's
+    // height; 0 keeps the camera level
+    pub head_bob: f64,
+    // Pixels the horizon is lowered by in the frame being rendered
+    horizon_shift: i32,
+    // Lava and acid floor cells, and how many frames the player has spent
+    // on hazards since last stepping onto one
+    hazards: HashMap<(i32, i32), Hazard>,
+    hazard_frames: u32,
+    // Push-wall currently sliding, if any. Its cells are open floor in the
+    // map while it moves and it becomes a plain wall where it stops.
+    push_wall: Option<PushWall>,
+    // Exit cells, marked in the view so they can be found
+    exits: Vec<(i32, i32)>,
+    // Cell the player occupied at the end of the last update
+    current_cell: (i32, i32),
+    pub fov: f64,
+    column_fov: f64, // FOV the column angle table was built for
+    pub split: Option<SplitView>,
+    // Color of the void seen where a ray leaves the map without hitting a wall
+    pub background_color: u8,
+    pub floor_grid: bool, // Overlay cell boundaries on the floor
+    // Paused game with a free camera and no overlays, for clean screenshots
+    photo_mode: Option<PhotoMode>,
+    // Where the player was left while a detached free camera flies around;
+    // `player` is the camera until control returns
+    spectating: Option<Player>,
+    // Unit direction the light travels in; walls are shaded by how squarely
+    // they face it. None disables shading.
+    pub light_direction: Option<(f64, f64)>,
+    // Output transforms: mirror left-right and flip upside down
+    pub mirror: bool,
+    pub flip: bool,
+    // Edge darkening when walls close in, eased over time
+    pub vignette: bool,
+    vignette_strength: f64,
+    vignette_updated: Option<Instant>,
+    // Safety limit on DDA steps per ray; rays that reach it count as misses.
+    // None allows the map's width plus height, enough to cross any map.
+    pub max_dda_steps: Option<usize>,
+    // Time of day driving the sky colors and light level. None keeps the
+    // fixed blue sky and full daylight.
+    pub day_cycle: Option<DayCycle>,
+    // Fog, light level and sky and floor colors of the current map, replaced
+    // whenever a map is loaded
+    pub environment: Environment,
+    // Fog range and color for maps that don't set their own
+    pub default_fog: Environment,
+    // Measurement mode: a crosshair reports the distance to the wall ahead,
+    // and up to two markers dropped on walls report the distance between them
+    pub measuring: bool,
+    markers: Vec<(f64, f64)>,
+    // Where the player has been on this map, as runs of points a short way
+    // apart; teleports and resets start a new run
+    explored: Vec<Vec<(f64, f64)>>,
+    // Ordered dithering between neighbouring gradient colors to hide banding
+    pub dither: bool,
+    // Darken the whole view, behind an overlay such as the help panel
+    pub dimmed: bool,
+    // Status bar on the bottom row of the screen, which the view is kept out of
+    pub hud: bool,
+    // Frame rate and render time in the top-right corner
+    pub fps_counter: bool,
+    // Frames presented per second and seconds spent rendering and writing
+    // out each one, eased over time, and when the last frame was presented
+    fps: f64,
+    render_time: f64,
+    last_presented: Option<Instant>,
+    // Shapes analog movement and turn input before it is scaled to speed
+    pub response_curve: ResponseCurve,
+    // Base colors for wall materials, replacing the distance ramp; walls of
+    // these materials darken with distance instead
+    pub wall_colors: HashMap<u8, u8>,
+    // Frames played since the map was loaded or the player reset, driving
+    // animations, and when that run started, timing it
+    run_frames: u64,
+    run_started: Instant,
+    // Best completion time for the current map, shown with the live delta
+    pub best_time: Option<f64>,
+    // Short message shown in the label until the given time
+    notice: Option<(String, Instant)>,
+    // Endless world the map is a window onto, if one is loaded
+    world: Option<World>,
+}
+
+impl Default for Raycaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Raycaster {
+    pub fn new() -> Self {
+        let mut raycaster = Raycaster {
+            player: Player {
+                x: 0.0,
+                y: 0.0,
+                angle: 0.0,
+                health: DEFAULT_HEALTH,
+            },
+            map: Map::filled(1, 1, b'1'),
+            authored_map: Map::filled(1, 1, b'1'),
+            map_index: 0,
+            map_name: String::new(),
+            map_hash: 0,
+            spawn: (0.0, 0.0, 0.0),
+            spawn_points: Vec::new(),
+            spawn_index: 0,
+            at_spawn: false,
+            last_width: 0,
+            last_height: 0,
+            column_angles: Vec::new(),
+            hit_flash: None,
+            ceiling_map: None,
+            raised_ceilings: false,
+            grid_mode: false,
+            grid_move: None,
+            inline: false,
+            inline_rows: 0,
+            sprites: Vec::new(),
+            fixed_resolution: None,
+            letterbox_color: DEFAULT_LETTERBOX_COLOR,
+            view_columns: (0, 0),
+            floor_map: None,
+            ramps: false,
+            textures: true,
+            image_textures: Vec::new(),
+            map_textures: Vec::new(),
+            texture_files: Vec::new(),
+            floor_texture: None,
+            ceiling_texture: None,
+            map_floor_texture: None,
+            map_ceiling_texture: None,
+            floor_texture_file: None,
+            ceiling_texture_file: None,
+            render_mode: RenderMode::HalfBlock,
+            color_depth: ColorDepth::Indexed,
+            drawn_pixels: None,
+            enemies: Vec::new(),
+            events: Vec::new(),
+            max_health: DEFAULT_HEALTH,
+            last_damage: None,
+            effects: EffectQueue::default(),
+            shake: None,
+            shake_rng: Rng::new(0),
+            resolution_scale: 1.0,
+            quality_level: None,
+            teleporters: HashMap::new(),
+            teleport_facing: HashMap::new(),
+            doors: HashMap::new(),
+            destructibles: HashMap::new(),
+            wall_heights: HashMap::new(),
+            sectors: HashMap::new(),
+            sector_colors: HashMap::new(),
+            light_levels: HashMap::new(),
+            point_lights: Vec::new(),
+            carried_light: None,
+            flashlight: false,
+            minimap: false,
+            weapon: false,
+            walk: WalkCycle::default(),
+            head_bob: DEFAULT_HEAD_BOB,
+            horizon_shift: 0,
+            hazards: HashMap::new(),
+            hazard_frames: 0,
+            push_wall: None,
+            exits: Vec::new(),
+            teleport_cooldown: 0,
+            current_cell: (0, 0),
+            fov: FOV,
+            column_fov: FOV,
+            split: None,
+            background_color: DEFAULT_BACKGROUND_COLOR,
+            floor_grid: false,
+            photo_mode: None,
+            spectating: None,
+            light_direction: Some(light_direction(DEFAULT_LIGHT_ANGLE)),
+            mirror: false,
+            flip: false,
+            vignette: false,
+            vignette_strength: 0.0,
+            vignette_updated: None,
+            max_dda_steps: None,
+            day_cycle: None,
+            environment: Environment::default(),
+            default_fog: Environment::default(),
+            measuring: false,
+            markers: Vec::new(),
+            explored: Vec::new(),
+            dither: false,
+            dimmed: false,
+            hud: false,
+            fps_counter: false,
+            fps: 0.0,
+            render_time: 0.0,
+            last_presented: None,
+            response_curve: ResponseCurve::Linear,
+            wall_colors: HashMap::new(),
+            run_frames: 0,
+            run_started: Instant::now(),
+            best_time: None,
+            notice: None,
+            world: None,
+        };
+        raycaster.load_builtin(0);
+        raycaster
+    }
+
+    // Switch to a built-in map, replacing its sprites and enemies and
+    // moving the player to its spawn point. Health carries over.
+    pub fn load_builtin(&mut self, index: usize) {
+        let map = &BUILTIN_MAPS[index % BUILTIN_MAPS.len()];
+        self.set_photo_mode(false);
+        self.set_spectating(false);
+        self.world = None;
+        self.map = Map::from_rows(map.walls).expect("built-in maps are rectangular");
+        self.authored_map = self.map.clone();
+        self.map_index = index % BUILTIN_MAPS.len();
+        self.map_name = map.name.to_string();
+        self.wall_colors.clear();
+        self.map_textures.clear();
+        self.texture_files.clear();
+        self.map_floor_texture = None;
+        self.map_ceiling_texture = None;
+        self.floor_texture_file = None;
+        self.ceiling_texture_file = None;
+        self.ceiling_map = map.ceiling;
+        self.floor_map = map.floor;
+        self.sprites = map
+            .sprites
+            .iter()
+            .map(|&(x, y, color)| Sprite { x, y, color, kind: SpriteKind::Orb })
+            .collect();
+        self.enemies = map
+            .enemy_patrols
+            .iter()
+            .map(|patrol| Enemy::new(patrol.to_vec()))
+            .collect();
+        self.link_teleporters();
+        self.teleport_cooldown = 0;
+        self.teleport_facing.clear();
+        self.doors = parse_doors(&self.map);
+        self.destructibles = find_destructibles(&self.map);
+        self.wall_heights.clear();
+        self.sectors.clear();
+        self.sector_colors.clear();
+        self.light_levels.clear();
+        self.point_lights.clear();
+        self.hazards.clear();
+        self.hazard_frames = 0;
+        self.environment = Environment::default();
+        self.exits = find_exits(&self.map);
+        self.push_wall = None;
+        self.map_hash = hash_map(&self.map);
+        self.hit_flash = None;
+        self.markers.clear();
+        self.explored.clear();
+        self.events.clear();
+        self.run_frames = 0;
+        self.run_started = Instant::now();
+        
+        self.spawn = map.spawn;
+        self.spawn_points = vec![map.spawn];
+        self.spawn_index = 0;
+        self.move_to_spawn();
+    }
+
+    // Switch to a map loaded from a file, with the sprites it places but no
+    // enemies. The player starts at the map's first spawn point or, without
+    // one, facing east in the first open cell.
+    pub fn load_map(&mut self, map: MapFile) {
+        self.set_photo_mode(false);
+        self.set_spectating(false);
+        self.world = None;
+        self.map = map.walls;
+        self.authored_map = self.map.clone();
+        self.map_name = map.name;
+        self.wall_colors = map.wall_colors.into_iter().collect();
+        self.load_map_textures(map.textures, map.floor_texture, map.ceiling_texture);
+        // Map files have no ceiling or floor layer
+        self.ceiling_map = None;
+        self.floor_map = None;
+        self.sprites = map.sprites;
+        self.point_lights = map.lights;
+        self.enemies.clear();
+        self.link_teleporters();
+        self.teleport_cooldown = 0;
+        self.teleport_facing = map
+            .teleporter_facing
+            .iter()
+            .flat_map(|&(letter, facing)| {
+                let cells = self.map.cells().filter(move |&(_, cell)| cell == letter);
+                cells.zip(facing).map(|(((x, y), _), angle)| ((x as i32, y as i32), angle))
+            })
+            .collect();
+        self.doors = parse_doors(&self.map);
+        self.destructibles = find_destructibles(&self.map);
+        self.wall_heights = map
+            .wall_heights
+            .iter()
+            .map(|&((x, y), height)| ((x as i32, y as i32), height))
+            .collect();
+        self.sectors = map
+            .sectors
+            .iter()
+            .map(|&((x, y), letter)| ((x as i32, y as i32), letter))
+            .collect();
+        self.sector_colors = map.sector_colors.into_iter().collect();
+        self.environment = map.environment;
+        self.light_levels = map
+            .light_levels
+            .iter()
+            .map(|&((x, y), level)| ((x as i32, y as i32), level))
+            .collect();
+        self.hazards = map
+            .hazards
+            .iter()
+            .map(|&((x, y), hazard)| ((x as i32, y as i32), hazard))
+            .collect();
+        self.hazard_frames = 0;
+        self.exits = find_exits(&self.map);
+        self.push_wall = None;
+        self.map_hash = hash_map(&self.map);
+        self.hit_flash = None;
+        self.markers.clear();
+        self.explored.clear();
+        self.events.clear();
+        self.run_frames = 0;
+        self.run_started = Instant::now();
+        
+        self.spawn_points = map.spawns;
+        if self.spawn_points.is_empty() {
+            let (x, y) = self
+                .map
+                .cells()
+                .map(|(position, _)| position)
+                .find(|&(x, y)| self.is_open(x, y))
+                .unwrap_or((0, 0));
+            self.spawn_points.push((x as f64 + 0.5, y as f64 + 0.5, 0.0));
+        }
+        self.spawn_index = 0;
+        self.spawn = self.spawn_points[0];
+        self.move_to_spawn();
+    }
+
+    // Switch to an endless world streamed in chunks (see `World`), starting
+    // in the middle of its first chunk
+    pub fn load_world(&mut self, mut world: World) {
+        let walls = world.window();
+        let (x, y) = world.spawn();
+        self.load_map(MapFile {
+            name: world.name(),
+            spawns: vec![(x, y, 0.0)],
+            ..MapFile::from_walls(walls)
+        });
+        self.world = Some(world);
+    }
+
+    pub fn world(&self) -> Option<&World> {
+        self.world.as_ref()
+    }
+
+    // Keep an endless world's window centered on the chunk the player is in,
+    // moving everything held in window cells along with it. Doors and
+    // destructible walls still in the window keep their state.
+    fn stream_world(&mut self) {
+        let Some(world) = &mut self.world else {
+            return;
+        };
+        let (origin_x, origin_y) = world.origin();
+        let chunk = World::chunk_at(
+            origin_x + self.player.x.floor() as i64,
+            origin_y + self.player.y.floor() as i64,
+        );
+        if chunk == world.center() {
+            return;
+        }
+        world.store(&self.map);
+        let (dx, dy) = world.recenter(chunk);
+        self.map = world.window();
+        self.authored_map = self.map.clone();
+        
+        let (shift_x, shift_y) = (dx as f64, dy as f64);
+        let shift_cell = |(x, y): (i32, i32)| (x - dx as i32, y - dy as i32);
+        self.player.x -= shift_x;
+        self.player.y -= shift_y;
+        self.spawn.0 -= shift_x;
+        self.spawn.1 -= shift_y;
+        for spawn in &mut self.spawn_points {
+            spawn.0 -= shift_x;
+            spawn.1 -= shift_y;
+        }
+        for marker in &mut self.markers {
+            marker.0 -= shift_x;
+            marker.1 -= shift_y;
+        }
+        for point in self.explored.iter_mut().flatten() {
+            point.0 -= shift_x;
+            point.1 -= shift_y;
+        }
+        if let Some(grid_move) = &mut self.grid_move {
+            grid_move.from_x -= shift_x;
+            grid_move.from_y -= shift_y;
+            grid_move.to_x -= shift_x;
+            grid_move.to_y -= shift_y;
+        }
+        if let Some(push_wall) = &mut self.push_wall {
+            push_wall.cell = shift_cell(push_wall.cell);
+        }
+        self.current_cell = shift_cell(self.current_cell);
+        
+        let doors = std::mem::replace(&mut self.doors, parse_doors(&self.map));
+        for (cell, door) in doors {
+            if let Some(kept) = self.doors.get_mut(&shift_cell(cell)) {
+                *kept = door;
+            }
+        }
+        let destructibles = std::mem::replace(&mut self.destructibles, find_destructibles(&self.map));
+        for (cell, wall) in destructibles {
+            if let Some(kept) = self.destructibles.get_mut(&shift_cell(cell)) {
+                *kept = wall;
+            }
+        }
+        self.link_teleporters();
+        self.exits = find_exits(&self.map);
+        self.hit_flash = None;
+    }
+
+    // Put the player back at the spawn point, leaving health and the rest of
+    // the world as they are. Does nothing if the player hasn't moved since
+    // last put there, so holding the key down doesn't keep raising events.
+    pub fn reset_to_spawn(&mut self) {
+        self.set_photo_mode(false);
+        self.set_spectating(false);
+        if self.at_spawn {
+            return;
+        }
+        
+        self.move_to_spawn();
+        self.teleport_cooldown = 0;
+        self.run_frames = 0;
+        self.run_started = Instant::now();
+        self.events.push(GameEvent::Reset);
+    }
+
+    // Places the map lets the player start, the first being the default
+    pub fn spawn_points(&self) -> &[(f64, f64, f64)] {
+        &self.spawn_points
+    }
+
+    // The map's spawn points other than the chosen one
+    pub fn other_spawn_points(&self) -> impl Iterator<Item = (f64, f64, f64)> + '_ {
+        let chosen = self.spawn_index;
+        self.spawn_points
+            .iter()
+            .enumerate()
+            .filter(move |&(index, _)| index != chosen)
+            .map(|(_, &spawn)| spawn)
+    }
+
+    // Make another of the map's spawn points the one the player starts and
+    // resets at, and move the player there. Returns false if the map has no
+    // spawn point with that index.
+    pub fn choose_spawn(&mut self, index: usize) -> bool {
+        let Some(&spawn) = self.spawn_points.get(index) else {
+            return false;
+        };
+        self.spawn_index = index;
+        self.spawn = spawn;
+        self.move_to_spawn();
+        self.teleport_cooldown = 0;
+        self.run_frames = 0;
+        self.run_started = Instant::now();
+        true
+    }
+
+    // Seconds since the current run started, by the wall clock so that slow
+    // frames don't slow the timer down
+    pub fn run_time(&self) -> f64 {
+        self.run_started.elapsed().as_secs_f64()
+    }
+
+    // End the current run, raising a LevelComplete event with its time
+    pub fn complete_level(&mut self) {
+        self.events.push(GameEvent::LevelComplete { seconds: self.run_time() });
+    }
+
+    // Load or generate the textures a map names. A material whose texture
+    // can't be loaded keeps its usual look, and the failure is shown as a
+    // notice.
+    fn load_map_textures(&mut self, files: Vec<(u8, PathBuf)>, floor: Option<PathBuf>, ceiling: Option<PathBuf>) {
+        self.map_textures.clear();
+        for (material, path) in &files {
+            if let Some(texture) = self.open_texture(path) {
+                let index = *material as usize;
+                if self.map_textures.len() <= index {
+                    self.map_textures.resize_with(index + 1, || None);
+                }
+                self.map_textures[index] = Some(texture);
+            }
+        }
+        self.texture_files = files;
+        self.map_floor_texture = floor.as_deref().and_then(|path| self.open_texture(path));
+        self.map_ceiling_texture = ceiling.as_deref().and_then(|path| self.open_texture(path));
+        self.floor_texture_file = floor;
+        self.ceiling_texture_file = ceiling;
+    }
+
+    fn open_texture(&mut self, path: &Path) -> Option<ImageTexture> {
+        ImageTexture::open(path)
+            .map_err(|err| self.show_notice(&err.to_string(), TEXTURE_NOTICE_TIME))
+            .ok()
+    }
+
+    // Image texture drawn across the ceiling or floor, if any
+    fn plane_texture(&self, ceiling: bool) -> Option<&ImageTexture> {
+        let texture = if ceiling {
+            self.ceiling_texture.as_ref().or(self.map_ceiling_texture.as_ref())
+        } else {
+            self.floor_texture.as_ref().or(self.map_floor_texture.as_ref())
+        };
+        texture.filter(|_| self.textures)
+    }
+
+    // Image texture drawn on walls of a material, if any
+    fn image_texture(&self, material: u8) -> Option<&ImageTexture> {
+        [&self.image_textures, &self.map_textures]
+            .into_iter()
+            .find_map(|textures| textures.get(material as usize).and_then(Option::as_ref))
+    }
+
+    // Link the map's teleporter cells: each letter A-Z links the two cells
+    // that share it. A letter used only once, or more than twice, is ignored
+    // and reported in a notice.
+    fn link_teleporters(&mut self) {
+        let mut cells: HashMap<u8, Vec<(i32, i32)>> = HashMap::new();
+        for ((x, y), cell) in self.map.cells() {
+            if cell.is_ascii_uppercase() {
+                cells.entry(cell).or_default().push((x as i32, y as i32));
+            }
+        }
+        
+        self.teleporters.clear();
+        let mut unpaired = Vec::new();
+        for (letter, positions) in cells {
+            match positions[..] {
+                [a, b] => {
+                    self.teleporters.insert(a, b);
+                    self.teleporters.insert(b, a);
+                }
+                _ => unpaired.push((letter as char).to_string()),
+            }
+        }
+        if !unpaired.is_empty() {
+            unpaired.sort_unstable();
+            let notice = format!("Teleporters without a pair ignored: {}", unpaired.join(", "));
+            self.show_notice(&notice, TELEPORTER_NOTICE_TIME);
+        }
+    }
+
+    // Show a message in the label for `duration`
+    pub fn show_notice(&mut self, text: &str, duration: Duration) {
+        self.notice = Some((text.to_string(), Instant::now() + duration));
+    }
+
+    pub fn map_hash(&self) -> u64 {
+        self.map_hash
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    // The current map as authored and edited, with its name, spawn points,
+    // wall colors, wall heights, teleporter facings, sectors, light levels
+    // and environment, ready to be saved
+    pub fn map_file(&self) -> MapFile {
+        let mut letters: Vec<u8> = self.authored_map.cells().map(|(_, cell)| cell).filter(u8::is_ascii_uppercase).collect();
+        letters.sort_unstable();
+        letters.dedup();
+        // Teleporter cells with a facing always come in pairs
+        let teleporter_facing = letters
+            .into_iter()
+            .filter_map(|letter| {
+                let mut facings = self
+                    .authored_map
+                    .cells()
+                    .filter(|&(_, cell)| cell == letter)
+                    .map(|((x, y), _)| self.teleport_facing.get(&(x as i32, y as i32)).copied());
+                Some((letter, [facings.next()??, facings.next()??]))
+            })
+            .collect();
+        let mut wall_colors: Vec<(u8, u8)> = self.wall_colors.iter().map(|(&material, &color)| (material, color)).collect();
+        wall_colors.sort_unstable();
+        let mut wall_heights: Vec<((usize, usize), f64)> = self
+            .wall_heights
+            .iter()
+            .map(|(&(x, y), &height)| ((x as usize, y as usize), height))
+            .collect();
+        wall_heights.sort_unstable_by_key(|&((x, y), _)| (y, x));
+        let sectors = self
+            .sectors
+            .iter()
+            .map(|(&(x, y), &letter)| ((x as usize, y as usize), letter))
+            .collect();
+        let mut sector_colors: Vec<(u8, SectorColors)> = self.sector_colors.iter().map(|(&letter, &colors)| (letter, colors)).collect();
+        sector_colors.sort_unstable_by_key(|&(letter, _)| letter);
+        let light_levels = self
+            .light_levels
+            .iter()
+            .map(|(&(x, y), &level)| ((x as usize, y as usize), level))
+            .collect();
+        let mut hazards: Vec<((usize, usize), Hazard)> = self
+            .hazards
+            .iter()
+            .map(|(&(x, y), &hazard)| ((x as usize, y as usize), hazard))
+            .collect();
+        hazards.sort_unstable_by_key(|&((x, y), _)| (y, x));
+        // The chosen spawn point may have been moved in the editor
+        let mut spawns = self.spawn_points.clone();
+        if let Some(chosen) = spawns.get_mut(self.spawn_index) {
+            *chosen = self.spawn;
+        }
+        MapFile {
+            name: self.map_name.clone(),
+            walls: self.authored_map.clone(),
+            spawns,
+            sprites: self.sprites.clone(),
+            lights: self.point_lights.clone(),
+            wall_colors,
+            textures: self.texture_files.clone(),
+            floor_texture: self.floor_texture_file.clone(),
+            ceiling_texture: self.ceiling_texture_file.clone(),
+            teleporter_facing,
+            wall_heights,
+            sectors,
+            sector_colors,
+            light_levels,
+            hazards,
+            environment: self.environment,
+        }
+    }
+
+    fn move_to_spawn(&mut self) {
+        let (x, y, angle) = self.spawn;
+        self.player.x = x;
+        self.player.y = y;
+        self.player.angle = normalize_angle(angle);
+        // In an endless world the spawn point may have left the window
+        self.stream_world();
+        self.nudge_out_of_wall();
+        // Snap to the grid again and drop any step in progress
+        self.set_grid_mode(self.grid_mode);
+        self.current_cell = (self.player.x.floor() as i32, self.player.y.floor() as i32);
+        // Arrive standing still, without the bob of the last stride
+        self.walk = WalkCycle::default();
+        self.at_spawn = true;
+    }
+
+    // Load the built-in map `step` places after the current one, wrapping around
+    pub fn cycle_map(&mut self, step: isize) {
+        let count = BUILTIN_MAPS.len() as isize;
+        let index = (self.map_index as isize + step).rem_euclid(count);
+        self.load_builtin(index as usize);
+    }
+
+    pub fn map_name(&self) -> &str {
+        &self.map_name
+    }
+
+    // Text shown in the top-left corner: the map name, plus the readings
+    // while measuring
+    fn label(&self) -> String {
+        let mut label = self.map_name().to_string();
+        if self.measuring {
+            match self.measure() {
+                Some(distance) => label.push_str(&format!(" | wall {:.2}", distance)),
+                None => label.push_str(" | wall -"),
+            }
+            if let Some(distance) = self.marker_distance() {
+                label.push_str(&format!(" | markers {:.2} apart", distance));
+            }
+        }
+        if let Some(best) = self.best_time {
+            let time = self.run_time();
+            label.push_str(&format!(" | {:.1}s | best {:.1}s ({:+.1})", time, best, time - best));
+        }
+        if let Some((text, until)) = &self.notice {
+            if Instant::now() < *until {
+                label.push_str(&format!(" | {}", text));
+            }
+        }
+        label
+    }
+
+    // Relocate the player to the nearest open cell if they ended up inside a wall
+    // (bad spawn data or teleport target). Searches outward in square rings around
+    // the player's cell and picks the open cell whose center is closest.
+    // Returns true if the player was moved, which is also shown as a notice.
+    pub fn nudge_out_of_wall(&mut self) -> bool {
+        let start_x = (self.player.x.floor() as i32).clamp(0, self.map.width() as i32 - 1);
+        let start_y = (self.player.y.floor() as i32).clamp(0, self.map.height() as i32 - 1);
+        
+        if self.is_open(start_x as usize, start_y as usize) {
+            return false;
+        }
+        
+        let max_radius = self.map.width().max(self.map.height()) as i32;
+        for radius in 1..=max_radius {
+            let mut best: Option<(f64, f64, f64)> = None;
+            
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    // Only visit the outer ring; inner rings were already searched
+                    if dx.abs() != radius && dy.abs() != radius {
+                        continue;
+                    }
+                    
+                    let cell_x = start_x + dx;
+                    let cell_y = start_y + dy;
+                    if !self.map.contains(cell_x, cell_y) {
+                        continue;
+                    }
+                    if !self.is_open(cell_x as usize, cell_y as usize) {
+                        continue;
+                    }
+                    
+                    let center_x = cell_x as f64 + 0.5;
+                    let center_y = cell_y as f64 + 0.5;
+                    let dist = (center_x - self.player.x).hypot(center_y - self.player.y);
+                    match best {
+                        Some((best_dist, _, _)) if best_dist <= dist => {}
+                        _ => best = Some((dist, center_x, center_y)),
+                    }
+                }
+            }
+            
+            if let Some((_, new_x, new_y)) = best {
+                let notice = format!(
+                    "Moved out of a wall at ({:.2}, {:.2}) to ({:.2}, {:.2})",
+                    self.player.x, self.player.y, new_x, new_y
+                );
+                self.show_notice(&notice, NUDGE_NOTICE_TIME);
+                self.player.x = new_x;
+                self.player.y = new_y;
+                return true;
+            }
+        }
+        
+        false
+    }
+
+    fn get_map_value(&self, x: usize, y: usize) -> u8 {
+        match self.map.get(x, y) {
+            Some(b'/' | b'\\') => DIAGONAL_MATERIAL,
+            Some(b'-' | b'|') => THIN_WALL_MATERIAL,
+            Some(b'A'..=b'Z' | EXIT_CELL) => 0, // Teleporters and exits are open floor
+            Some(DOOR_CELL) => DOOR_MATERIAL,
+            Some(PUSH_WALL_CELL) => PUSH_WALL_MATERIAL,
+            Some(DESTRUCTIBLE_CELL) => DESTRUCTIBLE_MATERIAL,
+            Some(WINDOW_CELL) => WINDOW_MATERIAL,
+            Some(MIRROR_CELL) => MIRROR_MATERIAL,
+            Some(cell) => cell - b'0',
+            None => 1,
+        }
+    }
+
+    // Map character at a cell, or None outside the map
+    pub fn cell(&self, x: usize, y: usize) -> Option<u8> {
+        self.map.get(x, y)
+    }
+
+    // Replace the map character at a cell. Teleporter cells can't be changed
+    // or created this way, since that would break their pairing. Doors are
+    // found again afterwards, closed, since a wall placed beside one can
+    // change the way it slides, and destructible walls are back at full
+    // strength. The authored map, which is what gets saved, changes too.
+    pub fn set_cell(&mut self, x: usize, y: usize, cell: u8) -> bool {
+        let changed = match self.map.get(x, y) {
+            Some(current) if !current.is_ascii_uppercase() && !cell.is_ascii_uppercase() => self.map.set(x, y, cell),
+            _ => false,
+        };
+        if changed {
+            self.authored_map.set(x, y, cell);
+            self.doors = parse_doors(&self.map);
+            self.destructibles = find_destructibles(&self.map);
+            let map = &self.map;
+            self.wall_heights.retain(|&(x, y), _| map.get(x as usize, y as usize).is_some_and(has_height));
+            self.hazards.retain(|&(x, y), _| map.get(x as usize, y as usize).is_some_and(is_open_cell));
+            self.exits = find_exits(&self.map);
+            self.map_hash = hash_map(&self.authored_map);
+        }
+        changed
+    }
+
+    // Map rows as text, in the same format as the built-in maps
+    pub fn map_text(&self) -> String {
+        self.authored_map
+            .rows()
+            .map(|row| format!("{}\n", String::from_utf8_lossy(row)))
+            .collect()
+    }
+
+    // Whether the player and enemies can walk through a cell
+    fn is_open(&self, x: usize, y: usize) -> bool {
+        if let Some(door) = self.doors.get(&(x as i32, y as i32)) {
+            return door.is_passable();
+        }
+        if self.push_wall.as_ref().is_some_and(|push_wall| push_wall.covers((x as i32, y as i32))) {
+            return false;
+        }
+        self.map.get(x, y).is_some_and(is_open_cell)
+    }
+
+    fn get_segment(&self, x: i32, y: i32) -> Option<Segment> {
+        if !self.map.contains(x, y) {
+            return None;
+        }
+        Segment::from_cell(self.map.get(x as usize, y as usize)?)
+    }
+
+    // Ceiling layer of the current map, if it has one and raised ceilings are on
+    fn raised_ceiling_map(&self) -> Option<&'static [&'static str]> {
+        self.ceiling_map.filter(|_| self.raised_ceilings)
+    }
+
+    // Wall height multiplier for a cell, 1.0 unless it lies under a raised ceiling
+    fn get_ceiling_height(&self, x: i32, y: i32) -> f64 {
+        if x < 0 || y < 0 {
+            return 1.0;
+        }
+        
+        let cell = self.raised_ceiling_map().and_then(|rows| rows.get(y as usize)?.as_bytes().get(x as usize));
+        match cell {
+            Some(b'1') => TALL_CEILING_HEIGHT,
+            _ => 1.0,
+        }
+    }
+
+    // Move the player straight to a position and facing (radians), for
+    // debugging. The position must be inside the map; a position inside a wall
+    // is nudged to the nearest open cell.
+    pub fn teleport(&mut self, x: f64, y: f64, angle: f64) -> Result<(), RaycastError> {
+        let (width, height) = (self.map.width(), self.map.height());
+        if !(0.0..width as f64).contains(&x) || !(0.0..height as f64).contains(&y) {
+            return Err(RaycastError::Config(format!(
+                "({}, {}) is outside the {}x{} map",
+                x, y, width, height
+            )));
+        }
+        
+        self.player.x = x;
+        self.player.y = y;
+        self.player.angle = normalize_angle(angle);
+        self.nudge_out_of_wall();
+        // Snap to the grid again and drop any step in progress
+        self.set_grid_mode(self.grid_mode);
+        self.current_cell = (self.player.x.floor() as i32, self.player.y.floor() as i32);
+        self.at_spawn = false;
+        Ok(())
+    }
+
+    // Count the open cells reachable from the player's cell by walking
+    // between orthogonally adjacent open cells
+    pub fn reachable_area(&self) -> usize {
+        let start_x = self.player.x.floor() as usize;
+        let start_y = self.player.y.floor() as usize;
+        if !self.is_open(start_x, start_y) {
+            return 0;
+        }
+        
+        let (width, height) = (self.map.width(), self.map.height());
+        let mut visited = vec![vec![false; width]; height];
+        let mut stack = vec![(start_x, start_y)];
+        visited[start_y][start_x] = true;
+        let mut count = 0;
+        
+        while let Some((x, y)) = stack.pop() {
+            count += 1;
+            let neighbors = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbors {
+                // Doors count as open, since the player can open them
+                let open = self.is_open(nx, ny) || self.doors.contains_key(&(nx as i32, ny as i32));
+                if nx < width && ny < height && !visited[ny][nx] && open {
+                    visited[ny][nx] = true;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        
+        count
+    }
+
+    // Summary table of the loaded map, printed by --info
+    pub fn map_info(&self) -> String {
+        let mut counts = [0usize; 10];
+        let mut diagonals = 0;
+        let mut thin_walls = 0;
+        let mut push_walls = 0;
+        let mut windows = 0;
+        let mut mirrors = 0;
+        let mut exits = 0;
+        for row in self.map.rows() {
+            for &cell in row {
+                if let Some(segment) = Segment::from_cell(cell) {
+                    if segment.is_diagonal() {
+                        diagonals += 1;
+                    } else {
+                        thin_walls += 1;
+                    }
+                } else if cell == DOOR_CELL {
+                    continue;
+                } else if cell == PUSH_WALL_CELL {
+                    push_walls += 1;
+                } else if cell == DESTRUCTIBLE_CELL {
+                    continue;
+                } else if cell == WINDOW_CELL {
+                    windows += 1;
+                } else if cell == MIRROR_CELL {
+                    mirrors += 1;
+                } else if cell == EXIT_CELL {
+                    exits += 1;
+                } else if cell.is_ascii_uppercase() {
+                    counts[0] += 1;
+                } else {
+                    counts[(cell - b'0') as usize] += 1;
+                }
+            }
+        }
+        let raised_ceilings = self.raised_ceiling_map().map_or(0, |rows| {
+            rows.iter().map(|row| row.bytes().filter(|&c| c == b'1').count()).sum()
+        });
+        
+        let mut info = String::new();
+        info.push_str("Map info\n");
+        info.push_str(&format!("  {:<18}{}\n", "Name", self.map_name()));
+        info.push_str(&format!("  {:<18}{} x {}\n", "Dimensions", self.map.width(), self.map.height()));
+        info.push_str(&format!(
+            "  {:<18}({:.2}, {:.2}) facing {:.0}°\n",
+            "Spawn",
+            self.player.x,
+            self.player.y,
+            self.player.angle.to_degrees()
+        ));
+        info.push_str(&format!("  {:<18}{}\n", "Spawn points", self.spawn_points.len()));
+        info.push_str(&format!("  {:<18}{}\n", "Reachable cells", self.reachable_area()));
+        info.push_str(&format!("  {:<18}{}\n", "Raised ceilings", raised_ceilings));
+        info.push_str(&format!("  {:<18}{}\n", "Diagonal walls", diagonals));
+        info.push_str(&format!("  {:<18}{}\n", "Thin walls", thin_walls));
+        info.push_str(&format!("  {:<18}{}\n", "Teleporter pairs", self.teleporters.len() / 2));
+        info.push_str(&format!("  {:<18}{}\n", "Doors", self.doors.len()));
+        info.push_str(&format!("  {:<18}{}\n", "Push-walls", push_walls));
+        info.push_str(&format!("  {:<18}{}\n", "Destructibles", self.destructibles.len()));
+        info.push_str(&format!("  {:<18}{}\n", "Windows", windows));
+        info.push_str(&format!("  {:<18}{}\n", "Mirrors", mirrors));
+        info.push_str(&format!("  {:<18}{}\n", "Exits", exits));
+        info.push('\n');
+        info.push_str(&format!("  {:<6}{:<8}{:>6}\n", "Cell", "Type", "Count"));
+        for (value, &count) in counts.iter().enumerate().filter(|(_, &count)| count > 0) {
+            let kind = match Translucency::for_material(value as u8) {
+                _ if value == 0 => "empty",
+                Some(translucency) if translucency.solid => "glass",
+                Some(_) => "curtain",
+                None => "wall",
+            };
+            info.push_str(&format!("  {:<6}{:<8}{:>6}\n", value, kind, count));
+        }
+        
+        info
+    }
+
+    // Cosmetic floor height at a world position, 0.0 for flat floor
+    fn get_floor_height(&self, x: f64, y: f64) -> f64 {
+        let (Some(rows), true) = (self.floor_map, x >= 0.0 && y >= 0.0) else {
+            return 0.0;
+        };
+        match rows.get(y as usize).and_then(|row| row.as_bytes().get(x as usize)) {
+            Some(b'r') => x.fract() * PLATFORM_HEIGHT,
+            Some(b'p') => PLATFORM_HEIGHT,
+            _ => 0.0,
+        }
+    }
+
+    // Cast a ray from the player's position
+    pub fn cast_ray(&self, ray_angle: f64) -> RayHit {
+        self.cast_ray_from(self.player.x, self.player.y, ray_angle, f64::INFINITY)
+    }
+
+    // Cast a column's ray from the player's position, bouncing it off any
+    // mirrors it strikes. The hit, and the see-through cells and short walls
+    // passed on the way, are at distances along the whole path, which is
+    // where their reflections appear.
+    fn cast_view_ray(&self, ray_angle: f64) -> (RayHit, RayPath) {
+        let mut path = RayPath::new((self.player.x, self.player.y), ray_angle);
+        let mut ray = self.cast_ray(ray_angle);
+        while ray.hit
+            && path.bounces() < mirror::MAX_BOUNCES
+            && self.map.get(ray.map_x as usize, ray.map_y as usize) == Some(MIRROR_CELL)
+        {
+            let travelled = ray.distance;
+            path.bounce(travelled, ray.normal);
+            let (x, y) = path.point(travelled);
+            let (dx, dy) = path.direction();
+            let mut next = self.cast_ray_from(
+                x + ray.normal.0 * MIRROR_GAP,
+                y + ray.normal.1 * MIRROR_GAP,
+                dy.atan2(dx),
+                f64::INFINITY,
+            );
+            next.distance += travelled;
+            for layer in &mut next.translucent {
+                layer.distance += travelled;
+            }
+            for wall in &mut next.short_walls {
+                wall.distance += travelled;
+                wall.exit += travelled;
+            }
+            ray.translucent.append(&mut next.translucent);
+            ray.short_walls.append(&mut next.short_walls);
+            next.translucent = ray.translucent;
+            next.short_walls = ray.short_walls;
+            ray = next;
+        }
+        (ray, path)
+    }
+
+    // True if no wall lies on the straight line between two world points.
+    // When the line passes exactly through a grid corner the DDA steps along
+    // y first, so the line is blocked if the cell it crosses that way is a wall.
+    pub fn has_line_of_sight(&self, from: (f64, f64), to: (f64, f64)) -> bool {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let distance = dx.hypot(dy);
+        if distance == 0.0 {
+            return true;
+        }
+        
+        !self.cast_ray_from(from.0, from.1, dy.atan2(dx), distance).hit
+    }
+
+    // DDA ray cast from an arbitrary point. Stops at the first wall, when
+    // leaving the map, once the next grid crossing is beyond max_distance, or
+    // after max_dda_steps cells.
+    pub fn cast_ray_from(&self, x: f64, y: f64, ray_angle: f64, max_distance: f64) -> RayHit {
+        let sin = ray_angle.sin();
+        let cos = ray_angle.cos();
+        
+        let delta_x = if cos.abs() < 0.0001 { 1e30 } else { (1.0 / cos).abs() };
+        let delta_y = if sin.abs() < 0.0001 { 1e30 } else { (1.0 / sin).abs() };
+        
+        let step_x = if cos < 0.0 { -1 } else { 1 };
+        let step_y = if sin < 0.0 { -1 } else { 1 };
+        
+        let mut map_x = x.floor() as i32;
+        let mut map_y = y.floor() as i32;
+        
+        let mut side_dist_x = if cos < 0.0 {
+            (x - map_x as f64) * delta_x
+        } else {
+            (map_x as f64 + 1.0 - x) * delta_x
+        };
+        let mut side_dist_y = if sin < 0.0 {
+            (y - map_y as f64) * delta_y
+        } else {
+            (map_y as f64 + 1.0 - y) * delta_y
+        };
+        
+        // Distance to the diagonal or thin wall in a cell, if the ray crosses
+        // it between entering and leaving the cell
+        let segment_hit = |map_x: i32, map_y: i32, entry: f64, exit: f64| {
+            let segment = self.get_segment(map_x, map_y)?;
+            let (u, v) = (x - map_x as f64, y - map_y as f64);
+            segment
+                .intersect(u, v, cos, sin)
+                .filter(|&t| t >= entry && t <= exit.min(max_distance))
+                .map(|t| (t, segment.normal_towards(u, v)))
+        };
+        
+        // Distance to a door's panel or the sliding push-wall in a cell, where
+        // along its face the ray strikes, the face's normal and its material,
+        // if the ray crosses it between entering and leaving the cell
+        let moving_hit = |map_x: i32, map_y: i32, entry: f64, exit: f64| {
+            let (t, along, normal, material) = match (self.doors.get(&(map_x, map_y)), &self.push_wall) {
+                (Some(door), _) => {
+                    let (t, along) = door.intersect(x - map_x as f64, y - map_y as f64, cos, sin)?;
+                    let normal = if door.along_x { (0.0, -sin.signum()) } else { (-cos.signum(), 0.0) };
+                    (t, along, normal, DOOR_MATERIAL)
+                }
+                (None, Some(push_wall)) if push_wall.covers((map_x, map_y)) => {
+                    let (t, along, normal) = push_wall.intersect(x, y, cos, sin)?;
+                    (t, along, normal, PUSH_WALL_MATERIAL)
+                }
+                _ => return None,
+            };
+            (t >= entry && t <= exit.min(max_distance)).then_some((t, along, normal, material))
+        };
+        
+        // The starting cell may itself hold a segment, a door or the push-wall
+        let mut segment = segment_hit(map_x, map_y, 0.0, side_dist_x.min(side_dist_y));
+        let mut moving = moving_hit(map_x, map_y, 0.0, side_dist_x.min(side_dist_y));
+        let mut hit = segment.is_some() || moving.is_some();
+        let mut side = false;
+        let mut translucent = Vec::new();
+        let mut short_walls = Vec::new();
+        // Material of the previous cell, so the inner faces of a block of
+        // see-through cells are only drawn once
+        let mut previous_material = self.get_map_value(map_x.max(0) as usize, map_y.max(0) as usize);
+        
+        let max_steps = self.max_dda_steps.unwrap_or(self.map.width() + self.map.height());
+        let mut steps = 0;
+        while !hit {
+            if side_dist_x.min(side_dist_y) > max_distance || steps >= max_steps {
+                break;
+            }
+            steps += 1;
+            // Through a corner, step along y first. The crossings of a
+            // diagonal ray can differ by rounding, so near ties count too.
+            if side_dist_x < side_dist_y - CORNER_TOLERANCE {
+                side_dist_x += delta_x;
+                map_x += step_x;
+                side = false;
+            } else {
+                side_dist_y += delta_y;
+                map_y += step_y;
+                side = true;
+            }
+            
+            if !self.map.contains(map_x, map_y) {
+                break;
+            }
+            
+            let entry = if side { side_dist_y - delta_y } else { side_dist_x - delta_x };
+            if self.doors.contains_key(&(map_x, map_y))
+                || self.push_wall.as_ref().is_some_and(|push_wall| push_wall.covers((map_x, map_y)))
+            {
+                moving = moving_hit(map_x, map_y, entry, side_dist_x.min(side_dist_y));
+                hit = moving.is_some();
+                continue;
+            }
+            if self.get_segment(map_x, map_y).is_some() {
+                segment = segment_hit(map_x, map_y, entry, side_dist_x.min(side_dist_y));
+                hit = segment.is_some();
+                continue;
+            }
+            
+            let material = self.get_map_value(map_x as usize, map_y as usize);
+            let (wall_x, normal) = if side {
+                (x + entry * cos, (0.0, -step_y as f64))
+            } else {
+                (y + entry * sin, (-step_x as f64, 0.0))
+            };
+            // Every window in a row has its own grate, so unlike a block of
+            // glass each one is drawn
+            let window = self.map.get(map_x as usize, map_y as usize) == Some(WINDOW_CELL);
+            if window && translucent.len() < MAX_TRANSLUCENT_LAYERS {
+                translucent.push(TranslucentHit {
+                    distance: entry,
+                    map_x,
+                    map_y,
+                    material,
+                    wall_x: wall_x - wall_x.floor(),
+                    normal,
+                });
+                previous_material = material;
+                continue;
+            }
+            let height = self.wall_heights.get(&(map_x, map_y)).copied();
+            if let Some(height) = height.filter(|_| short_walls.len() < MAX_SHORT_WALLS) {
+                short_walls.push(ShortWallHit {
+                    distance: entry,
+                    exit: side_dist_x.min(side_dist_y),
+                    map_x,
+                    map_y,
+                    material,
+                    height,
+                    wall_x: wall_x - wall_x.floor(),
+                    normal,
+                });
+                previous_material = material;
+                continue;
+            }
+            match Translucency::for_material(material) {
+                Some(_) if translucent.len() < MAX_TRANSLUCENT_LAYERS => {
+                    if material != previous_material {
+                        translucent.push(TranslucentHit {
+                            distance: entry,
+                            map_x,
+                            map_y,
+                            material,
+                            wall_x: wall_x - wall_x.floor(),
+                            normal,
+                        });
+                    }
+                }
+                _ => hit = material != 0,
+            }
+            previous_material = material;
+        }
+        
+        // Distances are measured along the (unit length) ray, so the hit point
+        // is simply the ray direction scaled by the distance
+        let (distance, wall_x, normal) = match (segment, moving) {
+            // Thin walls along the y axis are measured along y; diagonals and
+            // thin walls along the x axis along x
+            (Some((distance, normal)), _) if normal.1 == 0.0 => {
+                side = false;
+                (distance, y + distance * sin, normal)
+            }
+            (Some((distance, normal)), _) => {
+                side = normal.0 == 0.0;
+                (distance, x + distance * cos, normal)
+            }
+            (None, Some((distance, along, normal, _))) => {
+                side = normal.1 != 0.0;
+                (distance, along, normal)
+            }
+            (None, None) if !side => {
+                let distance = side_dist_x - delta_x;
+                (distance, y + distance * sin, (-step_x as f64, 0.0))
+            }
+            (None, None) => {
+                let distance = side_dist_y - delta_y;
+                (distance, x + distance * cos, (0.0, -step_y as f64))
+            }
+        };
+        let material = match moving {
+            Some((_, _, _, material)) => material,
+            None if hit => self.get_map_value(map_x as usize, map_y as usize),
+            None => 0,
+        };
+        
+        RayHit {
+            distance,
+            map_x,
+            map_y,
+            hit,
+            side,
+            wall_x: wall_x - wall_x.floor(),
+            material,
+            normal,
+            translucent,
+            short_walls,
+        }
+    }
+
+    // Rebuild the per-column ray angle table for a new screen width
+    fn update_column_angles(&mut self, screen_width: usize) {
+        self.column_angles = (0..screen_width)
+            .map(|x| {
+                let camera_x = 2.0 * x as f64 / screen_width as f64 - 1.0;
+                (camera_x * self.fov).atan()
+            })
+            .collect();
+        self.column_fov = self.fov;
+    }
+
+    // Interact along the center of the view (keyboard fire)
+    pub fn fire(&mut self) -> Option<RayHit> {
+        self.interact(0.0)
+    }
+
+    // Interact along the ray that renders the given screen column (mouse click)
+    pub fn fire_at_column(&mut self, column: usize) -> Option<RayHit> {
+        let column = if self.mirror {
+            self.last_width.saturating_sub(column + 1)
+        } else {
+            column
+        };
+        let column = column * self.render_mode.pixel_columns(1);
+        // Map the screen column into the rendered image, which may be scaled
+        let (first, count) = self.view_columns;
+        if column < first || column >= first + count {
+            return None;
+        }
+        let image_column = (column - first) * self.column_angles.len() / count;
+        let offset = self.column_angles.get(image_column).copied().unwrap_or(0.0);
+        self.interact(offset)
+    }
+
+    // Cast an interaction ray at the given offset from the player's facing and
+    // highlight the wall it strikes. A destructible wall takes a hit, and
+    // crumbles into floor after its last one.
+    fn interact(&mut self, angle_offset: f64) -> Option<RayHit> {
+        let ray = self.cast_ray(self.player.angle + angle_offset);
+        if !ray.hit {
+            return None;
+        }
+        
+        self.hit_flash = Some(HitFlash {
+            map_x: ray.map_x,
+            map_y: ray.map_y,
+            until: Instant::now() + HIT_FLASH_DURATION,
+        });
+        self.events.push(GameEvent::WallHit {
+            map_x: ray.map_x,
+            map_y: ray.map_y,
+            distance: ray.distance,
+        });
+        let cell = (ray.map_x, ray.map_y);
+        if self.destructibles.get_mut(&cell).is_some_and(DestructibleWall::hit) {
+            self.destructibles.remove(&cell);
+            self.map.set(cell.0 as usize, cell.1 as usize, b'0');
+            self.hit_flash = None;
+            self.events.push(GameEvent::WallDestroyed { map_x: cell.0, map_y: cell.1 });
+        }
+        Some(ray)
+    }
+
+    // Open the door or push the push-wall straight ahead, if it is within
+    // reach. Returns false if there is nothing there to use, the door is
+    // already open, or the push-wall has no room to move.
+    pub fn activate(&mut self) -> bool {
+        let ray = self.cast_ray(self.player.angle);
+        if !ray.hit || ray.distance > USE_RANGE {
+            return false;
+        }
+        let cell = (ray.map_x, ray.map_y);
+        if let Some(door) = self.doors.get_mut(&cell) {
+            if !door.open() {
+                return false;
+            }
+            self.events.push(GameEvent::DoorOpened { map_x: cell.0, map_y: cell.1 });
+            return true;
+        }
+        
+        // Push-walls slide away from the face that was used, one at a time
+        if self.push_wall.is_some() || self.cell(cell.0 as usize, cell.1 as usize) != Some(PUSH_WALL_CELL) {
+            return false;
+        }
+        let push_wall = PushWall::new(cell, (-ray.normal.0.round() as i32, -ray.normal.1.round() as i32));
+        if !self.can_push_into(push_wall.next_cell()) {
+            return false;
+        }
+        self.map.set(cell.0 as usize, cell.1 as usize, b'0');
+        self.push_wall = Some(push_wall);
+        self.events.push(GameEvent::WallPushed { map_x: cell.0, map_y: cell.1 });
+        true
+    }
+
+    // Whether a push-wall may slide into a cell: plain floor with nobody in it
+    fn can_push_into(&self, cell: (i32, i32)) -> bool {
+        let occupied = |x: f64, y: f64| (x.floor() as i32, y.floor() as i32) == cell;
+        self.map.contains(cell.0, cell.1)
+            && self.map.get(cell.0 as usize, cell.1 as usize) == Some(b'0')
+            && !occupied(self.player.x, self.player.y)
+            && !self.enemies.iter().any(|enemy| occupied(enemy.x, enemy.y))
+    }
+
+    // Distance to the wall under the crosshair, if there is one
+    pub fn measure(&self) -> Option<f64> {
+        let ray = self.cast_ray(self.player.angle);
+        ray.hit.then_some(ray.distance)
+    }
+
+    // Drop a measurement marker on the wall under the crosshair. A third
+    // marker replaces the oldest. Returns false if no wall is in view.
+    pub fn drop_marker(&mut self) -> bool {
+        let ray = self.cast_ray(self.player.angle);
+        if !ray.hit {
+            return false;
+        }
+        let distance = (ray.distance - MARKER_WALL_GAP).max(0.0);
+        let (sin, cos) = self.player.angle.sin_cos();
+        if self.markers.len() == 2 {
+            self.markers.remove(0);
+        }
+        self.markers.push((self.player.x + cos * distance, self.player.y + sin * distance));
+        true
+    }
+
+    pub fn clear_markers(&mut self) {
+        self.markers.clear();
+    }
+
+    pub fn markers(&self) -> &[(f64, f64)] {
+        &self.markers
+    }
+
+    // Straight-line distance between the two markers, once both are placed
+    pub fn marker_distance(&self) -> Option<f64> {
+        match self.markers[..] {
+            [a, b] => Some((a.0 - b.0).hypot(a.1 - b.1)),
+            _ => None,
+        }
+    }
+
+    // Take all events raised since the last call
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Reduce the player's health unless they were damaged too recently.
+    // Returns true if damage was applied.
+    pub fn take_damage(&mut self, amount: f64, now: Instant) -> bool {
+        if self.is_game_over()
+            || self.last_damage.is_some_and(|last| now.duration_since(last) < DAMAGE_COOLDOWN)
+        {
+            return false;
+        }
+        self.player.health = (self.player.health - amount).max(0.0);
+        self.last_damage = Some(now);
+        self.effects.push(DAMAGE_FLASH, DAMAGE_FLASH_DURATION, now);
+        self.shake(DAMAGE_SHAKE, DAMAGE_SHAKE_DURATION);
+        true
+    }
+
+    // Lay a flash or fade over the whole view for `duration`. Fades hold
+    // their color once done until `clear_effects`.
+    pub fn add_effect(&mut self, effect: ScreenEffect, duration: Duration) {
+        self.effects.push(effect, duration, Instant::now());
+    }
+
+    pub fn clear_effects(&mut self) {
+        self.effects.clear();
+    }
+
+    // Shake the view for a while, by up to `intensity` times its height at
+    // first and less and less until `duration` is up. A weaker shake doesn't
+    // cut short a stronger one already going.
+    pub fn shake(&mut self, intensity: f64, duration: Duration) {
+        let now = Instant::now();
+        if self.shake.as_ref().is_some_and(|shake| shake.strength(now) > intensity) {
+            return;
+        }
+        self.shake = Some(Shake {
+            intensity,
+            started: now,
+            duration,
+        });
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.player.health <= 0.0
+    }
+
+    // Everything drawn as a billboard: static sprites, teleporter markers,
+    // enemies, measurement markers and, seen from a free camera, the player
+    pub fn billboards(&self) -> Vec<Sprite> {
+        let teleporters = self.teleporters.keys().map(|&(x, y)| Sprite {
+            x: x as f64 + 0.5,
+            y: y as f64 + 0.5,
+            color: TELEPORTER_COLOR,
+            kind: SpriteKind::Orb,
+        });
+        let exits = self.exits.iter().map(|&(x, y)| Sprite {
+            x: x as f64 + 0.5,
+            y: y as f64 + 0.5,
+            color: EXIT_COLOR,
+            kind: SpriteKind::Orb,
+        });
+        let enemies = self.enemies.iter().map(|enemy| Sprite {
+            x: enemy.x,
+            y: enemy.y,
+            color: enemy.color(),
+            kind: SpriteKind::Orb,
+        });
+        let markers = self.markers.iter().map(|&(x, y)| Sprite {
+            x,
+            y,
+            color: MARKER_COLOR,
+            kind: SpriteKind::Orb,
+        });
+        let player = self.spectating.iter().map(|player| Sprite {
+            x: player.x,
+            y: player.y,
+            color: SPECTATED_PLAYER_COLOR,
+            kind: SpriteKind::Orb,
+        });
+        self.sprites
+            .iter()
+            .copied()
+            .chain(teleporters)
+            .chain(exits)
+            .chain(enemies)
+            .chain(markers)
+            .chain(player)
+            .collect()
+    }
+
+    pub fn render(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let started = Instant::now();
+        let (screen_width, screen_height) = self.screen_size(stdout)?;
+        let (view_width, view_height) = self.viewport(screen_width, screen_height);
+        let mut frame = match (self.render_mode, self.color_depth) {
+            (RenderMode::Ascii, _) => render::encode_text(&self.compose_frame(view_width, view_height)),
+            (_, ColorDepth::TrueColor) => self.render_mode.encode_rgb(&self.compose_frame(view_width, view_height)),
+            (_, ColorDepth::Indexed) => self.render_mode.encode(&self.compose_frame(view_width, view_height)),
+            (_, ColorDepth::Basic) => self.render_mode.encode_basic(&self.compose_frame(view_width, view_height)),
+        };
+        if view_height < screen_height {
+            frame.push_str(&self.status_bar(screen_width));
+        }
+        if self.fps_counter && self.photo_mode.is_none() {
+            let counter = format!("{:.1} ms | {:.0} fps", self.render_time * 1000.0, self.fps);
+            push_top_right_label(&mut frame, screen_width, screen_height, &counter);
+        }
+        let label = self.photo_mode.is_none().then(|| self.label());
+        self.present(stdout, &frame, screen_height, label.as_deref())?;
+        self.update_frame_stats(started.elapsed());
+        Ok(())
+    }
+
+    // Size in character cells of the part of a screen the 3D view is drawn
+    // in; the rest is chrome such as the status bar
+    pub fn viewport(&self, screen_width: usize, screen_height: usize) -> (usize, usize) {
+        let hud = self.hud && self.photo_mode.is_none() && screen_height > 1;
+        (screen_width, screen_height - usize::from(hud))
+    }
+
+    // The status bar row: position, facing, frame rate and health, in
+    // reverse video across the full width
+    fn status_bar(&self, screen_width: usize) -> String {
+        let degrees = self.player.angle.to_degrees().rem_euclid(360.0);
+        let text = format!(
+            " x {:.1}  y {:.1}  facing {:.0}° {}  {:.0} fps  health {:.0}/{:.0}",
+            self.player.x,
+            self.player.y,
+            degrees,
+            compass_point(degrees),
+            self.fps,
+            self.player.health.max(0.0),
+            self.max_health,
+        );
+        let text: String = text.chars().chain(std::iter::repeat(' ')).take(screen_width).collect();
+        format!("\r\n\x1b[0m\x1b[7m{}\x1b[0m", text)
+    }
+
+    // Frames presented per second, eased so it can be read
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    // Ease the frame rate and render time towards those of the frame just
+    // presented, which took `render_time` to render and write out
+    fn update_frame_stats(&mut self, render_time: Duration) {
+        let now = Instant::now();
+        if let Some(last) = self.last_presented {
+            let elapsed = now.duration_since(last).as_secs_f64();
+            if elapsed > 0.0 {
+                // Exponential easing keeps the rate independent of the frame rate
+                let blend = 1.0 - (-elapsed / FPS_EASE).exp();
+                self.fps += (1.0 / elapsed - self.fps) * blend;
+                self.render_time += (render_time.as_secs_f64() - self.render_time) * blend;
+            }
+        } else {
+            self.render_time = render_time.as_secs_f64();
+        }
+        self.last_presented = Some(now);
+    }
+
+    // Render the view as character cells for a `columns` x `rows` area,
+    // for embedding in another TUI (e.g. a ratatui widget) rather than
+    // owning the terminal. The map name label is left to the caller.
+    // Bitmap render modes are drawn as half-blocks.
+    pub fn render_cells(&mut self, columns: usize, rows: usize) -> Vec<Vec<Cell>> {
+        if self.render_mode.is_bitmap() {
+            let bitmap = std::mem::replace(&mut self.render_mode, RenderMode::HalfBlock);
+            let cells = self.render_cells(columns, rows);
+            self.render_mode = bitmap;
+            return cells;
+        }
+        if self.render_mode == RenderMode::Ascii {
+            return render::text_cells(&self.compose_frame(columns, rows));
+        }
+        let mut frame_buffer: Vec<Vec<u8>> = self.compose_frame(columns, rows);
+        if self.color_depth == ColorDepth::Basic {
+            for pixel in frame_buffer.iter_mut().flatten() {
+                *pixel = palette::to_basic(*pixel);
+            }
+        }
+        self.render_mode.cells(&frame_buffer)
+    }
+
+    // Render the view into an area of a ratatui buffer, as `render_cells`
+    // does for the area's size. Needs the `ratatui` feature.
+    #[cfg(feature = "ratatui")]
+    pub fn render_into(&mut self, area: ratatui::layout::Rect, buffer: &mut ratatui::buffer::Buffer) {
+        let cells = self.render_cells(area.width as usize, area.height as usize);
+        render::write_cells(&cells, area, buffer);
+    }
+
+    // Build the frame buffer for a screen of the given size in character
+    // cells, at the render mode's resolution and with the output transforms
+    // applied
+    fn compose_frame<P: Pixel>(&mut self, screen_width: usize, screen_height: usize) -> Vec<Vec<P>> {
+        let pixel_width = self.render_mode.pixel_columns(screen_width);
+        let pixel_height = self.render_mode.pixel_rows(screen_height);
+        let mut frame_buffer = match self.fixed_resolution {
+            None if self.split.is_some() => self.render_split(pixel_width, pixel_height),
+            Some((width, height)) => {
+                let image = P::render(self, width, height);
+                self.letterbox(&image, pixel_width, pixel_height)
+            }
+            None if self.resolution_scale < 1.0 => {
+                let width = ((pixel_width as f64 * self.resolution_scale) as usize).max(1);
+                let height = ((pixel_height as f64 * self.resolution_scale) as usize).max(1);
+                let image = P::render(self, width, height);
+                self.view_columns = (0, pixel_width);
+                stretch(&image, pixel_width, pixel_height)
+            }
+            None => {
+                self.view_columns = (0, pixel_width);
+                P::render(self, pixel_width, pixel_height)
+            }
+        };
+        
+        if self.mirror {
+            frame_buffer.iter_mut().for_each(|row| row.reverse());
+        }
+        if self.flip {
+            frame_buffer.reverse();
+        }
+        // Overlays go on after the transforms so they read the right way round
+        if self.minimap && self.photo_mode.is_none() {
+            self.draw_minimap(&mut frame_buffer);
+        }
+        if self.dimmed {
+            for pixel in frame_buffer.iter_mut().flatten() {
+                *pixel = pixel.scale(DIM_FACTOR);
+            }
+        }
+        frame_buffer
+    }
+
+    // Draw the map editor's top-down view instead of the 3D view
+    pub fn render_editor(&mut self, stdout: &mut io::Stdout, editor: &Editor) -> io::Result<()> {
+        let (screen_width, screen_height) = self.screen_size(stdout)?;
+        let size = (self.render_mode.pixel_columns(screen_width), self.render_mode.pixel_rows(screen_height));
+        let frame_buffer = editor.frame(self, size.0, size.1);
+        let frame = self.render_mode.encode(&frame_buffer);
+        let mut label = format!("Editor - material {}", editor.material as char);
+        if let Some(status) = &editor.status {
+            label.push_str(&format!(" - {}", status));
+        }
+        self.present(stdout, &frame, screen_height, Some(&label))
+    }
+
+    // Draw the full-screen automap instead of the 3D view
+    pub fn render_automap(&mut self, stdout: &mut io::Stdout, automap: &Automap) -> io::Result<()> {
+        let (screen_width, screen_height) = self.screen_size(stdout)?;
+        let size = (self.render_mode.pixel_columns(screen_width), self.render_mode.pixel_rows(screen_height));
+        let frame_buffer = automap.frame(self, size.0, size.1);
+        let frame = self.render_mode.encode(&frame_buffer);
+        let label = format!("Automap - zoom {:.1}x", automap.zoom);
+        self.present(stdout, &frame, screen_height, Some(&label))
+    }
+
+    // Terminal size in characters. Clears the screen if the size changed
+    // (handles terminal resize); inline mode never clears so earlier
+    // scrollback is left untouched.
+    fn screen_size(&mut self, stdout: &mut io::Stdout) -> io::Result<(usize, usize)> {
+        let (screen_width, screen_height) = size()?;
+        let screen_width = screen_width as usize;
+        let screen_height = screen_height as usize;
+        
+        if screen_width != self.last_width || screen_height != self.last_height {
+            if !self.inline {
+                execute!(stdout, Clear(ClearType::All))?;
+            }
+            self.last_width = screen_width;
+            self.last_height = screen_height;
+        }
+        Ok((screen_width, screen_height))
+    }
+
+    // Write an encoded frame to the terminal, with an optional label over its
+    // top-left corner
+    fn present(
+        &mut self,
+        stdout: &mut io::Stdout,
+        frame: &str,
+        screen_height: usize,
+        label: Option<&str>,
+    ) -> io::Result<()> {
+        let mut output = String::new();
+        if !self.inline {
+            output.push_str("\x1b[H"); // Move cursor to home (0,0) without clearing
+        } else if self.inline_rows > 1 {
+            // Move back up to the first row of the previous frame
+            output.push_str(&format!("\r\x1b[{}A", self.inline_rows - 1));
+        } else {
+            output.push('\r');
+        }
+        self.inline_rows = screen_height;
+        output.push_str(frame);
+        if let Some(label) = label {
+            push_top_left_label(&mut output, screen_height, label);
+        }
+        
+        // Write everything at once
+        write!(stdout, "{}", output)?;
+        stdout.flush()?;
+        
+        Ok(())
+    }
+    
+    // Render the scene as RGB colors with the given size in pixels. Walls,
+    // floors and ceilings keep the exact colors they were shaded with, where
+    // `render_frame` matches them to the palette; anything drawn over them
+    // takes its palette color.
+    pub fn render_frame_rgb(&mut self, screen_width: usize, double_height: usize) -> Vec<Vec<Rgb>> {
+        self.render_recorded(screen_width, double_height, |_, drawn, code| match drawn {
+            Some(drawn) => drawn.color,
+            None => palette::to_rgb(code),
+        })
+    }
+
+    // Render the scene as ASCII characters with the given size in pixels.
+    // Walls and sprites are drawn from the dense end of the ramp for nearby
+    // ones to the sparse end far away, the floor in dashes and dots and the
+    // ceiling left blank; anything else is drawn by its brightness.
+    pub fn render_frame_ascii(&mut self, screen_width: usize, double_height: usize) -> Vec<Vec<char>> {
+        self.render_recorded(screen_width, double_height, |raycaster, drawn, code| match drawn {
+            Some(drawn) => raycaster.ascii_char(drawn),
+            None => render::ascii_shade(palette::luma(palette::to_rgb(code))),
+        })
+    }
+
+    // Render a frame while recording its pixels, then turn each into an
+    // output pixel. Pixels drawn over since they were recorded are passed
+    // as None with their palette color.
+    fn render_recorded<P>(
+        &mut self,
+        screen_width: usize,
+        double_height: usize,
+        pixel: impl Fn(&Self, Option<DrawnPixel>, u8) -> P,
+    ) -> Vec<Vec<P>> {
+        self.drawn_pixels = Some(Vec::new());
+        let frame_buffer = self.render_frame(screen_width, double_height);
+        let drawn_pixels = self.drawn_pixels.take().unwrap_or_default();
+        frame_buffer
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, &code)| {
+                        let drawn = drawn_pixels.get(y).and_then(|row| row.get(x)).copied().flatten();
+                        pixel(self, drawn.filter(|drawn| drawn.code == code), code)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Render the scene into a frame buffer of 256-color codes with the given
+    // size in pixels
+    pub fn render_frame(&mut self, screen_width: usize, double_height: usize) -> Vec<Vec<u8>> {
+        if self.column_angles.len() != screen_width || self.column_fov != self.fov {
+            self.update_column_angles(screen_width);
+        }
+        
+        if self.hit_flash.as_ref().is_some_and(|flash| Instant::now() >= flash.until) {
+            self.hit_flash = None;
+        }
+        
+        // The free camera and photo mode glide without bobbing
+        self.horizon_shift = if self.photo_mode.is_some() || self.spectating.is_some() {
+            0
+        } else {
+            (self.walk.bob() * self.head_bob * double_height as f64).round() as i32
+        };
+        
+        let mut frame_buffer = vec![vec![0u8; screen_width]; double_height];
+        // Pixels are only recorded when `render_recorded` asks
+        let mut drawn_pixels: Option<DrawnPixels> = self
+            .drawn_pixels
+            .take()
+            .map(|_| vec![vec![None; screen_width]; double_height]);
+        // Perpendicular wall distance per column, used to hide sprites behind walls
+        let mut depth_buffer = vec![0.0; screen_width];
+        
+        // Calculate all columns
+        for x in 0..screen_width {
+            let ray_angle = self.player.angle + self.column_angles[x];
+            
+            let (ray, path) = self.cast_view_ray(ray_angle);
+            let perp_wall_dist = ray.distance;
+            // Sprites are hidden by a mirror, not drawn into its reflection
+            depth_buffer[x] = path.first_bounce().unwrap_or(perp_wall_dist);
+            
+            // Use double height for calculations
+            let line_height = (double_height as f64 / perp_wall_dist.max(0.1)) as usize;
+            // Raised ceilings extend the wall upwards while its base stays on the floor
+            let ceiling_height = self.get_ceiling_height(ray.map_x, ray.map_y);
+            let extra_height = (line_height as f64 * (ceiling_height - 1.0)) as i32;
+            let wall_top = (double_height as i32 - line_height as i32) / 2 - extra_height + self.horizon_shift;
+            let draw_start = wall_top.clamp(0, double_height as i32);
+            let draw_end = ((double_height as i32 + line_height as i32) / 2 + self.horizon_shift).clamp(draw_start, double_height as i32);
+            
+            // Get 256-color code for wall based on distance
+            let flashed = self
+                .hit_flash
+                .as_ref()
+                .is_some_and(|flash| flash.map_x == ray.map_x && flash.map_y == ray.map_y);
+            let lighting = self.lighting(ray.normal) * self.light_in_front(&path, perp_wall_dist, ray.normal);
+            // With dithering, pixels mix the two nearest ramp colors in
+            // proportion; otherwise the whole wall takes the nearest one
+            let ramp_position = self.wall_ramp_position(perp_wall_dist);
+            let (ramp_index, ramp_fraction) = if self.dither {
+                (ramp_position.floor() as usize, ramp_position.fract())
+            } else {
+                (ramp_position.round() as usize, 0.0)
+            };
+            let cracked = self
+                .destructibles
+                .get(&(ray.map_x, ray.map_y))
+                .is_some_and(DestructibleWall::is_cracked);
+            let base_color = if cracked { Some(CRACKED_COLOR) } else { self.base_color(ray.material) };
+            let ramp_color = |index: usize| {
+                if flashed {
+                    HIT_FLASH_COLOR
+                } else {
+                    self.ramp_color(base_color, index, lighting)
+                }
+            };
+            let wall_color = ramp_color(ramp_index);
+            let image_texture = self.image_texture(ray.material).filter(|_| self.textures && !flashed && !cracked);
+            let texture = WallTexture::for_material(ray.material).filter(|_| self.textures && !flashed && !cracked);
+            let brightness = distance_brightness(perp_wall_dist) * lighting;
+            // Shaded variants of the wall color, indexed by the texture's shade level
+            let wall_shades = SHADES.map(|factor| palette::scale(wall_color, factor));
+            let (next_wall_color, next_wall_shades) = if ramp_fraction > 0.0 {
+                let color = ramp_color(ramp_index + 1);
+                (color, SHADES.map(|factor| palette::scale(color, factor)))
+            } else {
+                (wall_color, wall_shades)
+            };
+            // Truecolor frames blend the ramp steps either side exactly
+            let exact_wall_color = if flashed {
+                palette::to_rgb(HIT_FLASH_COLOR)
+            } else {
+                self.ramp_rgb(base_color, ramp_position, lighting)
+            };
+            
+            // Fill the column as three runs: ceiling, wall, then floor
+            let (wall_start, wall_end) = (draw_start as usize, draw_end as usize);
+            for (y, row) in frame_buffer[..wall_start].iter_mut().enumerate() {
+                // Ceiling - darker gradient based on distance from center
+                let dist_from_center = (wall_start - y) as f64 / double_height as f64;
+                let point = self.floor_point(&path, y, double_height);
+                let sector = point.and_then(|(px, py)| self.sector_at(px, py));
+                let distance = self.row_distance(y, double_height);
+                let color = self.ceiling_rgb(point, sector, dist_from_center, distance);
+                row[x] = self.plane_code(color, x, y);
+                if let Some(drawn_pixels) = &mut drawn_pixels {
+                    drawn_pixels[y][x] = Some(DrawnPixel {
+                        code: row[x],
+                        color,
+                        surface: Surface::Ceiling,
+                        distance,
+                    });
+                }
+            }
+            if ray.hit {
+                for (y, row) in frame_buffer[wall_start..wall_end].iter_mut().enumerate() {
+                    let wall_v = ((wall_start + y) as i32 - wall_top) as f64 / line_height.max(1) as f64;
+                    let (wall_color, wall_shades) = if self.dither_threshold(x, wall_start + y) < ramp_fraction {
+                        (next_wall_color, &next_wall_shades)
+                    } else {
+                        (wall_color, &wall_shades)
+                    };
+                    row[x] = match (image_texture, texture) {
+                        (Some(image), _) => {
+                            let (r, g, b) = image.sample(ray.wall_x, wall_v);
+                            let shade = |c: u8| (c as f64 * brightness) as u8;
+                            palette::from_rgb(shade(r), shade(g), shade(b))
+                        }
+                        (None, Some(texture)) => wall_shades[texture.sample(ray.wall_x, wall_v)],
+                        (None, None) => wall_color,
+                    };
+                    row[x] = self.fogged(row[x], perp_wall_dist);
+                    if let Some(drawn_pixels) = &mut drawn_pixels {
+                        let color = match (image_texture, texture) {
+                            (Some(image), _) => {
+                                let (r, g, b) = image.sample(ray.wall_x, wall_v);
+                                let shade = |c: u8| (c as f64 * brightness) as u8;
+                                (shade(r), shade(g), shade(b))
+                            }
+                            (None, Some(texture)) => {
+                                palette::scale_rgb(exact_wall_color, SHADES[texture.sample(ray.wall_x, wall_v)])
+                            }
+                            (None, None) => exact_wall_color,
+                        };
+                        drawn_pixels[wall_start + y][x] = Some(DrawnPixel {
+                            code: row[x],
+                            color: self.fogged_rgb(color, perp_wall_dist),
+                            surface: Surface::Wall,
+                            distance: perp_wall_dist,
+                        });
+                    }
+                }
+            } else {
+                for row in &mut frame_buffer[wall_start..wall_end] {
+                    row[x] = self.background_color;
+                }
+            }
+            for (y, row) in frame_buffer[wall_end..].iter_mut().enumerate() {
+                // Floor - darker gradient based on distance from center
+                let dist_from_center = y as f64 / double_height as f64;
+                let point = self.floor_point(&path, wall_end + y, double_height);
+                // Hazards glow in their own shimmering colors whatever the
+                // light
+                let hazard = point.and_then(|(px, py)| self.hazard_at(px, py));
+                let distance = self.row_distance(wall_end + y, double_height);
+                let sector_color = point.and_then(|(px, py)| self.sector_at(px, py)).map(|colors| colors.floor);
+                let color = self.floor_rgb(point, hazard, sector_color, dist_from_center, distance);
+                row[x] = self.plane_code(color, x, wall_end + y);
+                if let Some(drawn_pixels) = &mut drawn_pixels {
+                    drawn_pixels[wall_end + y][x] = Some(DrawnPixel {
+                        code: row[x],
+                        color,
+                        surface: Surface::Floor,
+                        distance,
+                    });
+                }
+            }
+            
+            if self.ramps {
+                self.draw_floor_heights(&mut frame_buffer, x, &path, perp_wall_dist);
+            }
+            if self.floor_grid {
+                self.draw_floor_grid(&mut frame_buffer, x, &path, draw_end.max(0) as usize);
+            }
+            // See-through cells and short walls each cover what is behind
+            // them, so they are drawn together, farthest first
+            let mut translucent = ray.translucent.iter().rev().peekable();
+            for wall in ray.short_walls.iter().rev() {
+                while let Some(layer) = translucent.next_if(|layer| layer.distance > wall.distance) {
+                    self.draw_translucent(&mut frame_buffer, x, &path, layer);
+                }
+                self.draw_short_wall(&mut frame_buffer, x, &path, wall);
+            }
+            for layer in translucent {
+                self.draw_translucent(&mut frame_buffer, x, &path, layer);
+            }
+        }
+        
+        self.draw_sprites(&mut frame_buffer, &depth_buffer, drawn_pixels.as_mut());
+        if let Some((dx, dy)) = self.shake_offset(double_height) {
+            shift(&mut frame_buffer, dx, dy);
+            if let Some(drawn_pixels) = &mut drawn_pixels {
+                shift(drawn_pixels, dx, dy);
+            }
+        }
+        self.drawn_pixels = drawn_pixels;
+        if self.vignette {
+            self.update_vignette(&depth_buffer);
+            draw_vignette(&mut frame_buffer, self.vignette_strength);
+        } else {
+            // Start from nothing and ease in again when next turned on
+            self.vignette_strength = 0.0;
+            self.vignette_updated = None;
+        }
+        if self.photo_mode.is_some() {
+            return frame_buffer;
+        }
+        if self.weapon && self.spectating.is_none() {
+            viewmodel::overlay(&self.walk, screen_width, double_height).composite(&mut frame_buffer);
+        }
+        for (color, strength) in self.effects.active(Instant::now()) {
+            flash(&mut frame_buffer, color, strength);
+        }
+        self.draw_health_bar(&mut frame_buffer);
+        self.draw_quality_level(&mut frame_buffer);
+        if self.measuring {
+            draw_crosshair(&mut frame_buffer);
+        }
+        frame_buffer
+    }
+
+    // Color set for a wall material, by the map or by default, if it has one;
+    // materials without one are drawn with the gray ramp
+    fn base_color(&self, material: u8) -> Option<u8> {
+        self.wall_colors.get(&material).copied().or_else(|| {
+            let index = material.checked_sub(1)?;
+            MATERIAL_COLORS.get(index as usize).copied().flatten()
+        })
+    }
+
+    // Wall color at a step of the distance ramp, lit by the given factor
+    fn ramp_color(&self, base_color: Option<u8>, index: usize, lighting: f64) -> u8 {
+        let index = index.min(WALL_RAMP.len() - 1);
+        match base_color {
+            None if self.color_depth == ColorDepth::Basic => palette::scale(BASIC_WALL_RAMP[index], lighting),
+            Some(base) => {
+                let fade = index as f64 / (WALL_RAMP.len() - 1) as f64;
+                palette::scale(base, lighting * (1.0 - (1.0 - FAR_WALL_BRIGHTNESS) * fade))
+            }
+            None => palette::scale(WALL_RAMP[index], lighting),
+        }
+    }
+
+    // Exact wall color at a fractional position along the distance ramp,
+    // blending the steps either side of it
+    fn ramp_rgb(&self, base_color: Option<u8>, position: f64, lighting: f64) -> Rgb {
+        let last = (WALL_RAMP.len() - 1) as f64;
+        let position = position.clamp(0.0, last);
+        match base_color {
+            Some(base) => {
+                let fade = position / last;
+                palette::scale_rgb(palette::to_rgb(base), lighting * (1.0 - (1.0 - FAR_WALL_BRIGHTNESS) * fade))
+            }
+            None => {
+                let near = palette::to_rgb(WALL_RAMP[position.floor() as usize]);
+                let far = palette::to_rgb(WALL_RAMP[position.ceil() as usize]);
+                palette::scale_rgb(palette::mix_rgb(near, far, position.fract()), lighting)
+            }
+        }
+    }
+
+    // Floor and ceiling colors of the sector holding a world point, if it is
+    // in one
+    fn hazard_at(&self, x: f64, y: f64) -> Option<Hazard> {
+        self.hazards.get(&(x.floor() as i32, y.floor() as i32)).copied()
+    }
+
+    fn sector_at(&self, x: f64, y: f64) -> Option<SectorColors> {
+        let letter = self.sectors.get(&(x.floor() as i32, y.floor() as i32))?;
+        self.sector_colors.get(letter).copied()
+    }
+
+    // Random offset in pixels to move this frame's view by while a shake is
+    // going, or None once it is over. Photo mode holds still.
+    fn shake_offset(&mut self, double_height: usize) -> Option<(i32, i32)> {
+        let now = Instant::now();
+        let shake = self.shake.as_ref()?;
+        if now.duration_since(shake.started) >= shake.duration {
+            self.shake = None;
+            return None;
+        }
+        if self.photo_mode.is_some() {
+            return None;
+        }
+        let reach = (shake.strength(now) * double_height as f64).round() as usize;
+        if reach == 0 {
+            return None;
+        }
+        let mut offset = || (self.shake_rng.below(reach * 2 + 1) as i32) - reach as i32;
+        Some((offset(), offset()))
+    }
+
+    // Screen row of eye level in a view the given number of pixels tall,
+    // lowered by the head-bob
+    fn horizon(&self, double_height: f64) -> f64 {
+        double_height / 2.0 + self.horizon_shift as f64
+    }
+
+    // Distance along the ray to the floor or ceiling seen at a screen row,
+    // which are as far below eye level as above it
+    fn row_distance(&self, y: usize, double_height: usize) -> f64 {
+        let from_horizon = (y as f64 + 0.5 - self.horizon(double_height as f64)).abs();
+        0.5 * double_height as f64 / from_horizon.max(0.5)
+    }
+
+    // World point on the floor or ceiling seen at a screen row of the column
+    // whose ray took a path, or None if there are no sectors, lights, hazards
+    // or textures that would need it. A row and its mirror across the
+    // horizon look at the same point.
+    fn floor_point(&self, path: &RayPath, y: usize, double_height: usize) -> Option<(f64, f64)> {
+        if self.sectors.is_empty()
+            && !self.has_lights()
+            && self.hazards.is_empty()
+            && self.plane_texture(false).is_none()
+            && self.plane_texture(true).is_none()
+        {
+            return None;
+        }
+        Some(path.point(self.row_distance(y, double_height)))
+    }
+
+    // Color of the ceiling or floor texture at a world point, darkening
+    // towards the horizon like sector colors, or None without a texture
+    fn plane_texel_rgb(&self, ceiling: bool, point: (f64, f64), dist_from_center: f64) -> Option<Rgb> {
+        Some(self.daylit_rgb(self.plane_sample(ceiling, point, dist_from_center)?))
+    }
+
+    // Texture color at a world point, shaded towards the horizon but not
+    // yet for the time of day
+    fn plane_sample(&self, ceiling: bool, (x, y): (f64, f64), dist_from_center: f64) -> Option<Rgb> {
+        let (r, g, b) = self.plane_texture(ceiling)?.sample(x, y);
+        let factor = horizon_brightness(dist_from_center);
+        let shade = |c: u8| (c as f64 * factor) as u8;
+        Some((shade(r), shade(g), shade(b)))
+    }
+
+    // A color seen at a distance through the map's fog. Without fog colors
+    // are returned exactly.
+    fn fogged(&self, color: u8, distance: f64) -> u8 {
+        let environment = self.environment.or_fog(&self.default_fog);
+        match environment.fog(distance) {
+            0.0 => color,
+            amount => blend(color, palette::to_rgb(environment.fog_color), amount),
+        }
+    }
+
+    fn fogged_rgb(&self, color: Rgb, distance: f64) -> Rgb {
+        let environment = self.environment.or_fog(&self.default_fog);
+        match environment.fog(distance) {
+            0.0 => color,
+            amount => palette::mix_rgb(color, palette::to_rgb(environment.fog_color), amount),
+        }
+    }
+
+    // Brightness factor at a world point: the light level of the cell
+    // holding it, brightened by any point lights near it
+    fn light_at(&self, x: f64, y: f64) -> f64 {
+        let level = self
+            .light_levels
+            .get(&(x.floor() as i32, y.floor() as i32))
+            .map_or(1.0, |&level| {
+                DARKEST_LIGHT + (1.0 - DARKEST_LIGHT) * level as f64 / MAX_LIGHT_LEVEL as f64
+            });
+        let added: f64 = self.lights().map(|light| light.brightness_at(x, y)).sum();
+        let light = if added > 0.0 {
+            (level + added).min(MAX_POINT_LIT_BRIGHTNESS)
+        } else {
+            level
+        };
+        if self.flashlight {
+            light * self.flashlight_at(x, y)
+        } else {
+            light
+        }
+    }
+
+    // Brightness factor from the flashlight at a world point: brightest in
+    // the middle of the cone, fading across its soft edge and with distance
+    // down to the darkness outside it
+    fn flashlight_at(&self, x: f64, y: f64) -> f64 {
+        let (dx, dy) = (x - self.player.x, y - self.player.y);
+        let distance = dx.hypot(dy);
+        // Right at the player the direction is meaningless; count it as lit
+        let off_axis = if distance < FLASHLIGHT_NEAR {
+            0.0
+        } else {
+            let angle = normalize_angle(dy.atan2(dx) - self.player.angle);
+            angle.min(2.0 * std::f64::consts::PI - angle)
+        };
+        let edge = ((FLASHLIGHT_HALF_ANGLE - off_axis) / FLASHLIGHT_EDGE + 0.5).clamp(0.0, 1.0);
+        let reach = (1.0 - distance / FLASHLIGHT_RANGE).clamp(0.0, 1.0);
+        FLASHLIGHT_DARKNESS + (FLASHLIGHT_BRIGHTNESS - FLASHLIGHT_DARKNESS) * edge * reach.sqrt()
+    }
+
+    // Point lights in the map and the one the player carries, if any, where
+    // they are this frame
+    fn lights(&self) -> impl Iterator<Item = PointLight> + '_ {
+        let carried = self.carried_light.map(|light| PointLight {
+            x: self.player.x,
+            y: self.player.y,
+            ..light
+        });
+        self.point_lights.iter().copied().chain(carried)
+    }
+
+    // Whether anything lights the map unevenly, so surfaces have to be lit
+    // by where they are
+    fn has_lights(&self) -> bool {
+        !self.light_levels.is_empty() || !self.point_lights.is_empty() || self.carried_light.is_some() || self.flashlight
+    }
+
+    // Brightness factor for a wall face from the light in front of it,
+    // where a ray taking a path struck it at distance
+    fn light_in_front(&self, path: &RayPath, distance: f64, normal: (f64, f64)) -> f64 {
+        if !self.has_lights() {
+            return 1.0;
+        }
+        // Step just off the face, back towards the viewer
+        let (x, y) = path.point(distance);
+        self.light_at(x + normal.0 * 0.01, y + normal.1 * 0.01)
+    }
+
+    // A color dimmed by the light level of the cell holding a world point.
+    // Fully lit cells keep their colors exactly.
+    fn lit(&self, color: u8, x: f64, y: f64) -> u8 {
+        match self.light_at(x, y) {
+            1.0 => color,
+            light => palette::scale(color, light),
+        }
+    }
+
+    fn lit_rgb(&self, color: Rgb, x: f64, y: f64) -> Rgb {
+        match self.light_at(x, y) {
+            1.0 => color,
+            light => palette::scale_rgb(color, light),
+        }
+    }
+
+    // Lambertian brightness factor for a wall face with the given normal.
+    // North and south faces (those with `side` set on the ray that hit
+    // them) are also darker than east and west ones, so the two walls of a
+    // corner never come out the same shade; diagonals fall in between.
+    fn lighting(&self, normal: (f64, f64)) -> f64 {
+        let Some((light_x, light_y)) = self.light_direction else {
+            return self.daylight();
+        };
+        // Faces are lit when their normal points back against the light
+        let facing = -(normal.0 * light_x + normal.1 * light_y);
+        let side = 1.0 - (1.0 - NORTH_SOUTH_BRIGHTNESS) * normal.1 * normal.1;
+        self.daylight() * side * (AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * facing.max(0.0))
+    }
+
+    // Overall light level from the time of day and the map's ambient light
+    fn daylight(&self) -> f64 {
+        self.day_cycle.as_ref().map_or(1.0, DayCycle::light) * self.environment.ambient
+    }
+
+    // A color as lit at the current time of day and by the map's ambient
+    // light. Without a day cycle or a change to the ambient light colors are
+    // returned exactly, skipping the round trip through RGB.
+    fn daylit(&self, color: u8) -> u8 {
+        match (&self.day_cycle, self.environment.ambient) {
+            (None, 1.0) => color,
+            _ => palette::scale(color, self.daylight()),
+        }
+    }
+
+    fn daylit_rgb(&self, color: Rgb) -> Rgb {
+        match (&self.day_cycle, self.environment.ambient) {
+            (None, 1.0) => color,
+            _ => palette::scale_rgb(color, self.daylight()),
+        }
+    }
+
+    // Health bar along the bottom-left of the view, a quarter of its width
+    fn draw_health_bar(&self, frame_buffer: &mut [Vec<u8>]) {
+        let width = frame_buffer.first().map_or(0, |row| row.len());
+        let height = frame_buffer.len();
+        if width < 8 || height < 6 {
+            return;
+        }
+        
+        let bar_width = width / 4;
+        let fraction = (self.player.health / self.max_health.max(1.0)).clamp(0.0, 1.0);
+        let filled = (bar_width as f64 * fraction).ceil() as usize;
+        
+        for row in &mut frame_buffer[height - 3..height - 1] {
+            for (i, pixel) in row[1..=bar_width].iter_mut().enumerate() {
+                *pixel = if i < filled { HEALTH_BAR_COLOR } else { HEALTH_BAR_EMPTY_COLOR };
+            }
+        }
+    }
+
+    // Minimap in the top-right corner of the view, under the frame counter:
+    // the cells around the player, north up and centered on them, with a
+    // line the way they face
+    fn draw_minimap<P: Pixel>(&self, frame_buffer: &mut [Vec<P>]) {
+        let width = frame_buffer.first().map_or(0, |row| row.len());
+        let height = frame_buffer.len();
+        let size = (width.min(height) as f64 * MINIMAP_SIZE) as usize;
+        if size < (MINIMAP_RADIUS * 2.0) as usize + 2 {
+            return;
+        }
+        let scale = size as f64 / (MINIMAP_RADIUS * 2.0); // Pixels per cell
+        // Below the top row of text, where the frame counter goes
+        let top = self.render_mode.pixel_rows(1).max(1);
+        if top + size >= height {
+            return;
+        }
+        let left = width - size - 1;
+        let center = size as f64 / 2.0;
+        
+        for (y, row) in frame_buffer[top..top + size].iter_mut().enumerate() {
+            for (x, pixel) in row[left..left + size].iter_mut().enumerate() {
+                let color = if x == 0 || y == 0 || x == size - 1 || y == size - 1 {
+                    MINIMAP_BORDER_COLOR
+                } else {
+                    let world_x = self.player.x + (x as f64 + 0.5 - center) / scale;
+                    let world_y = self.player.y + (y as f64 + 0.5 - center) / scale;
+                    self.minimap_color(world_x, world_y)
+                };
+                *pixel = P::from_code(color);
+            }
+        }
+        
+        let (sin, cos) = self.player.angle.sin_cos();
+        let steps = (MINIMAP_FACING_LENGTH * scale).ceil() as usize;
+        for step in 1..=steps {
+            let x = (center + cos * step as f64) as usize;
+            let y = (center + sin * step as f64) as usize;
+            if x > 0 && y > 0 && x < size - 1 && y < size - 1 {
+                frame_buffer[top + y][left + x] = P::from_code(MINIMAP_FACING_COLOR);
+            }
+        }
+        frame_buffer[top + center as usize][left + center as usize] = P::from_code(MINIMAP_PLAYER_COLOR);
+    }
+
+    // Color of the map cell under a world point on the minimap
+    fn minimap_color(&self, x: f64, y: f64) -> u8 {
+        if x < 0.0 || y < 0.0 {
+            return MINIMAP_OUTSIDE_COLOR;
+        }
+        self.map.get(x as usize, y as usize).map_or(MINIMAP_OUTSIDE_COLOR, editor::cell_color)
+    }
+
+    // One 2x2 pip per adaptive quality level in the bottom-right corner, lit
+    // for the levels of quality currently kept
+    fn draw_quality_level(&self, frame_buffer: &mut [Vec<u8>]) {
+        let Some(level) = self.quality_level else {
+            return;
+        };
+        let width = frame_buffer.first().map_or(0, |row| row.len());
+        let height = frame_buffer.len();
+        let pips = QUALITY_LEVELS.len();
+        if width < pips * 3 + 1 || height < 6 {
+            return;
+        }
+        
+        for row in &mut frame_buffer[height - 3..height - 1] {
+            for pip in 0..pips {
+                let x = width - (pips - pip) * 3;
+                let color = if pip < pips - level { QUALITY_PIP_COLOR } else { QUALITY_PIP_EMPTY_COLOR };
+                row[x..x + 2].fill(color);
+            }
+        }
+    }
+
+    // Redraw the floor of one column where it is raised. Marches along the ray
+    // from near to far, projecting the floor height at each step; anything
+    // already covered by nearer floor is hidden, like a heightfield renderer.
+    // Flat floor is left as drawn by the main column pass.
+    fn draw_floor_heights(&self, frame_buffer: &mut [Vec<u8>], x: usize, path: &RayPath, wall_distance: f64) {
+        let double_height = frame_buffer.len() as f64;
+        let horizon = self.horizon(double_height);
+        
+        // Lowest screen row not yet covered by nearer floor
+        let mut covered_from = frame_buffer.len();
+        let mut distance = 0.3;
+        while distance < wall_distance && covered_from > 0 {
+            let (world_x, world_y) = path.point(distance);
+            let height = self.get_floor_height(world_x, world_y);
+            let screen_y = (horizon + double_height / distance * (0.5 - height)).max(0.0) as usize;
+            
+            if screen_y < covered_from {
+                if height > 0.0 {
+                    // Raised floor is brighter the higher it is
+                    let dist_from_center = (screen_y as f64 - horizon).max(0.0) / double_height;
+                    let sector_color = self.sector_at(world_x, world_y).map(|colors| colors.floor);
+                    let (r, g, b) = self.floor_rgb(Some((world_x, world_y)), None, sector_color, dist_from_center, 0.0);
+                    let base = palette::from_rgb(r, g, b);
+                    let color = self.fogged(base.saturating_add((height / PLATFORM_HEIGHT * 4.0) as u8), distance);
+                    for row in &mut frame_buffer[screen_y..covered_from] {
+                        row[x] = color;
+                    }
+                }
+                covered_from = screen_y;
+            }
+            
+            // Finer steps up close where each step covers more rows
+            distance += 0.02 + distance * 0.02;
+        }
+    }
+
+    // Render the player's view on the left and the split view on the right,
+    // separated by a one-column divider in the letterbox color
+    fn render_split<P: Pixel>(&mut self, screen_width: usize, double_height: usize) -> Vec<Vec<P>> {
+        let left_width = screen_width.saturating_sub(1) / 2;
+        let right_width = screen_width.saturating_sub(left_width + 1);
+        let left = P::render(self, left_width, double_height);
+        let Some(split) = self.split.take() else {
+            return left;
+        };
+        
+        // Temporarily swap in the split view's camera and settings
+        let camera = split.camera.clone().unwrap_or_else(|| self.player.clone());
+        let player = std::mem::replace(&mut self.player, camera);
+        let fov = std::mem::replace(&mut self.fov, split.fov);
+        let right = P::render(self, right_width, double_height);
+        self.player = player;
+        self.fov = fov;
+        self.split = Some(split);
+        
+        // Leave the column table matching the left view, which receives clicks
+        self.update_column_angles(left_width);
+        self.view_columns = (0, left_width);
+        
+        left.into_iter()
+            .zip(right)
+            .map(|(mut row, right_row)| {
+                row.push(P::from_code(self.letterbox_color));
+                row.extend(right_row);
+                row
+            })
+            .collect()
+    }
+
+    // Ease the vignette strength towards how boxed in the player is, judged
+    // by the average wall distance across the middle third of the view
+    fn update_vignette(&mut self, depth_buffer: &[f64]) {
+        let now = Instant::now();
+        let elapsed = self
+            .vignette_updated
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+        self.vignette_updated = Some(now);
+        
+        let center = &depth_buffer[depth_buffer.len() / 3..depth_buffer.len() * 2 / 3];
+        if center.is_empty() {
+            return;
+        }
+        let average = center.iter().sum::<f64>() / center.len() as f64;
+        let target = ((VIGNETTE_START - average) / (VIGNETTE_START - VIGNETTE_FULL)).clamp(0.0, 1.0);
+        // Exponential easing keeps the rate independent of the frame rate
+        let blend = 1.0 - (-elapsed / VIGNETTE_EASE).exp();
+        self.vignette_strength += (target - self.vignette_strength) * blend;
+    }
+
+    // Blend a see-through cell a column's ray passed through over what was
+    // drawn behind it, or for a window draw its bars over it
+    fn draw_translucent(&self, frame_buffer: &mut [Vec<u8>], x: usize, path: &RayPath, layer: &TranslucentHit) {
+        let double_height = frame_buffer.len() as i32;
+        let line_height = (double_height as f64 / layer.distance.max(0.1)) as i32;
+        let ceiling_height = self.get_ceiling_height(layer.map_x, layer.map_y);
+        let extra_height = (line_height as f64 * (ceiling_height - 1.0)) as i32;
+        let face_top = (double_height - line_height) / 2 - extra_height + self.horizon_shift;
+        let top = face_top.clamp(0, double_height);
+        let bottom = ((double_height + line_height) / 2 + self.horizon_shift).clamp(0, double_height);
+        
+        if self.map.get(layer.map_x as usize, layer.map_y as usize) == Some(WINDOW_CELL) {
+            // The gaps between the bars are left as they were
+            let lighting = self.lighting(layer.normal) * self.light_in_front(path, layer.distance, layer.normal);
+            let ramp_index = self.wall_ramp_position(layer.distance).round() as usize;
+            let color = self.fogged(self.ramp_color(self.base_color(layer.material), ramp_index, lighting), layer.distance);
+            let face_height = (line_height + extra_height).max(1) as f64;
+            for y in top.min(bottom)..bottom {
+                let wall_v = (y - face_top) as f64 / face_height;
+                if window::is_bar(layer.wall_x, wall_v) {
+                    frame_buffer[y as usize][x] = color;
+                }
+            }
+            return;
+        }
+        let Some(translucency) = Translucency::for_material(layer.material) else {
+            return;
+        };
+        let tint = palette::to_rgb(palette::scale(translucency.tint, distance_brightness(layer.distance)));
+        for row in &mut frame_buffer[top.min(bottom) as usize..bottom as usize] {
+            row[x] = blend(row[x], tint, translucency.opacity);
+        }
+    }
+
+    // Draw a wall shorter than full height that a column's ray passed over:
+    // its face from the floor up to its height and, while the eye is above
+    // it, its top reaching back to where the ray left the cell
+    fn draw_short_wall(&self, frame_buffer: &mut [Vec<u8>], x: usize, path: &RayPath, wall: &ShortWallHit) {
+        let double_height = frame_buffer.len() as f64;
+        let horizon = self.horizon(double_height);
+        // Screen row of a point at a height above the floor, the eye being
+        // at half the full wall height
+        let row_at = |distance: f64, height: f64| horizon + (0.5 - height) * double_height / distance.max(0.1);
+        let to_row = |row: f64| row.clamp(0.0, double_height) as usize;
+        
+        let lighting = self.lighting(wall.normal) * self.light_in_front(path, wall.distance, wall.normal);
+        let ramp_index = self.wall_ramp_position(wall.distance).round() as usize;
+        let color = self.ramp_color(self.base_color(wall.material), ramp_index, lighting);
+        let (face_top, face_bottom) = (row_at(wall.distance, wall.height), row_at(wall.distance, 0.0));
+        let (top, bottom) = (to_row(face_top), to_row(face_bottom));
+        if wall.height < 0.5 {
+            let top_color = self.fogged(palette::scale(color, WALL_TOP_BRIGHTNESS), wall.distance);
+            for row in &mut frame_buffer[to_row(row_at(wall.exit, wall.height)).min(top)..top] {
+                row[x] = top_color;
+            }
+        }
+        
+        // The face shows the bottom part of the texture, as if the wall were
+        // a full one cut down
+        let image_texture = self.image_texture(wall.material).filter(|_| self.textures);
+        let texture = WallTexture::for_material(wall.material).filter(|_| self.textures);
+        let brightness = distance_brightness(wall.distance) * lighting;
+        let shades = SHADES.map(|factor| palette::scale(color, factor));
+        for (y, row) in frame_buffer[top..bottom].iter_mut().enumerate() {
+            let along = ((top + y) as f64 - face_top) / (face_bottom - face_top).max(1.0);
+            let wall_v = 1.0 - wall.height * (1.0 - along);
+            row[x] = match (image_texture, texture) {
+                (Some(image), _) => {
+                    let (r, g, b) = image.sample(wall.wall_x, wall_v);
+                    let shade = |c: u8| (c as f64 * brightness) as u8;
+                    palette::from_rgb(shade(r), shade(g), shade(b))
+                }
+                (None, Some(texture)) => shades[texture.sample(wall.wall_x, wall_v)],
+                (None, None) => color,
+            };
+            row[x] = self.fogged(row[x], wall.distance);
+        }
+    }
+
+    // Faint lines on the floor along map cell boundaries, so positions in the
+    // view can be matched to map coordinates. Assumes a flat floor.
+    fn draw_floor_grid(&self, frame_buffer: &mut [Vec<u8>], x: usize, path: &RayPath, floor_start: usize) {
+        let double_height = frame_buffer.len() as f64;
+        let horizon = self.horizon(double_height);
+        
+        for (y, row) in frame_buffer.iter_mut().enumerate().skip(floor_start) {
+            let below_horizon = y as f64 + 0.5 - horizon;
+            if below_horizon <= 0.0 {
+                continue;
+            }
+            // Inverse of the wall projection: the floor at this row is where a
+            // wall base at this distance would appear
+            let distance = 0.5 * double_height / below_horizon;
+            let (world_x, world_y) = path.point(distance);
+            
+            // Lines widen with distance so far ones don't break up
+            let width = GRID_LINE_WIDTH * distance.max(1.0);
+            let on_line = |coordinate: f64| (coordinate - coordinate.round()).abs() < width;
+            if on_line(world_x) || on_line(world_y) {
+                row[x] = palette::scale(row[x], GRID_LINE_BRIGHTNESS);
+            }
+        }
+    }
+
+    // Scale a fixed-resolution image to fit the screen (nearest neighbour),
+    // preserving its aspect ratio and filling the margins with the letterbox color
+    fn letterbox<P: Pixel>(&mut self, image: &[Vec<P>], screen_width: usize, double_height: usize) -> Vec<Vec<P>> {
+        let image_height = image.len();
+        let image_width = image.first().map_or(0, Vec::len);
+        let mut frame_buffer = vec![vec![P::from_code(self.letterbox_color); screen_width]; double_height];
+        if image_width == 0 || image_height == 0 {
+            self.view_columns = (0, 0);
+            return frame_buffer;
+        }
+        
+        let scale = (screen_width as f64 / image_width as f64).min(double_height as f64 / image_height as f64);
+        let scaled_width = ((image_width as f64 * scale) as usize).clamp(1, screen_width.max(1));
+        let scaled_height = ((image_height as f64 * scale) as usize).clamp(1, double_height.max(1));
+        let offset_x = (screen_width - scaled_width) / 2;
+        let offset_y = (double_height - scaled_height) / 2;
+        self.view_columns = (offset_x, scaled_width);
+        
+        for (y, row) in frame_buffer.iter_mut().skip(offset_y).take(scaled_height).enumerate() {
+            let source_row = &image[y * image_height / scaled_height];
+            for (x, pixel) in row.iter_mut().skip(offset_x).take(scaled_width).enumerate() {
+                *pixel = source_row[x * image_width / scaled_width];
+            }
+        }
+        
+        frame_buffer
+    }
+
+    // Draw sprites standing on the floor in the shape of their kind, farthest
+    // first so nearer sprites overlap them, skipping columns where a wall is
+    // closer. Their pixels are recorded too if `drawn_pixels` is given.
+    fn draw_sprites(&self, frame_buffer: &mut [Vec<u8>], depth_buffer: &[f64], mut drawn_pixels: Option<&mut DrawnPixels>) {
+        let screen_width = depth_buffer.len() as f64;
+        let double_height = frame_buffer.len() as f64;
+        
+        let billboards = self.billboards();
+        let mut visible: Vec<(f64, f64, &Sprite)> = billboards
+            .iter()
+            .filter_map(|sprite| {
+                let (depth, lateral) = self.player.view_offset(sprite.x, sprite.y);
+                (depth > 0.1).then_some((depth.hypot(lateral), lateral / depth, sprite))
+            })
+            .collect();
+        visible.sort_by(|a, b| b.0.total_cmp(&a.0));
+        
+        for (distance, slope, sprite) in visible {
+            let color = self.fogged(self.lit(self.daylit(sprite.color), sprite.x, sprite.y), distance);
+            let exact_color = self.daylit_rgb(palette::to_rgb(sprite.color));
+            let exact_color = self.fogged_rgb(self.lit_rgb(exact_color, sprite.x, sprite.y), distance);
+            // Same projection as the wall columns, which are sized by distance
+            // along the ray: one world unit is double_height pixels tall and
+            // width / (2 * fov) columns wide at distance 1
+            let center_x = (slope / self.fov + 1.0) / 2.0 * screen_width;
+            let unit_height = double_height / distance;
+            let (size_x, size_y) = sprite.kind.size();
+            let width = screen_width / (2.0 * self.fov * distance) * size_x;
+            let height = unit_height * size_y;
+            let bottom = self.horizon(double_height) + unit_height / 2.0;
+            let center_y = bottom - height / 2.0;
+            
+            let x_start = (center_x - width / 2.0).max(0.0) as usize;
+            let x_end = (center_x + width / 2.0).clamp(0.0, screen_width) as usize;
+            let y_start = (bottom - height).max(0.0) as usize;
+            let y_end = bottom.clamp(0.0, double_height) as usize;
+            
+            for (x, &wall_depth) in depth_buffer.iter().enumerate().take(x_end).skip(x_start) {
+                if distance >= wall_depth {
+                    continue;
+                }
+                let u = (x as f64 + 0.5 - center_x) / (width / 2.0);
+                for (y, row) in frame_buffer.iter_mut().enumerate().take(y_end).skip(y_start) {
+                    let v = (y as f64 + 0.5 - center_y) / (height / 2.0);
+                    let Some(shade) = sprite.kind.shade(u, v) else {
+                        continue;
+                    };
+                    row[x] = if shade < 1.0 { palette::scale(color, shade) } else { color };
+                    if let Some(drawn_pixels) = drawn_pixels.as_deref_mut() {
+                        drawn_pixels[y][x] = Some(DrawnPixel {
+                            code: row[x],
+                            color: palette::scale_rgb(exact_color, shade),
+                            surface: Surface::Sprite,
+                            distance,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    
+    // Position along the warm wall color ramp for a distance, from 0 (nearest
+    // color) to the last index; fractions fall between two colors
+    fn wall_ramp_position(&self, distance: f64) -> f64 {
+        // Clamp distance to reasonable range (0.1 to 15.0)
+        let clamped_dist = distance.clamp(0.1, 15.0);
+        
+        // Use logarithmic scale for better depth perception
+        let log_dist = (clamped_dist + 1.0f64).ln();
+        let max_log = (15.0f64 + 1.0f64).ln();
+        let normalized = 1.0 - (log_dist / max_log);
+        
+        // Step through the warm ramp: bright yellow for close, dark red for far
+        (1.0 - normalized) * (WALL_RAMP.len() - 1) as f64
+    }
+
+    // Character for a recorded pixel of an ASCII frame. Walls and sprites
+    // step through the ramp with the wall colors, stopping short of blank so
+    // even the farthest stay visible.
+    fn ascii_char(&self, drawn: DrawnPixel) -> char {
+        let far = self.wall_ramp_position(drawn.distance) / (WALL_RAMP.len() - 1) as f64;
+        match drawn.surface {
+            Surface::Ceiling => ' ',
+            Surface::Floor if far < ASCII_FLOOR_DASHES => '-',
+            Surface::Floor => '.',
+            Surface::Wall | Surface::Sprite => {
+                let last = render::ASCII_RAMP.len() - 2;
+                render::ASCII_RAMP[(far * last as f64).round() as usize] as char
+            }
+        }
+    }
+
+    // Dithering threshold for a pixel, between 0 and 1. Colors on a gradient
+    // step up to the next color where their fractional part exceeds it.
+    // Always 0 without dithering, so gradients just round down.
+    fn dither_threshold(&self, x: usize, y: usize) -> f64 {
+        if self.dither {
+            (BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.0
+        } else {
+            0.0
+        }
+    }
+    
+    // Palette color for a pixel of floor or ceiling from its exact color,
+    // dithered in the Bayer pattern when dithering is on
+    fn plane_code(&self, color: Rgb, x: usize, y: usize) -> u8 {
+        if self.dither {
+            palette::from_rgb_dithered(color, self.dither_threshold(x, y))
+        } else {
+            palette::from_rgb(color.0, color.1, color.2)
+        }
+    }
+    
+    // Color of the ceiling seen at a world point: sky blue tones, the sky
+    // for the time of day, or the sector's or environment's color. The sky
+    // over an outdoor sector is neither lit by the cell below it nor hidden
+    // by fog.
+    fn ceiling_rgb(
+        &self,
+        point: Option<(f64, f64)>,
+        sector: Option<SectorColors>,
+        dist_from_center: f64,
+        distance: f64,
+    ) -> Rgb {
+        let textured = point
+            .filter(|_| sector.is_none())
+            .and_then(|point| self.plane_texel_rgb(true, point, dist_from_center));
+        let color = textured.unwrap_or_else(|| {
+            match (sector.and_then(|colors| colors.ceiling), &self.day_cycle, self.environment.sky_color) {
+                (Some(color), _, _) => self.daylit_rgb(sector_shade_rgb(color, dist_from_center)),
+                (None, Some(cycle), _) => cycle.sky_rgb(dist_from_center * 2.0),
+                (None, None, Some(color)) => sector_shade_rgb(color, dist_from_center),
+                (None, None, None) => gradient_rgb(SKY_GRADIENT, dist_from_center),
+            }
+        });
+        if sector.is_some_and(|colors| colors.ceiling.is_none()) {
+            return color;
+        }
+        let color = point.map_or(color, |(x, y)| self.lit_rgb(color, x, y));
+        self.fogged_rgb(color, distance)
+    }
+    
+    // Color of the floor seen at a world point: dark stone/concrete dimmed
+    // at night, the sector's or environment's color, or a hazard's shimmer
+    fn floor_rgb(
+        &self,
+        point: Option<(f64, f64)>,
+        hazard: Option<Hazard>,
+        sector_color: Option<u8>,
+        dist_from_center: f64,
+        distance: f64,
+    ) -> Rgb {
+        if let (Some((x, y)), Some(hazard)) = (point, hazard) {
+            return self.fogged_rgb(sector_shade_rgb(hazard.color(self.run_frames, x, y), dist_from_center), distance);
+        }
+        let textured = point
+            .filter(|_| sector_color.is_none())
+            .and_then(|point| self.plane_texel_rgb(false, point, dist_from_center));
+        let color = textured.unwrap_or_else(|| match sector_color.or(self.environment.floor_color) {
+            Some(color) => self.daylit_rgb(sector_shade_rgb(color, dist_from_center)),
+            None => self.daylit_rgb(gradient_rgb(FLOOR_GRADIENT, dist_from_center)),
+        });
+        let color = point.map_or(color, |(x, y)| self.lit_rgb(color, x, y));
+        self.fogged_rgb(color, distance)
+    }
+
+    // Switch between free movement and grid movement. Entering grid mode snaps
+    // the player to the center of their cell and the nearest 90° facing.
+    pub fn set_grid_mode(&mut self, enabled: bool) {
+        self.grid_mode = enabled;
+        self.grid_move = None;
+        if enabled {
+            self.player.x = self.player.x.floor() + 0.5;
+            self.player.y = self.player.y.floor() + 0.5;
+            self.player.angle = normalize_angle(snap_to_quarter_turn(self.player.angle));
+        }
+    }
+
+    // Enter or leave photo mode. While it is on the game is paused, overlays
+    // are hidden and the camera flies freely through walls; leaving puts the
+    // player and overlays back as they were.
+    pub fn set_photo_mode(&mut self, enabled: bool) {
+        match (enabled, self.photo_mode.take()) {
+            (true, None) => {
+                self.photo_mode = Some(PhotoMode {
+                    player: self.player.clone(),
+                    floor_grid: self.floor_grid,
+                });
+                self.floor_grid = false;
+                self.grid_move = None;
+            }
+            (false, Some(saved)) => {
+                self.player = saved.player;
+                self.floor_grid = saved.floor_grid;
+                // Re-snap in case photo mode interrupted a grid step
+                self.set_grid_mode(self.grid_mode);
+            }
+            (_, saved) => self.photo_mode = saved,
+        }
+    }
+
+    pub fn photo_mode(&self) -> bool {
+        self.photo_mode.is_some()
+    }
+
+    // Detach a free camera from the player, or hand control back. The player
+    // stays where they were, drawn as a sprite, and the world is paused while
+    // the camera flies through walls.
+    pub fn set_spectating(&mut self, enabled: bool) {
+        match (enabled, self.spectating.take()) {
+            (true, None) => {
+                self.spectating = Some(self.player.clone());
+                self.grid_move = None;
+            }
+            (false, Some(player)) => {
+                self.player = player;
+                // Re-snap in case the camera detached mid grid step
+                self.set_grid_mode(self.grid_mode);
+            }
+            (_, player) => self.spectating = player,
+        }
+    }
+
+    pub fn spectating(&self) -> bool {
+        self.spectating.is_some()
+    }
+
+    // Advance one frame with the movement actions whose keys are held
+    pub fn update(&mut self, actions: &[Action]) {
+        let mut movement = Movement::default();
+        for action in actions {
+            match action {
+                Action::Forward => movement.forward += 1.0,
+                Action::Backward => movement.forward -= 1.0,
+                Action::StrafeLeft => movement.strafe -= 1.0,
+                Action::StrafeRight => movement.strafe += 1.0,
+                Action::TurnLeft => movement.turn -= 1.0,
+                Action::TurnRight => movement.turn += 1.0,
+                _ => {}
+            }
+        }
+        self.advance(actions, movement);
+    }
+
+    // Advance one frame with analog input, each axis from -1 to 1 (see
+    // `Movement`). Magnitudes go through the response curve; in grid mode,
+    // where steps are all or nothing, strong deflections act as key presses.
+    pub fn update_analog(&mut self, forward: f64, strafe: f64, turn: f64) {
+        let curve = |value: f64| value.signum() * self.response_curve.apply(value.abs());
+        let movement = Movement {
+            forward: curve(forward),
+            strafe: curve(strafe),
+            turn: curve(turn),
+        };
+        let actions: Vec<Action> = [
+            (forward, Action::Forward, Action::Backward),
+            (strafe, Action::StrafeRight, Action::StrafeLeft),
+            (turn, Action::TurnRight, Action::TurnLeft),
+        ]
+        .into_iter()
+        .filter(|(value, _, _)| value.abs() >= ANALOG_GRID_THRESHOLD)
+        .map(|(value, positive, negative)| if value > 0.0 { positive } else { negative })
+        .collect();
+        self.advance(&actions, movement);
+    }
+
+    // One frame of play. Grid movement uses the actions, free movement the
+    // movement amounts.
+    fn advance(&mut self, actions: &[Action], movement: Movement) {
+        if self.photo_mode.is_some() || self.spectating.is_some() {
+            self.update_free(movement, true);
+            return;
+        }
+        
+        if self.grid_mode {
+            self.update_grid(actions);
+        } else {
+            self.update_free(movement, false);
+        }
+        if movement.forward != 0.0 || movement.strafe != 0.0 || movement.turn != 0.0 {
+            self.at_spawn = false;
+        }
+        // Grid steps count as entering a cell once their animation finishes
+        if self.grid_move.is_none() {
+            self.update_current_cell();
+        }
+        self.update_hazard();
+        self.update_explored();
+        self.walk.update(&self.player);
+        self.stream_world();
+        self.update_enemies();
+        self.update_doors();
+        self.update_push_wall();
+        self.run_frames += 1;
+        if let Some(cycle) = &mut self.day_cycle {
+            cycle.advance(FRAME_TIME);
+        }
+    }
+
+    // Raise an event when the player moves into a new cell, and finish the
+    // level on an exit or follow any teleporter found there
+    fn update_current_cell(&mut self) {
+        self.teleport_cooldown = self.teleport_cooldown.saturating_sub(1);
+        let cell = (self.player.x.floor() as i32, self.player.y.floor() as i32);
+        if cell == self.current_cell {
+            return;
+        }
+        self.current_cell = cell;
+        self.events.push(GameEvent::TileEntered { map_x: cell.0, map_y: cell.1 });
+        if self.cell(cell.0 as usize, cell.1 as usize) == Some(EXIT_CELL) {
+            self.complete_level();
+            return;
+        }
+        
+        let Some(&(to_x, to_y)) = self.teleporters.get(&cell) else {
+            return;
+        };
+        if self.teleport_cooldown > 0 {
+            return;
+        }
+        // Arrive in the middle of the partner cell, facing the way the map
+        // says or else keeping the current facing
+        self.player.x = to_x as f64 + 0.5;
+        self.player.y = to_y as f64 + 0.5;
+        if let Some(&angle) = self.teleport_facing.get(&(to_x, to_y)) {
+            let angle = if self.grid_mode { snap_to_quarter_turn(angle) } else { angle };
+            self.player.angle = normalize_angle(angle);
+        }
+        // Start most of the way to solid and fade out
+        let flash = ScreenEffect::Flash { color: palette::to_rgb(TELEPORTER_COLOR), strength: 0.7 };
+        self.effects.push(flash, TELEPORT_FLASH_DURATION, Instant::now());
+        self.nudge_out_of_wall();
+        self.current_cell = (self.player.x.floor() as i32, self.player.y.floor() as i32);
+        self.teleport_cooldown = TELEPORT_COOLDOWN_FRAMES;
+        self.events.push(GameEvent::Teleported { map_x: to_x, map_y: to_y });
+    }
+
+    // Add the player's position to the explored path once they have moved
+    // far enough from the last point
+    fn update_explored(&mut self) {
+        let position = (self.player.x, self.player.y);
+        if let Some(run) = self.explored.last_mut() {
+            let &(x, y) = run.last().unwrap_or(&position);
+            let distance = (position.0 - x).hypot(position.1 - y);
+            if distance < EXPLORED_SPACING {
+                return;
+            }
+            if distance <= EXPLORED_JUMP {
+                run.push(position);
+                return;
+            }
+        }
+        self.explored.push(vec![position]);
+    }
+
+    // Runs of points the player has passed through on this map, oldest
+    // first, for drawing their route
+    pub fn explored(&self) -> &[Vec<(f64, f64)>] {
+        &self.explored
+    }
+
+    // Hurt the player on stepping onto a hazard and then every
+    // HAZARD_TICK_FRAMES for as long as they stay on hazards
+    fn update_hazard(&mut self) {
+        let cell = (self.player.x.floor() as i32, self.player.y.floor() as i32);
+        let Some(&hazard) = self.hazards.get(&cell) else {
+            self.hazard_frames = 0;
+            return;
+        };
+        if self.hazard_frames % HAZARD_TICK_FRAMES == 0 {
+            let amount = hazard.damage();
+            if self.take_damage(amount, Instant::now()) {
+                self.events.push(GameEvent::HazardDamage { map_x: cell.0, map_y: cell.1, amount });
+            }
+        }
+        self.hazard_frames += 1;
+    }
+
+    // Advance enemy AI and raise contact events for enemies touching the player
+    fn update_enemies(&mut self) {
+        let player = (self.player.x, self.player.y);
+        let mut enemies = std::mem::take(&mut self.enemies);
+        
+        for (index, enemy) in enemies.iter_mut().enumerate() {
+            let position = (enemy.x, enemy.y);
+            let distance = (player.0 - position.0).hypot(player.1 - position.1);
+            let sees_player = distance <= CHASE_RANGE && self.has_line_of_sight(position, player);
+            enemy.update(player, sees_player, |x, y| {
+                x >= 0.0 && y >= 0.0 && self.is_open(x as usize, y as usize)
+            });
+            
+            if (player.0 - enemy.x).hypot(player.1 - enemy.y) <= CONTACT_RANGE {
+                self.events.push(GameEvent::EnemyContact { enemy: index });
+            }
+        }
+        
+        self.enemies = enemies;
+    }
+
+    // Slide doors open or closed, holding open any with the player or an
+    // enemy in the doorway
+    fn update_doors(&mut self) {
+        let cell = |x: f64, y: f64| (x.floor() as i32, y.floor() as i32);
+        let occupants: Vec<(i32, i32)> = self
+            .enemies
+            .iter()
+            .map(|enemy| cell(enemy.x, enemy.y))
+            .chain([cell(self.player.x, self.player.y)])
+            .collect();
+        for (position, door) in &mut self.doors {
+            door.update(occupants.contains(position));
+        }
+    }
+
+    // Slide the moving push-wall on. When it reaches a cell it carries on if
+    // it has cells left and room to move, and otherwise stops there as a wall.
+    fn update_push_wall(&mut self) {
+        let Some(mut push_wall) = self.push_wall.take() else {
+            return;
+        };
+        if push_wall.advance() && !(push_wall.has_cells_left() && self.can_push_into(push_wall.next_cell())) {
+            let (x, y) = push_wall.cell;
+            self.map.set(x as usize, y as usize, b'1');
+            return;
+        }
+        self.push_wall = Some(push_wall);
+    }
+
+    // Free (analog-style) movement and turning. With noclip the player can
+    // pass through walls but not leave the map.
+    fn update_free(&mut self, movement: Movement, noclip: bool) {
+        let (sin, cos) = self.player.angle.sin_cos();
+        let move_x = (movement.forward * cos - movement.strafe * sin) * MOVE_SPEED;
+        let move_y = (movement.forward * sin + movement.strafe * cos) * MOVE_SPEED;
+        let rotate = movement.turn * ROTATION_SPEED;
+        
+        // Collision detection
+        let new_x = self.player.x + move_x;
+        let new_y = self.player.y + move_y;
+        
+        if new_x >= 0.0
+            && new_x < self.map.width() as f64
+            && new_y >= 0.0
+            && new_y < self.map.height() as f64
+        {
+            let map_x = new_x.floor() as usize;
+            let map_y = new_y.floor() as usize;
+            
+            let open = noclip || match self.get_segment(map_x as i32, map_y as i32) {
+                // Cells with a diagonal or thin wall can be entered but the
+                // wall itself can't be crossed, so stay on the side the player
+                // started from
+                Some(segment) => {
+                    let side = |x: f64, y: f64| segment.side(x - map_x as f64, y - map_y as f64) > 0.0;
+                    side(self.player.x, self.player.y) == side(new_x, new_y)
+                }
+                None => self.is_open(map_x, map_y),
+            };
+            if open {
+                self.player.x = new_x;
+                self.player.y = new_y;
+            }
+        }
+        
+        self.player.angle = normalize_angle(self.player.angle + rotate);
+    }
+
+    // Grid movement: each key press moves one cell or turns 90°, animated over
+    // GRID_STEP_FRAMES frames. Input is ignored until the current move finishes.
+    fn update_grid(&mut self, actions: &[Action]) {
+        if let Some(grid_move) = &mut self.grid_move {
+            grid_move.frame += 1;
+            let t = grid_move.frame as f64 / GRID_STEP_FRAMES as f64;
+            // Smoothstep easing so steps start and stop gently
+            let eased = t * t * (3.0 - 2.0 * t);
+            
+            self.player.x = grid_move.from_x + (grid_move.to_x - grid_move.from_x) * eased;
+            self.player.y = grid_move.from_y + (grid_move.to_y - grid_move.from_y) * eased;
+            self.player.angle = grid_move.from_angle + (grid_move.to_angle - grid_move.from_angle) * eased;
+            
+            if grid_move.frame >= GRID_STEP_FRAMES {
+                self.player.x = grid_move.to_x;
+                self.player.y = grid_move.to_y;
+                self.player.angle = normalize_angle(grid_move.to_angle);
+                self.grid_move = None;
+            }
+            return;
+        }
+        
+        // Unit cell offsets for facing +x, +y, -x, -y
+        const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+        let facing = (snap_to_quarter_turn(self.player.angle) / std::f64::consts::FRAC_PI_2).round() as i32;
+        let direction = |turn: i32| DIRECTIONS[(facing + turn).rem_euclid(4) as usize];
+        
+        let mut step = None;
+        let mut turn = 0.0;
+        for action in actions {
+            match action {
+                Action::Forward => step = Some(direction(0)),
+                Action::Backward => step = Some(direction(2)),
+                Action::StrafeLeft => step = Some(direction(-1)),
+                Action::StrafeRight => step = Some(direction(1)),
+                Action::TurnLeft => turn = -std::f64::consts::FRAC_PI_2,
+                Action::TurnRight => turn = std::f64::consts::FRAC_PI_2,
+                _ => continue,
+            }
+            break;
+        }
+        
+        let (mut to_x, mut to_y) = (self.player.x, self.player.y);
+        if let Some((dx, dy)) = step {
+            let cell_x = self.player.x.floor() as i32 + dx;
+            let cell_y = self.player.y.floor() as i32 + dy;
+            if cell_x < 0
+                || cell_y < 0
+                || !self.is_open(cell_x as usize, cell_y as usize)
+            {
+                return;
+            }
+            to_x = cell_x as f64 + 0.5;
+            to_y = cell_y as f64 + 0.5;
+        } else if turn == 0.0 {
+            return;
+        }
+        
+        self.grid_move = Some(GridMove {
+            from_x: self.player.x,
+            from_y: self.player.y,
+            from_angle: self.player.angle,
+            to_x,
+            to_y,
+            to_angle: self.player.angle + turn,
+            frame: 0,
+        });
+    }
+}
+
+// Unit vector for a light travelling at an angle in degrees
+pub fn light_direction(degrees: f64) -> (f64, f64) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (cos, sin)
+}
+
+// A sector's or the map's floor or ceiling color, darkening towards the
+// horizon as the default gradients do
+fn sector_shade_rgb(color: u8, dist_from_center: f64) -> Rgb {
+    palette::scale_rgb(palette::to_rgb(color), horizon_brightness(dist_from_center))
+}
+
+// Brightness factor for a row of floor or ceiling, dimmest at the horizon
+fn horizon_brightness(dist_from_center: f64) -> f64 {
+    SECTOR_HORIZON_BRIGHTNESS + (1.0 - SECTOR_HORIZON_BRIGHTNESS) * dist_from_center.min(1.0)
+}
+
+// Exact color of a default floor or ceiling gradient, blending between
+// neighbouring palette colors where the palette version steps between them
+fn gradient_rgb((first, last): (u8, u8), dist_from_center: f64) -> Rgb {
+    let position = dist_from_center.min(1.0) * (last - first) as f64;
+    let near = palette::to_rgb(first + position.floor() as u8);
+    let far = palette::to_rgb((first + position.ceil() as u8).min(last));
+    palette::mix_rgb(near, far, position.fract())
+}
+
+
+// Brightness factor for image-textured walls, dimming with distance on the
+// same logarithmic scale as the wall color gradient
+fn distance_brightness(distance: f64) -> f64 {
+    let log_dist = (distance.clamp(0.1, 15.0) + 1.0).ln();
+    let max_log = (15.0f64 + 1.0).ln();
+    0.3 + 0.7 * (1.0 - log_dist / max_log)
+}
+
+// FNV-1a hash of a wall grid and its size
+fn hash_map(map: &Map) -> u64 {
+    let size = [map.width(), map.height()].map(|length| length as u64);
+    let cells = map.cells().map(|(_, cell)| cell as u64);
+    size.into_iter().chain(cells).fold(0xcbf29ce484222325, |hash, value| {
+        (hash ^ value).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn find_destructibles(map: &Map) -> HashMap<(i32, i32), DestructibleWall> {
+    map.cells()
+        .filter(|&(_, cell)| cell == DESTRUCTIBLE_CELL)
+        .map(|((x, y), _)| ((x as i32, y as i32), DestructibleWall::new()))
+        .collect()
+}
+
+fn find_exits(map: &Map) -> Vec<(i32, i32)> {
+    map.cells()
+        .filter(|&(_, cell)| cell == EXIT_CELL)
+        .map(|((x, y), _)| (x as i32, y as i32))
+        .collect()
+}
+
+// Find door cells in a map. A door between walls to the west and east slides
+// along the x axis; any other door slides along the y axis.
+fn parse_doors(map: &Map) -> HashMap<(i32, i32), Door> {
+    let solid = |x: usize, y: usize| matches!(map.get(x, y), Some(b'1'..=b'9' | b'/' | b'\\' | b'-' | b'|'));
+    map.cells()
+        .filter(|&(_, cell)| cell == DOOR_CELL)
+        .map(|((x, y), _)| {
+            let along_x = x > 0 && solid(x - 1, y) && solid(x + 1, y);
+            ((x as i32, y as i32), Door::new(along_x))
+        })
+        .collect()
+}
+
+// Write a label over the top-left corner of a frame that was just encoded,
+// leaving the cursor where the frame ended. Relative cursor moves keep this
+// working in inline mode.
+fn push_top_left_label(output: &mut String, screen_height: usize, label: &str) {
+    let rows_up = screen_height.saturating_sub(1);
+    if rows_up > 0 {
+        output.push_str(&format!("\r\x1b[{}A", rows_up));
+    } else {
+        output.push('\r');
+    }
+    output.push_str(&format!("\x1b[0m {} ", label));
+    if rows_up > 0 {
+        output.push_str(&format!("\x1b[{}B", rows_up));
+    }
+}
+
+// Write a label right-aligned on the top row of the screen, as
+// `push_top_left_label` writes one on the left
+fn push_top_right_label(output: &mut String, screen_width: usize, screen_height: usize, label: &str) {
+    let rows_up = screen_height.saturating_sub(1);
+    if rows_up > 0 {
+        output.push_str(&format!("\x1b[{}A", rows_up));
+    }
+    let column = screen_width.saturating_sub(label.chars().count() + 2) + 1;
+    output.push_str(&format!("\x1b[{}G\x1b[0m {} ", column, label));
+    if rows_up > 0 {
+        output.push_str(&format!("\x1b[{}B", rows_up));
+    }
+}
+
+// Nearest of the eight compass points to a facing in degrees, with 0
+// pointing east along +x and 90 south along +y
+fn compass_point(degrees: f64) -> &'static str {
+    const POINTS: [&str; 8] = ["E", "SE", "S", "SW", "W", "NW", "N", "NE"];
+    POINTS[((degrees / 45.0).round() as usize) % POINTS.len()]
+}
+
+// Move everything in a frame buffer right by `dx` pixels and down by `dy`,
+// repeating the edge rows and columns into the space uncovered
+fn shift<T: Clone>(buffer: &mut [Vec<T>], dx: i32, dy: i32) {
+    let height = buffer.len() as i32;
+    let width = buffer.first().map_or(0, Vec::len) as i32;
+    let source = buffer.to_vec();
+    for (y, row) in buffer.iter_mut().enumerate() {
+        let source_row = &source[(y as i32 - dy).clamp(0, height - 1) as usize];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = source_row[(x as i32 - dx).clamp(0, width - 1) as usize].clone();
+        }
+    }
+}
+
+// Small plus sign in the middle of the view
+fn draw_crosshair(frame_buffer: &mut [Vec<u8>]) {
+    let height = frame_buffer.len();
+    let width = frame_buffer.first().map_or(0, Vec::len);
+    if width < 3 || height < 3 {
+        return;
+    }
+    let (center_x, center_y) = (width / 2, height / 2);
+    frame_buffer[center_y][center_x - 1..=center_x + 1].fill(CROSSHAIR_COLOR);
+    frame_buffer[center_y - 1][center_x] = CROSSHAIR_COLOR;
+    frame_buffer[center_y + 1][center_x] = CROSSHAIR_COLOR;
+}
+
+// Nearest-neighbour resize of an image to exactly width x height
+fn stretch<P: Pixel>(image: &[Vec<P>], width: usize, height: usize) -> Vec<Vec<P>> {
+    let image_height = image.len();
+    let image_width = image.first().map_or(0, Vec::len);
+    if image_width == 0 || image_height == 0 {
+        return vec![vec![P::from_code(0); width]; height];
+    }
+    
+    (0..height)
+        .map(|y| {
+            let source_row = &image[y * image_height / height];
+            (0..width).map(|x| source_row[x * image_width / width]).collect()
+        })
+        .collect()
+}
+
+// Darken a frame towards its edges, more strongly the higher the strength
+fn draw_vignette(frame_buffer: &mut [Vec<u8>], strength: f64) {
+    if strength < 0.01 {
+        return;
+    }
+    let height = frame_buffer.len() as f64;
+    let width = frame_buffer.first().map_or(0, Vec::len) as f64;
+    
+    for (y, row) in frame_buffer.iter_mut().enumerate() {
+        let dy = (y as f64 + 0.5) / height * 2.0 - 1.0;
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let dx = (x as f64 + 0.5) / width * 2.0 - 1.0;
+            // Untouched in the middle, easing in from halfway to the edges
+            let edge = ((dx.hypot(dy) - 0.5) / 0.9).clamp(0.0, 1.0);
+            let darken = strength * VIGNETTE_DARKEN * edge * edge * (3.0 - 2.0 * edge);
+            if darken > 0.0 {
+                *pixel = palette::scale(*pixel, 1.0 - darken);
+            }
+        }
+    }
+}
+
+// Mix a color towards an RGB tint by the given opacity
+fn blend(pixel: u8, tint: (u8, u8, u8), opacity: f64) -> u8 {
+    let (r, g, b) = palette::mix_rgb(palette::to_rgb(pixel), tint, opacity);
+    palette::from_rgb(r, g, b)
+}
+
+// Blend every pixel towards a color by `strength`, from 0.0 (unchanged) to
+// 1.0 (solid color)
+fn flash(frame_buffer: &mut [Vec<u8>], (r, g, b): (u8, u8, u8), strength: f64) {
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * strength) as u8;
+    for pixel in frame_buffer.iter_mut().flatten() {
+        let (pr, pg, pb) = palette::to_rgb(*pixel);
+        *pixel = palette::from_rgb(mix(pr, r), mix(pg, g), mix(pb, b));
+    }
+}
+
+// Wrap an angle into the range [0, 2π)
+fn normalize_angle(angle: f64) -> f64 {
+    angle.rem_euclid(2.0 * std::f64::consts::PI)
+}
+
+// Round an angle to the nearest multiple of 90°
+fn snap_to_quarter_turn(angle: f64) -> f64 {
+    (angle / std::f64::consts::FRAC_PI_2).round() * std::f64::consts::FRAC_PI_2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::CHUNK_SIZE;
+
+    // A raycaster on a map given as rows of map characters
+    fn raycaster_on(rows: &str) -> Raycaster {
+        let mut raycaster = Raycaster::new();
+        raycaster.load_map(MapFile::parse_text(rows).expect("test maps are valid"));
+        raycaster
+    }
+
+    #[test]
+    fn nudge_moves_player_out_of_wall_to_nearest_open_cell() {
+        let mut raycaster = raycaster_on("11111\n10001\n11101\n11111\n");
+        raycaster.player.x = 2.4;
+        raycaster.player.y = 2.5;
+        assert!(raycaster.nudge_out_of_wall());
+        assert_eq!((raycaster.player.x, raycaster.player.y), (2.5, 1.5));
+    }
+
+    #[test]
+    fn nudge_leaves_player_in_open_cell() {
+        let mut raycaster = raycaster_on("11111\n10001\n11101\n11111\n");
+        raycaster.player.x = 1.2;
+        raycaster.player.y = 1.7;
+        assert!(!raycaster.nudge_out_of_wall());
+        assert_eq!((raycaster.player.x, raycaster.player.y), (1.2, 1.7));
+    }
+
+    #[test]
+    fn wall_ramp_darkens_steadily() {
+        // No step may brighten, or darken by more than a quarter of the range
+        let lumas: Vec<f64> = WALL_RAMP.iter().map(|&color| palette::luma(palette::to_rgb(color))).collect();
+        for pair in lumas.windows(2) {
+            assert!(pair[1] < pair[0], "ramp brightens: {:?}", lumas);
+            assert!(pair[0] - pair[1] < 0.25, "ramp jumps: {:?}", lumas);
+        }
+    }
+
+    #[test]
+    fn line_of_sight_across_open_floor() {
+        let raycaster = raycaster_on("11111\n10001\n10101\n10001\n11111\n");
+        assert!(raycaster.has_line_of_sight((1.5, 1.5), (3.5, 1.5)));
+        assert!(raycaster.has_line_of_sight((1.5, 1.5), (1.5, 3.5)));
+    }
+
+    #[test]
+    fn line_of_sight_blocked_by_wall() {
+        let raycaster = raycaster_on("11111\n10001\n10101\n10001\n11111\n");
+        assert!(!raycaster.has_line_of_sight((1.5, 2.5), (3.5, 2.5)));
+        assert!(!raycaster.has_line_of_sight((2.5, 1.5), (2.5, 3.5)));
+    }
+
+    #[test]
+    fn line_of_sight_through_corner_steps_along_y_first() {
+        // The line from (1.5, 1.5) to (2.5, 2.5) passes exactly through the
+        // corner at (2, 2), so only the cell below the start can block it
+        let wall_below = raycaster_on("11111\n10001\n11001\n10001\n11111\n");
+        assert!(!wall_below.has_line_of_sight((1.5, 1.5), (2.5, 2.5)));
+        let wall_beside = raycaster_on("11111\n10101\n10001\n10001\n11111\n");
+        assert!(wall_beside.has_line_of_sight((1.5, 1.5), (2.5, 2.5)));
+    }
+
+    #[test]
+    fn repeated_enemy_contact_ends_the_game() {
+        let mut raycaster = raycaster_on("11111\n10001\n10001\n11111\n");
+        raycaster.enemies = vec![Enemy::new(vec![(raycaster.player.x, raycaster.player.y)])];
+        let hits_to_die = (DEFAULT_HEALTH / DEFAULT_CONTACT_DAMAGE).ceil() as usize;
+        let mut now = Instant::now();
+        for hit in 1..=hits_to_die {
+            assert!(!raycaster.is_game_over(), "game over after {} hits", hit - 1);
+            raycaster.update_enemies();
+            for event in raycaster.drain_events() {
+                if let GameEvent::EnemyContact { .. } = event {
+                    // Contact again within the cooldown does no more damage
+                    assert!(raycaster.take_damage(DEFAULT_CONTACT_DAMAGE, now));
+                    assert!(!raycaster.take_damage(DEFAULT_CONTACT_DAMAGE, now));
+                }
+            }
+            now += DAMAGE_COOLDOWN;
+        }
+        assert!(raycaster.is_game_over());
+    }
+
+    #[test]
+    fn stepping_on_teleporter_moves_player_to_its_pair() {
+        let mut raycaster = raycaster_on("1111111\n10A0A01\n1111111\n");
+        raycaster.player.x = 2.5;
+        raycaster.update(&[]);
+        assert_eq!((raycaster.player.x, raycaster.player.y), (4.5, 1.5));
+        assert!(raycaster
+            .drain_events()
+            .iter()
+            .any(|event| matches!(event, GameEvent::Teleported { map_x: 4, map_y: 1 })));
+    }
+
+    #[test]
+    fn mirroring_twice_gives_back_the_frame() {
+        let mut raycaster = raycaster_on("111111\n100021\n100001\n100001\n111111\n");
+        let frame = raycaster.compose_frame::<u8>(40, 20);
+        raycaster.mirror = true;
+        let mut mirrored = raycaster.compose_frame::<u8>(40, 20);
+        assert_ne!(mirrored, frame);
+        mirrored.iter_mut().for_each(|row| row.reverse());
+        assert_eq!(mirrored, frame);
+    }
+
+    // FNV-1a hash of a frame's size and pixels, to compare it with a golden
+    // frame without storing the whole image
+    fn frame_hash<P: Copy + Into<u32>>(frame: &[Vec<P>]) -> u64 {
+        let width = frame.first().map_or(0, Vec::len);
+        [frame.len(), width]
+            .into_iter()
+            .map(|size| size as u32)
+            .chain(frame.iter().flatten().map(|&pixel| pixel.into()))
+            .fold(0xcbf29ce484222325, |hash, value| (hash ^ value as u64).wrapping_mul(0x100000001b3))
+    }
+
+    #[test]
+    fn column_runs_match_the_per_pixel_fill() {
+        // Hashes of the palette and truecolor frames as rendered by filling
+        // each column pixel by pixel, before it was split into ceiling, wall
+        // and floor runs. Scenes: the built-in map at its spawn, turned at an
+        // odd height, and with the horizon shifted; facing a wall close
+        // enough to fill every column; and with rays cut short so some
+        // columns show the background.
+        let spawn = Raycaster::new();
+        let mut turned = Raycaster::new();
+        turned.player.angle += 1.0;
+        let mut shifted = Raycaster::new();
+        shifted.horizon_shift = 5;
+        let mut close = raycaster_on("11111\n10001\n10001\n11111\n");
+        close.player.x = 1.05;
+        close.player.y = 1.5;
+        close.player.angle = std::f64::consts::PI;
+        let mut cut_short = Raycaster::new();
+        cut_short.max_dda_steps = Some(3);
+        let scenes = [
+            (spawn, 80, 48, 0x9858c2867a1a99a0, 0x5a36c9f2b6238349),
+            (turned, 61, 37, 0x02aa99ef08ca039e, 0x4efe95c4e9b25bcc),
+            (shifted, 50, 31, 0x60bb308a31542534, 0x301c9ec4d4fc0263),
+            (close, 40, 25, 0x05c1204bb42b5afc, 0x5069ad598d6cd054),
+            (cut_short, 64, 40, 0x008dda12059d4058, 0xa474da43d26a339f),
+        ];
+        for (index, (mut raycaster, width, height, golden, golden_rgb)) in scenes.into_iter().enumerate() {
+            let frame = raycaster.render_frame(width, height);
+            assert_eq!(frame_hash(&frame), golden, "palette frame of scene {}", index);
+            let rgb: Vec<Vec<u32>> = raycaster
+                .render_frame_rgb(width, height)
+                .iter()
+                .map(|row| row.iter().map(|&(r, g, b)| u32::from_be_bytes([0, r, g, b])).collect())
+                .collect();
+            assert_eq!(frame_hash(&rgb), golden_rgb, "truecolor frame of scene {}", index);
+        }
+    }
+
+    #[test]
+    fn ray_stops_after_max_dda_steps() {
+        let mut raycaster = raycaster_on("1111111111111\n1000000000001\n1111111111111\n");
+        raycaster.player.x = 1.5;
+        raycaster.player.y = 1.5;
+        assert!(raycaster.cast_ray(0.0).hit);
+        raycaster.max_dda_steps = Some(3);
+        let ray = raycaster.cast_ray(0.0);
+        assert!(!ray.hit);
+        assert_eq!(ray.map_x, 4);
+        assert!(ray.distance < 4.0);
+    }
+
+    #[test]
+    fn streaming_the_world_keeps_the_explored_path_in_place() {
+        let mut raycaster = Raycaster::new();
+        raycaster.load_world(World::new(1));
+        raycaster.update_explored();
+        let world_point = |raycaster: &Raycaster| {
+            let (origin_x, origin_y) = raycaster.world.as_ref().expect("a world is loaded").origin();
+            let (x, y) = raycaster.explored()[0][0];
+            (origin_x as f64 + x, origin_y as f64 + y)
+        };
+        let before = world_point(&raycaster);
+        let origin = raycaster.world.as_ref().expect("a world is loaded").origin();
+        raycaster.player.x += CHUNK_SIZE as f64;
+        raycaster.stream_world();
+        assert_ne!(raycaster.world.as_ref().expect("a world is loaded").origin(), origin);
+        assert_eq!(world_point(&raycaster), before);
+    }
+
+    #[test]
+    fn command_line_fog_yields_to_the_map_fog() {
+        let fog = Environment {
+            fog_end: 4.0,
+            fog_color: 196,
+            ..Environment::default()
+        };
+        let mut raycaster = raycaster_on("111\n101\n111\n");
+        raycaster.default_fog = fog;
+        assert_eq!(raycaster.fogged(21, 2.0), blend(21, palette::to_rgb(196), 0.5));
+        assert_eq!(raycaster.fogged(21, 10.0), 196);
+        raycaster.environment.fog_end = 20.0;
+        assert_eq!(raycaster.fogged(21, 10.0), blend(21, palette::to_rgb(196), 0.5));
+    }
+
+    #[test]
+    fn reset_after_walking_returns_once_and_stops_the_bob() {
+        let mut raycaster = raycaster_on("1111111\n1000001\n1111111\n");
+        raycaster.reset_to_spawn();
+        assert!(raycaster.drain_events().is_empty());
+        for _ in 0..10 {
+            raycaster.update(&[Action::Forward]);
+        }
+        assert!(raycaster.walk.bob() > 0.0);
+        raycaster.drain_events();
+        raycaster.reset_to_spawn();
+        raycaster.reset_to_spawn();
+        assert_eq!(raycaster.drain_events().iter().filter(|event| matches!(event, GameEvent::Reset)).count(), 1);
+        assert_eq!(raycaster.walk.bob(), 0.0);
+    }
+}
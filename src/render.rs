@@ -0,0 +1,638 @@
+// Terminal output formats. The renderer produces a frame buffer of
+// 256-color codes, or of RGB colors for truecolor terminals; a render mode
+// decides how many pixels map to each character cell and encodes the buffer
+// as ANSI text.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::kitty;
+use crate::palette::{self, Rgb};
+use crate::sixel;
+use crate::terminfo;
+use crate::RaycastError;
+
+// Characters of the ASCII render mode from the densest, for the brightest
+// or nearest pixels, to the sparsest
+pub const ASCII_RAMP: &[u8] = b"@#*+=-. ";
+const ASCII_CELL_FG: u8 = 231; // White, for ASCII frames drawn as cells
+const ASCII_CELL_BG: u8 = 16; // Black
+// Terminal types with only the 16 system colors, for when they have no
+// terminfo entry
+const BASIC_TERMS: [&str; 7] = ["linux", "vt100", "vt220", "ansi", "cons25", "xterm-color", "rxvt"];
+const DEFAULT_CELL_SIZE: (usize, usize) = (10, 20); // Pixels per character cell if the terminal won't say
+
+// Braille characters start at the one with no dots, each dot adding a bit
+const BRAILLE_BLANK: u32 = 0x2800;
+// Column, row and bit of each dot in a braille cell
+const BRAILLE_DOTS: [(usize, usize, u32); 8] = [
+    (0, 0, 0x01),
+    (0, 1, 0x02),
+    (0, 2, 0x04),
+    (1, 0, 0x08),
+    (1, 1, 0x10),
+    (1, 2, 0x20),
+    (0, 3, 0x40),
+    (1, 3, 0x80),
+];
+
+// One character cell of an encoded frame, for drawing into another TUI
+// library's buffer instead of writing escape codes. Colors are 256-color
+// codes, which map directly onto indexed colors (e.g. ratatui's
+// `Color::Indexed`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub fg: u8,
+    pub bg: u8,
+}
+
+// Copy cells into a ratatui buffer with the first at the area's top-left
+// corner, clipped to the area and the buffer. Colors become indexed colors.
+// Needs the `ratatui` feature.
+#[cfg(feature = "ratatui")]
+pub fn write_cells(cells: &[Vec<Cell>], area: ratatui::layout::Rect, buffer: &mut ratatui::buffer::Buffer) {
+    use ratatui::style::Color;
+
+    let area = area.intersection(buffer.area);
+    for (row, y) in cells.iter().zip(area.top()..area.bottom()) {
+        for (cell, x) in row.iter().zip(area.left()..area.right()) {
+            if let Some(target) = buffer.cell_mut((x, y)) {
+                target
+                    .set_char(cell.symbol)
+                    .set_fg(Color::Indexed(cell.fg))
+                    .set_bg(Color::Indexed(cell.bg));
+            }
+        }
+    }
+}
+
+// A layer of pixels drawn over a frame buffer with its top-left corner at
+// an offset, such as the weapon viewmodel. It may hang off the frame's
+// edges, and None pixels leave the frame showing through.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Overlay {
+    pub left: isize,
+    pub top: isize,
+    pub pixels: Vec<Vec<Option<u8>>>,
+}
+
+impl Overlay {
+    pub fn composite(&self, frame_buffer: &mut [Vec<u8>]) {
+        for (y, row) in self.pixels.iter().enumerate() {
+            let Ok(frame_y) = usize::try_from(self.top + y as isize) else { continue };
+            let Some(frame_row) = frame_buffer.get_mut(frame_y) else { continue };
+            for (x, &pixel) in row.iter().enumerate() {
+                let (Some(color), Ok(frame_x)) = (pixel, usize::try_from(self.left + x as isize)) else {
+                    continue;
+                };
+                if let Some(target) = frame_row.get_mut(frame_x) {
+                    *target = color;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    // Half-block characters, two pixels per cell (double vertical resolution)
+    HalfBlock,
+    // One colored cell per pixel
+    FullBlock,
+    // One character per pixel, denser the brighter the pixel, with no color
+    // codes at all, for serial consoles and fonts without block characters.
+    // Shading dims with distance, so near walls come out as `@` and `#` and
+    // far ones as `-` and `.`.
+    Ascii,
+    // Braille characters, a 2x4 grid of dots per cell (four times the
+    // vertical resolution of a full block). Each cell only has two colors,
+    // so its brighter pixels are drawn as dots in their average color over
+    // the average of the darker ones.
+    Braille,
+    // A sixel bitmap, for terminals that can show one, with a pixel for
+    // every `sixel::SCALE` screen pixels each way of a character cell of
+    // the given size in screen pixels
+    Sixel { cell_width: usize, cell_height: usize },
+    // An RGB image sent with the kitty graphics protocol, for terminals
+    // that support it, with a pixel for every `kitty::SCALE` screen pixels
+    // each way of a character cell of the given size in screen pixels.
+    // The terminal scales it to fit the screen exactly.
+    Kitty { cell_width: usize, cell_height: usize },
+}
+
+// Colors the terminal is sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    // 24-bit colors, so shading changes smoothly with distance
+    TrueColor,
+    // The xterm 256-color palette, which every color is matched to
+    Indexed,
+    // The 16 system colors, for minimal terminals. Colors keep their hue in
+    // bright, normal and dark bands (see `palette::to_basic`).
+    Basic,
+}
+
+impl ColorDepth {
+    // Truecolor if the terminal says it supports it through COLORTERM;
+    // otherwise as many colors as the terminfo entry for TERM lists, or
+    // guessed from the name of TERM when there is no entry. Unknown
+    // terminals are assumed to have the 256-color palette.
+    pub fn detect() -> Self {
+        if let Ok("truecolor" | "24bit") = std::env::var("COLORTERM").as_deref() {
+            return ColorDepth::TrueColor;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        match terminfo::max_colors(&term) {
+            Some(colors) if colors >= 256 => ColorDepth::Indexed,
+            Some(_) => ColorDepth::Basic,
+            None if BASIC_TERMS.contains(&term.as_str()) => ColorDepth::Basic,
+            None => ColorDepth::Indexed,
+        }
+    }
+}
+
+impl FromStr for ColorDepth {
+    type Err = RaycastError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "truecolor" | "24bit" => Ok(ColorDepth::TrueColor),
+            "256" => Ok(ColorDepth::Indexed),
+            "16" => Ok(ColorDepth::Basic),
+            _ => Err(RaycastError::Config(format!("unknown color depth: {}", value))),
+        }
+    }
+}
+
+// A frame buffer pixel that can be written as an SGR color parameter
+trait EscapeColor: Copy + PartialEq {
+    // Append the escape sequence setting the foreground (`layer` 38) or
+    // background (48) to this color
+    fn push_sgr(self, layer: u8, output: &mut String);
+    fn rgb(self) -> Rgb;
+    fn from_rgb(rgb: Rgb) -> Self;
+}
+
+impl EscapeColor for u8 {
+    fn push_sgr(self, layer: u8, output: &mut String) {
+        let _ = write!(output, "\x1b[{};5;{}m", layer, self);
+    }
+
+    fn rgb(self) -> Rgb {
+        palette::to_rgb(self)
+    }
+
+    fn from_rgb((r, g, b): Rgb) -> Self {
+        palette::from_rgb(r, g, b)
+    }
+}
+
+impl EscapeColor for Rgb {
+    fn push_sgr(self, layer: u8, output: &mut String) {
+        let (r, g, b) = self;
+        let _ = write!(output, "\x1b[{};2;{};{};{}m", layer, r, g, b);
+    }
+
+    fn rgb(self) -> Rgb {
+        self
+    }
+
+    fn from_rgb(rgb: Rgb) -> Self {
+        rgb
+    }
+}
+
+// A system color, 0-15, written with the original 8/16-color SGR codes
+#[derive(Clone, Copy, PartialEq)]
+struct Basic(u8);
+
+impl EscapeColor for Basic {
+    fn push_sgr(self, layer: u8, output: &mut String) {
+        // 38 -> 30-37 and 90-97, 48 -> 40-47 and 100-107
+        let base = layer - 8 + if self.0 < 8 { 0 } else { 60 };
+        let _ = write!(output, "\x1b[{}m", base + self.0 % 8);
+    }
+
+    fn rgb(self) -> Rgb {
+        palette::to_rgb(self.0)
+    }
+
+    fn from_rgb((r, g, b): Rgb) -> Self {
+        Basic(palette::to_basic(palette::from_rgb(r, g, b)))
+    }
+}
+
+impl RenderMode {
+    // Pixel rows to render for a terminal with `screen_height` character rows
+    pub fn pixel_rows(self, screen_height: usize) -> usize {
+        match self {
+            RenderMode::HalfBlock => screen_height * 2,
+            RenderMode::FullBlock | RenderMode::Ascii => screen_height,
+            RenderMode::Braille => screen_height * 4,
+            // The last row is left clear, so terminals don't scroll the
+            // screen to fit the cursor below the image
+            RenderMode::Sixel { cell_height, .. } => screen_height.saturating_sub(1) * cell_height / sixel::SCALE,
+            RenderMode::Kitty { cell_height, .. } => screen_height * cell_height / kitty::SCALE,
+        }
+    }
+
+    // Pixel columns to render for a terminal `screen_width` characters wide
+    pub fn pixel_columns(self, screen_width: usize) -> usize {
+        match self {
+            RenderMode::Braille => screen_width * 2,
+            RenderMode::Sixel { cell_width, .. } => screen_width * cell_width / sixel::SCALE,
+            RenderMode::Kitty { cell_width, .. } => screen_width * cell_width / kitty::SCALE,
+            RenderMode::HalfBlock | RenderMode::FullBlock | RenderMode::Ascii => screen_width,
+        }
+    }
+
+    // Kitty or sixel images at the size of the terminal's character cells,
+    // or half-blocks if the terminal isn't known to show either
+    pub fn detect() -> Self {
+        if kitty::detect() {
+            RenderMode::kitty()
+        } else if sixel::detect() {
+            RenderMode::sixel()
+        } else {
+            RenderMode::HalfBlock
+        }
+    }
+
+    pub fn sixel() -> Self {
+        let (cell_width, cell_height) = cell_size();
+        RenderMode::Sixel { cell_width, cell_height }
+    }
+
+    pub fn kitty() -> Self {
+        let (cell_width, cell_height) = cell_size();
+        RenderMode::Kitty { cell_width, cell_height }
+    }
+
+    // Whether frames are drawn as a bitmap rather than character cells
+    pub fn is_bitmap(self) -> bool {
+        matches!(self, RenderMode::Sixel { .. } | RenderMode::Kitty { .. })
+    }
+
+    // Encode a frame buffer as ANSI text. Rows are separated by CRLF and the
+    // output starts at the current cursor position.
+    pub fn encode(self, frame_buffer: &[Vec<u8>]) -> String {
+        match self {
+            RenderMode::Sixel { cell_height, .. } => sixel::encode(frame_buffer, cell_height),
+            RenderMode::Kitty { cell_width, cell_height } => {
+                kitty::encode(&to_rgb(frame_buffer), cell_width, cell_height)
+            }
+            RenderMode::HalfBlock => encode_half_block(frame_buffer),
+            RenderMode::FullBlock => encode_full_block(frame_buffer),
+            RenderMode::Ascii => encode_ascii(frame_buffer),
+            RenderMode::Braille => encode_braille(frame_buffer),
+        }
+    }
+
+    // Encode a frame buffer of RGB colors as ANSI text with 24-bit color
+    // sequences, laid out as `encode` lays out 256-color frames
+    pub fn encode_rgb(self, frame_buffer: &[Vec<Rgb>]) -> String {
+        match self {
+            RenderMode::Sixel { cell_height, .. } => sixel::encode(&to_codes(frame_buffer), cell_height),
+            RenderMode::Kitty { cell_width, cell_height } => kitty::encode(frame_buffer, cell_width, cell_height),
+            RenderMode::HalfBlock => encode_half_block(frame_buffer),
+            RenderMode::FullBlock => encode_full_block(frame_buffer),
+            RenderMode::Ascii => encode_ascii(frame_buffer),
+            RenderMode::Braille => encode_braille(frame_buffer),
+        }
+    }
+
+    // Encode a frame buffer as ANSI text with the 16 system colors, laid out
+    // as `encode` lays out 256-color frames. Bitmaps keep their own colors.
+    pub fn encode_basic(self, frame_buffer: &[Vec<u8>]) -> String {
+        let basic: Vec<Vec<Basic>> = frame_buffer
+            .iter()
+            .map(|row| row.iter().map(|&color| Basic(palette::to_basic(color))).collect())
+            .collect();
+        match self {
+            RenderMode::HalfBlock => encode_half_block(&basic),
+            RenderMode::FullBlock => encode_full_block(&basic),
+            RenderMode::Ascii => encode_ascii(&basic),
+            RenderMode::Braille => encode_braille(&basic),
+            RenderMode::Sixel { .. } | RenderMode::Kitty { .. } => self.encode(frame_buffer),
+        }
+    }
+
+    // Encode a frame buffer as rows of character cells, using the same
+    // characters and colors as `encode`. Bitmaps have no cells, so they are
+    // drawn as half-blocks.
+    pub fn cells(self, frame_buffer: &[Vec<u8>]) -> Vec<Vec<Cell>> {
+        match self {
+            RenderMode::Sixel { .. } | RenderMode::Kitty { .. } => RenderMode::HalfBlock.cells(frame_buffer),
+            RenderMode::HalfBlock => frame_buffer
+                .chunks(2)
+                .map(|rows| {
+                    let lower_row = rows.get(1).unwrap_or(&rows[0]);
+                    rows[0]
+                        .iter()
+                        .zip(lower_row)
+                        .map(|(&fg, &bg)| Cell { symbol: '▀', fg, bg })
+                        .collect()
+                })
+                .collect(),
+            RenderMode::FullBlock => frame_buffer
+                .iter()
+                .map(|row| row.iter().map(|&bg| Cell { symbol: ' ', fg: bg, bg }).collect())
+                .collect(),
+            RenderMode::Ascii => text_cells(&to_ascii(frame_buffer)),
+            RenderMode::Braille => braille_cells(frame_buffer)
+                .into_iter()
+                .map(|row| row.into_iter().map(|(symbol, fg, bg)| Cell { symbol, fg, bg }).collect())
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for RenderMode {
+    type Err = RaycastError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "half-block" => Ok(RenderMode::HalfBlock),
+            "full-block" => Ok(RenderMode::FullBlock),
+            "ascii" => Ok(RenderMode::Ascii),
+            "braille" => Ok(RenderMode::Braille),
+            "sixel" => Ok(RenderMode::sixel()),
+            "kitty" => Ok(RenderMode::kitty()),
+            _ => Err(RaycastError::Config(format!("unknown render mode: {}", value))),
+        }
+    }
+}
+
+// Size of a character cell in screen pixels, from the terminal if it
+// reports its size in pixels
+pub fn cell_size() -> (usize, usize) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as usize,
+            (size.height / size.rows) as usize,
+        ),
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+// Write a frame buffer as a binary PPM (P6) image, one image pixel per
+// frame buffer pixel
+pub fn write_ppm<W: Write>(frame_buffer: &[Vec<u8>], out: &mut W) -> io::Result<()> {
+    write_ppm_rgb(&to_rgb(frame_buffer), out)
+}
+
+// Write a frame buffer of RGB colors as a binary PPM (P6) image
+pub fn write_ppm_rgb<W: Write>(frame_buffer: &[Vec<Rgb>], out: &mut W) -> io::Result<()> {
+    let height = frame_buffer.len();
+    let width = frame_buffer.first().map_or(0, Vec::len);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for &(r, g, b) in frame_buffer.iter().flatten() {
+        pixels.extend_from_slice(&[r, g, b]);
+    }
+    out.write_all(&pixels)
+}
+
+// A frame of palette colors as RGB colors
+fn to_rgb(frame_buffer: &[Vec<u8>]) -> Vec<Vec<Rgb>> {
+    frame_buffer
+        .iter()
+        .map(|row| row.iter().map(|&color| palette::to_rgb(color)).collect())
+        .collect()
+}
+
+// A frame of RGB colors matched to the palette, for output that can only
+// show palette colors
+fn to_codes(frame_buffer: &[Vec<Rgb>]) -> Vec<Vec<u8>> {
+    frame_buffer
+        .iter()
+        .map(|row| row.iter().map(|&(r, g, b)| palette::from_rgb(r, g, b)).collect())
+        .collect()
+}
+
+// Two pixels per character cell: the upper pixel is the foreground color of
+// a ▀ and the lower pixel its background
+fn encode_half_block<P: EscapeColor>(frame_buffer: &[Vec<P>]) -> String {
+    let screen_height = frame_buffer.len().div_ceil(2);
+    let screen_width = frame_buffer.first().map_or(0, Vec::len);
+    // Use ▀ (upper half) and ▄ (lower half) to get 2 pixels per character
+    let mut output = String::with_capacity(screen_width * screen_height * 30);
+    
+    let mut current = None;
+    
+    for y in 0..screen_height {
+        let upper_row = &frame_buffer[y * 2];
+        let lower_row = frame_buffer.get(y * 2 + 1).unwrap_or(upper_row); // Fallback if out of bounds
+        
+        for (&upper_color, &lower_color) in upper_row.iter().zip(lower_row) {
+            // Set foreground (upper half) and background (lower half) colors
+            if current != Some((upper_color, lower_color)) {
+                upper_color.push_sgr(38, &mut output);
+                lower_color.push_sgr(48, &mut output);
+                current = Some((upper_color, lower_color));
+            }
+            
+            // Use upper half block character (▀) - shows upper color as foreground, lower as background
+            output.push('▀');
+        }
+        
+        // Reset color at end of line and move to next
+        if y < screen_height - 1 {
+            output.push_str("\x1b[0m\r\n");
+            current = None;
+        }
+    }
+    
+    // Reset color at the end of the frame
+    output.push_str("\x1b[0m");
+    output
+}
+
+// One pixel per character cell, drawn as a space with the pixel as the
+// background color so there are no gaps between cells regardless of font
+fn encode_full_block<P: EscapeColor>(frame_buffer: &[Vec<P>]) -> String {
+    let screen_width = frame_buffer.first().map_or(0, Vec::len);
+    let mut output = String::with_capacity(screen_width * frame_buffer.len() * 15);
+    
+    for (y, row) in frame_buffer.iter().enumerate() {
+        let mut current_bg = None;
+        for &color in row {
+            if current_bg != Some(color) {
+                color.push_sgr(48, &mut output);
+                current_bg = Some(color);
+            }
+            output.push(' ');
+        }
+        
+        // Reset color at end of line and move to next
+        if y + 1 < frame_buffer.len() {
+            output.push_str("\x1b[0m\r\n");
+        }
+    }
+    
+    output.push_str("\x1b[0m");
+    output
+}
+
+// Eight pixels per character cell, as the dots of a braille character in
+// the foreground color over the background color
+fn encode_braille<P: EscapeColor>(frame_buffer: &[Vec<P>]) -> String {
+    let cells = braille_cells(frame_buffer);
+    let screen_width = cells.first().map_or(0, Vec::len);
+    let mut output = String::with_capacity(screen_width * cells.len() * 30);
+    
+    for (y, row) in cells.iter().enumerate() {
+        let mut current = None;
+        for &(symbol, fg, bg) in row {
+            if current != Some((fg, bg)) {
+                fg.push_sgr(38, &mut output);
+                bg.push_sgr(48, &mut output);
+                current = Some((fg, bg));
+            }
+            output.push(symbol);
+        }
+        
+        if y + 1 < cells.len() {
+            output.push_str("\x1b[0m\r\n");
+        }
+    }
+    
+    output.push_str("\x1b[0m");
+    output
+}
+
+// Group a frame buffer into 2x4 pixel blocks, each as a braille character
+// with its foreground and background colors. Pixels brighter than the
+// block's average are its dots; a block of one brightness has none.
+fn braille_cells<P: EscapeColor>(frame_buffer: &[Vec<P>]) -> Vec<Vec<(char, P, P)>> {
+    let width = frame_buffer.first().map_or(0, Vec::len);
+    frame_buffer
+        .chunks(4)
+        .map(|rows| {
+            (0..width.div_ceil(2))
+                .map(|column| {
+                    let pixels: Vec<(u32, P)> = BRAILLE_DOTS
+                        .iter()
+                        .filter_map(|&(dx, dy, bit)| {
+                            rows.get(dy)?.get(column * 2 + dx).map(|&pixel| (bit, pixel))
+                        })
+                        .collect();
+                    braille_cell(&pixels)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Braille character and colors for the pixels of one block, each with the
+// bit of its dot
+fn braille_cell<P: EscapeColor>(pixels: &[(u32, P)]) -> (char, P, P) {
+    let brightness: Vec<f64> = pixels.iter().map(|&(_, pixel)| palette::luma(pixel.rgb())).collect();
+    let mean = brightness.iter().sum::<f64>() / pixels.len() as f64;
+    let (dots, rest): (Vec<_>, Vec<_>) = pixels
+        .iter()
+        .zip(&brightness)
+        .partition(|&(_, &brightness)| brightness > mean);
+    let bg = average(rest.iter().map(|(&(_, pixel), _)| pixel));
+    let fg = if dots.is_empty() { bg } else { average(dots.iter().map(|(&(_, pixel), _)| pixel)) };
+    let bits = dots.iter().fold(0, |bits, (&(bit, _), _)| bits | bit);
+    (char::from_u32(BRAILLE_BLANK + bits).unwrap_or(' '), fg, bg)
+}
+
+// Mean color of some pixels, which must not be empty
+fn average<P: EscapeColor>(pixels: impl Iterator<Item = P>) -> P {
+    let mut count = 0;
+    let mut sum = [0u32; 3];
+    for pixel in pixels {
+        let (r, g, b) = pixel.rgb();
+        sum[0] += r as u32;
+        sum[1] += g as u32;
+        sum[2] += b as u32;
+        count += 1;
+    }
+    let mean = |total: u32| (total / count.max(1)) as u8;
+    P::from_rgb((mean(sum[0]), mean(sum[1]), mean(sum[2])))
+}
+
+// Frames of characters, such as ASCII frames, as text with rows separated
+// by CRLF and no color codes
+pub fn encode_text(frame_buffer: &[Vec<char>]) -> String {
+    let screen_width = frame_buffer.first().map_or(0, Vec::len);
+    let mut output = String::with_capacity((screen_width + 2) * frame_buffer.len());
+    for (y, row) in frame_buffer.iter().enumerate() {
+        output.extend(row);
+        if y + 1 < frame_buffer.len() {
+            output.push_str("\r\n");
+        }
+    }
+    output
+}
+
+// Frames of characters as cells, drawn in plain white on black as a
+// terminal without colors shows them
+pub fn text_cells(frame_buffer: &[Vec<char>]) -> Vec<Vec<Cell>> {
+    frame_buffer
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&symbol| Cell {
+                    symbol,
+                    fg: ASCII_CELL_FG,
+                    bg: ASCII_CELL_BG,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Ramp character for a brightness from 0 (a space) to 1 (`@`)
+pub fn ascii_shade(brightness: f64) -> char {
+    let last = (ASCII_RAMP.len() - 1) as f64;
+    ASCII_RAMP[((1.0 - brightness.clamp(0.0, 1.0)) * last).round() as usize] as char
+}
+
+// Brightness a ramp character stands for, the inverse of `ascii_shade`;
+// characters outside the ramp count as full brightness
+pub fn ascii_brightness(symbol: char) -> f64 {
+    let last = (ASCII_RAMP.len() - 1) as f64;
+    ASCII_RAMP
+        .iter()
+        .position(|&ramp| ramp as char == symbol)
+        .map_or(1.0, |index| 1.0 - index as f64 / last)
+}
+
+fn encode_ascii<P: EscapeColor>(frame_buffer: &[Vec<P>]) -> String {
+    encode_text(&to_ascii(frame_buffer))
+}
+
+// A frame of colors as ramp characters by brightness
+fn to_ascii<P: EscapeColor>(frame_buffer: &[Vec<P>]) -> Vec<Vec<char>> {
+    frame_buffer
+        .iter()
+        .map(|row| row.iter().map(|&color| ascii_shade(palette::luma(color.rgb()))).collect())
+        .collect()
+}
+
+#[cfg(all(test, feature = "ratatui"))]
+mod tests {
+    use super::*;
+    use ratatui::buffer::Buffer;
+    use ratatui::layout::Rect;
+    use ratatui::style::Color;
+
+    #[test]
+    fn cells_are_written_into_the_area_and_clipped_to_it() {
+        let cell = Cell { symbol: '▀', fg: 196, bg: 21 };
+        let cells = vec![vec![cell; 4]; 3];
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 5, 5));
+        write_cells(&cells, Rect::new(2, 3, 4, 3), &mut buffer);
+        let written = &buffer[(2, 3)];
+        assert_eq!(written.symbol(), "▀");
+        assert_eq!((written.fg, written.bg), (Color::Indexed(196), Color::Indexed(21)));
+        assert_eq!(buffer[(4, 4)].symbol(), "▀");
+        assert_eq!(buffer[(1, 3)].symbol(), " ");
+    }
+}
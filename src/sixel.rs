@@ -0,0 +1,99 @@
+// Sixel graphics: frames sent to the terminal as bitmaps instead of
+// characters, on terminals that can show them (foot, mlterm, WezTerm,
+// xterm started with `-ti vt340`). Images are built from bands six pixels
+// tall, each band drawn once per color it uses.
+
+use std::fmt::Write as _;
+
+use crate::palette;
+
+pub const SCALE: usize = 2; // Screen pixels per rendered pixel each way, to keep frames fast
+const BAND_HEIGHT: usize = 6;
+const SIXEL_OFFSET: u8 = 63; // A band column's dots are sent as this plus their bits
+
+// Whether the environment names a terminal known to show sixel images.
+// xterm only does when started with `-ti vt340`, which can't be seen from
+// here, so it has to be asked for.
+pub fn detect() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel")
+        || matches!(program.as_str(), "WezTerm" | "mintty" | "iTerm.app")
+}
+
+// Encode a frame buffer as a sixel image, each pixel drawn `SCALE` screen
+// pixels square. The cursor is left at the start of the character row
+// below the image, as it is after a frame of text rows.
+pub fn encode(frame_buffer: &[Vec<u8>], cell_height: usize) -> String {
+    let width = frame_buffer.first().map_or(0, Vec::len);
+    let rows: Vec<&Vec<u8>> = frame_buffer.iter().flat_map(|row| std::iter::repeat(row).take(SCALE)).collect();
+    let mut output = String::with_capacity(width * rows.len());
+
+    // Save the cursor, since terminals leave it in different places after
+    // an image, and set the image size so its background is filled
+    let _ = write!(output, "\x1b7\x1bPq\"1;1;{};{}", width * SCALE, rows.len());
+    let mut used = [false; 256];
+    for &color in frame_buffer.iter().flatten() {
+        used[color as usize] = true;
+    }
+    for color in (0..=255u8).filter(|&color| used[color as usize]) {
+        let (r, g, b) = palette::to_rgb(color);
+        let percent = |channel: u8| channel as u32 * 100 / 255;
+        let _ = write!(output, "#{};2;{};{};{}", color, percent(r), percent(g), percent(b));
+    }
+
+    for band in rows.chunks(BAND_HEIGHT) {
+        let mut colors = [false; 256];
+        for &color in band.iter().flat_map(|row| row.iter()) {
+            colors[color as usize] = true;
+        }
+        for color in (0..=255u8).filter(|&color| colors[color as usize]) {
+            let _ = write!(output, "#{}", color);
+            let dots = (0..width).map(|x| {
+                band.iter()
+                    .enumerate()
+                    .filter(|(_, row)| row[x] == color)
+                    .fold(0u8, |bits, (y, _)| bits | 1 << y)
+            });
+            push_runs(&mut output, dots);
+            output.push('$'); // Back to the start of the band for the next color
+        }
+        output.push('-'); // On to the next band
+    }
+    output.push_str("\x1b\\\x1b8");
+
+    let text_rows = (rows.len()).div_ceil(cell_height.max(1));
+    if text_rows > 0 {
+        let _ = write!(output, "\x1b[{}B", text_rows);
+    }
+    output
+}
+
+// Append a band's columns of dots for one color, run-length encoded, each
+// column repeated `SCALE` times
+fn push_runs(output: &mut String, dots: impl Iterator<Item = u8>) {
+    let mut run: Option<(u8, usize)> = None;
+    for bits in dots {
+        run = match run {
+            Some((current, count)) if current == bits => Some((current, count + SCALE)),
+            Some(finished) => {
+                push_run(output, finished);
+                Some((bits, SCALE))
+            }
+            None => Some((bits, SCALE)),
+        };
+    }
+    // Columns with no dots at the end of the band can be left out
+    if let Some(run) = run.filter(|&(bits, _)| bits != 0) {
+        push_run(output, run);
+    }
+}
+
+fn push_run(output: &mut String, (bits, count): (u8, usize)) {
+    let symbol = (SIXEL_OFFSET + bits) as char;
+    if count > 3 {
+        let _ = write!(output, "!{}{}", count, symbol);
+    } else {
+        (0..count).for_each(|_| output.push(symbol));
+    }
+}
@@ -0,0 +1,80 @@
+// Shapes of billboard sprites. Each kind has a size relative to a wall and
+// an outline, shaded so round things look round even though every sprite is
+// a flat cutout turned to face the viewer.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpriteKind {
+    // The plain round marker used for teleporters, exits, enemies and the
+    // built-in maps' decorations
+    #[default]
+    Orb,
+    Pillar,
+    Barrel,
+}
+
+impl SpriteKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "orb" => Some(SpriteKind::Orb),
+            "pillar" => Some(SpriteKind::Pillar),
+            "barrel" => Some(SpriteKind::Barrel),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpriteKind::Orb => "orb",
+            SpriteKind::Pillar => "pillar",
+            SpriteKind::Barrel => "barrel",
+        }
+    }
+
+    // Color used when a map doesn't give one, as a 256-color code
+    pub fn default_color(self) -> u8 {
+        match self {
+            SpriteKind::Orb => 226,    // Yellow
+            SpriteKind::Pillar => 250, // Pale stone
+            SpriteKind::Barrel => 130, // Brown wood
+        }
+    }
+
+    // Width and height as fractions of a full-height wall
+    pub fn size(self) -> (f64, f64) {
+        match self {
+            SpriteKind::Orb => (0.6, 0.6),
+            SpriteKind::Pillar => (0.3, 1.0),
+            SpriteKind::Barrel => (0.4, 0.5),
+        }
+    }
+
+    // Brightness of the sprite at a point of its bounding box, where `u` runs
+    // from -1 (left) to 1 (right) and `v` from -1 (top) to 1 (bottom), or
+    // None where the point is outside its outline
+    pub fn shade(self, u: f64, v: f64) -> Option<f64> {
+        match self {
+            SpriteKind::Orb => (u * u + v * v <= 1.0).then_some(1.0),
+            SpriteKind::Pillar => {
+                // A shaft between a wider capital and base
+                if v.abs() > 0.88 {
+                    (u.abs() <= 1.0).then_some(0.85)
+                } else {
+                    let u = u / 0.7;
+                    (u.abs() <= 1.0).then_some(1.0 - 0.4 * u * u)
+                }
+            }
+            SpriteKind::Barrel => {
+                // Bulging staves with dark hoops a little way from each end
+                let half_width = 0.85 + 0.15 * (1.0 - v * v);
+                let u = u / half_width;
+                if u.abs() > 1.0 {
+                    None
+                } else if (0.5..0.62).contains(&v.abs()) {
+                    Some(0.55)
+                } else {
+                    Some(1.0 - 0.35 * u * u)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+// Top-down SVG drawings of maps, for documenting levels and checking what a
+// generator built. Cells are drawn in the map editor's colors, with any wall
+// colors the map sets, sprites are dots the width of the sprite, and spawn
+// points are marked with their facing. The route the player took can be
+// drawn over the top.
+
+use std::fmt::Write;
+
+use crate::mapfile::MapFile;
+use crate::palette;
+
+const CELL_SIZE: f64 = 16.0; // Pixels per map cell
+const THIN_WALL_WIDTH: f64 = 3.0; // Stroke width of thin and diagonal walls
+const SPAWN_RADIUS: f64 = 0.3; // In cells
+const SPAWN_COLOR: u8 = 39; // Sky blue
+const PATH_COLOR: u8 = 226; // Yellow
+const PATH_WIDTH: f64 = 2.0;
+const TELEPORTER_TEXT_COLOR: u8 = 231; // White
+
+// The map as an SVG document. `explored` holds runs of points the player
+// passed through, each drawn as a line; it can be empty.
+pub fn map_svg(map: &MapFile, explored: &[Vec<(f64, f64)>]) -> String {
+    let walls = &map.walls;
+    let (width, height) = (walls.width() as f64 * CELL_SIZE, walls.height() as f64 * CELL_SIZE);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = width,
+        h = height
+    );
+    let _ = writeln!(svg, "<title>{}</title>", escape(&map.name));
+    let _ = writeln!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>", width, height, hex(cell_color(map, b'0')));
+
+    for ((x, y), cell) in walls.cells() {
+        let (left, top) = (x as f64 * CELL_SIZE, y as f64 * CELL_SIZE);
+        let (right, bottom) = (left + CELL_SIZE, top + CELL_SIZE);
+        let color = hex(cell_color(map, cell));
+        match cell {
+            b'0' => {}
+            b'/' | b'\\' | b'-' | b'|' => {
+                let (x1, y1, x2, y2) = match cell {
+                    b'/' => (left, bottom, right, top),
+                    b'\\' => (left, top, right, bottom),
+                    b'-' => (left, top + CELL_SIZE / 2.0, right, top + CELL_SIZE / 2.0),
+                    _ => (left + CELL_SIZE / 2.0, top, left + CELL_SIZE / 2.0, bottom),
+                };
+                let _ = writeln!(
+                    svg,
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+                    x1, y1, x2, y2, color, THIN_WALL_WIDTH
+                );
+            }
+            _ => {
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    left, top, CELL_SIZE, CELL_SIZE, color
+                );
+            }
+        }
+        if cell.is_ascii_uppercase() {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+                left + CELL_SIZE / 2.0,
+                top + CELL_SIZE / 2.0,
+                CELL_SIZE * 0.75,
+                hex(TELEPORTER_TEXT_COLOR),
+                cell as char
+            );
+        }
+    }
+    for &((x, y), hazard) in &map.hazards {
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            x as f64 * CELL_SIZE,
+            y as f64 * CELL_SIZE,
+            CELL_SIZE,
+            CELL_SIZE,
+            hex(hazard.color(0, 0.0, 0.0))
+        );
+    }
+
+    for sprite in &map.sprites {
+        let _ = writeln!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>",
+            sprite.x * CELL_SIZE,
+            sprite.y * CELL_SIZE,
+            sprite.kind.size().0 / 2.0 * CELL_SIZE,
+            hex(sprite.color)
+        );
+    }
+
+    for run in explored.iter().filter(|run| run.len() > 1) {
+        let points: Vec<String> = run
+            .iter()
+            .map(|&(x, y)| format!("{:.1},{:.1}", x * CELL_SIZE, y * CELL_SIZE))
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\"/>",
+            points.join(" "),
+            hex(PATH_COLOR),
+            PATH_WIDTH
+        );
+    }
+
+    // Spawn points as a dot with a line showing which way it faces
+    for &(x, y, angle) in &map.spawns {
+        let (cx, cy) = (x * CELL_SIZE, y * CELL_SIZE);
+        let radius = SPAWN_RADIUS * CELL_SIZE;
+        let (sin, cos) = angle.sin_cos();
+        let color = hex(SPAWN_COLOR);
+        let _ = writeln!(svg, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>", cx, cy, radius, color);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            cx,
+            cy,
+            cx + cos * radius * 2.0,
+            cy + sin * radius * 2.0,
+            color,
+            PATH_WIDTH
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Color of a map character: the map's own color for a wall material, or
+// the editor's
+fn cell_color(map: &MapFile, cell: u8) -> u8 {
+    let material = cell.wrapping_sub(b'0');
+    map.wall_colors
+        .iter()
+        .find(|&&(colored, _)| colored == material)
+        .map_or_else(|| crate::editor::cell_color(cell), |&(_, color)| color)
+}
+
+fn hex(color: u8) -> String {
+    let (r, g, b) = palette::to_rgb(color);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Text with the characters that are special in XML escaped
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
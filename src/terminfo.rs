@@ -0,0 +1,78 @@
+// Just enough of the compiled terminfo format to read how many colors a
+// terminal type supports, without linking a curses library.
+
+use std::path::PathBuf;
+
+const MAGIC_16BIT: i16 = 0o432; // Numbers are 16-bit
+const MAGIC_32BIT: i16 = 0o1036; // Numbers are 32-bit (ncurses 6.1 extended format)
+const MAX_COLORS: usize = 13; // Index of the `colors` number capability
+const SYSTEM_DIRS: [&str; 4] = ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"];
+
+// Colors the terminfo entry for `term` says the terminal supports, or None
+// if there is no entry or it doesn't say
+pub fn max_colors(term: &str) -> Option<u32> {
+    let first = term.chars().next()?;
+    if term.contains('/') || term.contains("..") {
+        return None;
+    }
+    search_dirs().into_iter().find_map(|dir| {
+        // Entries are filed by first letter, or by its hex code on macOS
+        let entry = std::fs::read(dir.join(first.to_string()).join(term))
+            .or_else(|_| std::fs::read(dir.join(format!("{:x}", first as u32)).join(term)))
+            .ok()?;
+        parse_max_colors(&entry)
+    })
+}
+
+// Directories searched for entries, in the order ncurses searches them
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(list) = std::env::var("TERMINFO_DIRS") {
+        for dir in list.split(':') {
+            // An empty element stands for the system directories
+            if dir.is_empty() {
+                dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+            } else {
+                dirs.push(PathBuf::from(dir));
+            }
+        }
+    }
+    dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+fn parse_max_colors(entry: &[u8]) -> Option<u32> {
+    let header = |index: usize| -> Option<i16> {
+        let bytes = entry.get(index * 2..index * 2 + 2)?;
+        Some(i16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let number_size = match header(0)? {
+        MAGIC_16BIT => 2,
+        MAGIC_32BIT => 4,
+        _ => return None,
+    };
+    let names_size = usize::try_from(header(1)?).ok()?;
+    let bool_count = usize::try_from(header(2)?).ok()?;
+    let number_count = usize::try_from(header(3)?).ok()?;
+    if MAX_COLORS >= number_count {
+        return None;
+    }
+
+    // Numbers start on an even byte after the names and booleans
+    let mut offset = 12 + names_size + bool_count;
+    offset += offset % 2;
+    let start = offset + MAX_COLORS * number_size;
+    let bytes = entry.get(start..start + number_size)?;
+    let colors = match number_size {
+        2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+        _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    };
+    // Negative values mean the capability is absent or cancelled
+    u32::try_from(colors).ok()
+}
@@ -0,0 +1,235 @@
+// Procedural wall textures. Each pattern maps a point on a wall face to a
+// brightness level that modulates the wall's base color. Image textures,
+// loaded from files or generated, give walls colors of their own.
+
+// Brightness factors for the shades a pattern can produce
+pub const SHADES: [f64; 3] = [1.0, 0.8, 0.55];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WallTexture {
+    Brick,
+    Checker,
+    Stripes,
+}
+
+impl WallTexture {
+    // Texture used for a map material, or None for plain walls
+    pub fn for_material(material: u8) -> Option<Self> {
+        match material {
+            1 => Some(WallTexture::Brick),
+            2 => Some(WallTexture::Checker),
+            3 => Some(WallTexture::Stripes),
+            _ => None,
+        }
+    }
+
+    // Shade index into SHADES at horizontal wall coordinate `u` and vertical
+    // coordinate `v` (both in wall units; the pattern repeats every unit)
+    pub fn sample(self, u: f64, v: f64) -> usize {
+        let u = u.rem_euclid(1.0);
+        let v = v.rem_euclid(1.0);
+        match self {
+            WallTexture::Brick => {
+                // Four courses of bricks, every other course offset by half a brick
+                const MORTAR: f64 = 0.06;
+                let course = (v * 4.0).floor();
+                let course_v = (v * 4.0).fract();
+                let offset = if course as i32 % 2 == 0 { 0.0 } else { 0.25 };
+                let brick_u = ((u + offset) * 2.0).fract();
+                if course_v < MORTAR * 2.0 || brick_u < MORTAR {
+                    2
+                } else {
+                    0
+                }
+            }
+            WallTexture::Checker => {
+                let cell = (u * 4.0).floor() as i32 + (v * 4.0).floor() as i32;
+                if cell % 2 == 0 {
+                    0
+                } else {
+                    1
+                }
+            }
+            WallTexture::Stripes => {
+                if (u * 8.0).floor() as i32 % 2 == 0 {
+                    0
+                } else {
+                    1
+                }
+            }
+        }
+    }
+}
+
+// See-through material, drawn as a tinted slice over whatever lies behind it
+#[derive(Clone, Copy)]
+pub struct Translucency {
+    pub tint: u8, // 256-color code
+    pub opacity: f64, // 0.0 (invisible) to 1.0 (opaque)
+    pub solid: bool, // Whether it blocks movement
+}
+
+impl Translucency {
+    // Translucency of a map material, or None for opaque walls
+    pub fn for_material(material: u8) -> Option<Self> {
+        match material {
+            4 => Some(Translucency { tint: 51, opacity: 0.3, solid: true }), // Glass
+            5 => Some(Translucency { tint: 27, opacity: 0.45, solid: false }), // Water curtain
+            _ => None,
+        }
+    }
+}
+
+// Wall texture loaded from an image file or generated, stored as RGB texels
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<(u8, u8, u8)>,
+}
+
+impl ImageTexture {
+    // The texture a map or `--texture` names: a generated one for the name
+    // of a `GeneratedTexture`, otherwise an image file, which needs the
+    // `image-textures` feature
+    pub fn open(path: &std::path::Path) -> Result<Self, crate::RaycastError> {
+        if let Some(generated) = path.to_str().and_then(GeneratedTexture::from_name) {
+            return Ok(generated.generate());
+        }
+        #[cfg(feature = "image-textures")]
+        return Self::load(path);
+        #[cfg(not(feature = "image-textures"))]
+        Err(crate::RaycastError::Config(format!(
+            "texture {}: image files require building with the image-textures feature",
+            path.display()
+        )))
+    }
+
+    // Load a PNG or PPM image. Requires the `image-textures` feature.
+    #[cfg(feature = "image-textures")]
+    pub fn load(path: &std::path::Path) -> Result<Self, crate::RaycastError> {
+        let image = image::open(path)
+            .map_err(|err| crate::RaycastError::Config(format!("texture {}: {}", path.display(), err)))?
+            .to_rgb8();
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(crate::RaycastError::Config(format!("texture {}: image is empty", path.display())));
+        }
+        
+        Ok(ImageTexture {
+            width: width as usize,
+            height: height as usize,
+            pixels: image.pixels().map(|p| (p[0], p[1], p[2])).collect(),
+        })
+    }
+
+    // Texel at wall coordinates `u` and `v` (wall units, repeating every unit)
+    pub fn sample(&self, u: f64, v: f64) -> (u8, u8, u8) {
+        let x = ((u.rem_euclid(1.0) * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v.rem_euclid(1.0) * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Textures generated at startup, for walls that look textured without any
+// image files. They are named in place of a file and then drawn exactly like
+// loaded images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeneratedTexture {
+    Brick,
+    Checker,
+    Stone,
+}
+
+const GENERATED_SIZE: usize = 64; // Width and height of generated textures, in texels
+const BRICK_COLOR: (u8, u8, u8) = (150, 62, 40);
+const BRICK_VARIATION: f64 = 0.15; // Bricks are up to this much lighter or darker than BRICK_COLOR
+const MORTAR_COLOR: (u8, u8, u8) = (172, 166, 156);
+const CHECKER_COLORS: [(u8, u8, u8); 2] = [(205, 205, 205), (85, 85, 95)];
+const STONE_COLOR: (u8, u8, u8) = (138, 132, 122);
+const STONE_OCTAVES: u32 = 4; // Layers of noise, each with twice the detail of the last
+
+impl GeneratedTexture {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "brick" => Some(GeneratedTexture::Brick),
+            "checker" => Some(GeneratedTexture::Checker),
+            "stone" => Some(GeneratedTexture::Stone),
+            _ => None,
+        }
+    }
+
+    pub fn generate(self) -> ImageTexture {
+        let mut pixels = Vec::with_capacity(GENERATED_SIZE * GENERATED_SIZE);
+        for y in 0..GENERATED_SIZE {
+            for x in 0..GENERATED_SIZE {
+                let (u, v) = (x as f64 / GENERATED_SIZE as f64, y as f64 / GENERATED_SIZE as f64);
+                pixels.push(self.texel(u, v));
+            }
+        }
+        ImageTexture {
+            width: GENERATED_SIZE,
+            height: GENERATED_SIZE,
+            pixels,
+        }
+    }
+
+    // Color at wall coordinates `u` and `v`, both from 0 to 1
+    fn texel(self, u: f64, v: f64) -> (u8, u8, u8) {
+        match self {
+            GeneratedTexture::Brick => {
+                // The same layout as the brick pattern, with every brick a
+                // slightly different shade
+                const MORTAR: f64 = 0.06;
+                let course = (v * 4.0).floor() as i32;
+                let offset = if course % 2 == 0 { 0.0 } else { 0.25 };
+                let brick = ((u + offset) * 2.0).floor() as i32 % 2;
+                if (v * 4.0).fract() < MORTAR * 2.0 || ((u + offset) * 2.0).fract() < MORTAR {
+                    return MORTAR_COLOR;
+                }
+                let shade = 1.0 + BRICK_VARIATION * (2.0 * lattice(brick, course, 0) - 1.0);
+                scale_rgb(BRICK_COLOR, shade)
+            }
+            GeneratedTexture::Checker => {
+                let cell = (u * 4.0).floor() as usize + (v * 4.0).floor() as usize;
+                CHECKER_COLORS[cell % 2]
+            }
+            GeneratedTexture::Stone => {
+                // Fractal value noise, darkening and lightening the stone
+                let mut noise = 0.0;
+                let mut weight = 0.5;
+                for octave in 0..STONE_OCTAVES {
+                    noise += weight * value_noise(u, v, 4 << octave, octave);
+                    weight /= 2.0;
+                }
+                scale_rgb(STONE_COLOR, 0.5 + noise)
+            }
+        }
+    }
+}
+
+// Smoothly interpolated noise from 0 to 1 over a `cells` x `cells` lattice
+// that wraps around, so the texture tiles seamlessly
+fn value_noise(u: f64, v: f64, cells: i32, seed: u32) -> f64 {
+    let (x, y) = (u * cells as f64, v * cells as f64);
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0 as f64), smooth(y - y0 as f64));
+    let corner = |dx: i32, dy: i32| lattice((x0 + dx).rem_euclid(cells), (y0 + dy).rem_euclid(cells), seed);
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    top + (bottom - top) * ty
+}
+
+// Pseudo-random value from 0 to 1 for a lattice point
+fn lattice(x: i32, y: i32, seed: u32) -> f64 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1_e995);
+    hash ^= hash >> 15;
+    hash as f64 / u32::MAX as f64
+}
+
+fn scale_rgb((r, g, b): (u8, u8, u8), factor: f64) -> (u8, u8, u8) {
+    let channel = |c: u8| (c as f64 * factor).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
@@ -0,0 +1,102 @@
+// The weapon held in front of the player, drawn over the bottom middle of
+// the 3D view, and the walking cycle it moves with. It sways from side to
+// side and bobs as the player walks and settles back when they stop; the
+// camera bobs along with it.
+
+use std::f64::consts::PI;
+
+use crate::raycaster::Player;
+use crate::render::Overlay;
+
+// Pistol in a hand, seen from behind. Each character is a pixel of the
+// colors below; `.` is see-through.
+const WEAPON: [&str; 13] = [
+    "......#o#......",
+    "......#O#......",
+    "......#O#......",
+    ".....#OOO#.....",
+    ".....#OoO#.....",
+    ".....#OOO#.....",
+    "....##OOO##....",
+    "...#ssOOOss#...",
+    "..#sSSsOsSSs#..",
+    "..#sSSSSSSSs#..",
+    ".#sSSSSSSSSSs#.",
+    ".#sSSSSSSSSSs#.",
+    "#sSSSSSSSSSSSs#",
+];
+const HEIGHT: f64 = 0.35; // Height of the weapon as a fraction of the view
+const SWAY: f64 = 0.15; // Side-to-side swing as a fraction of the weapon's width
+const BOB: f64 = 0.08; // Up-and-down bob as a fraction of the weapon's height
+const STRIDE: f64 = 1.2; // Cells walked per swing from one side to the other and back
+const SWING_EASE: f64 = 0.15; // Fraction of the way the swing moves towards walking or rest per frame
+const MAX_STEP: f64 = 1.0; // Moves longer than this in one frame, such as teleports, aren't walking
+
+fn color(pixel: u8) -> Option<u8> {
+    match pixel {
+        b'#' => Some(234), // Near-black outline
+        b'O' => Some(244), // Gun metal
+        b'o' => Some(250), // Light catching the metal
+        b'S' => Some(180), // Skin
+        b's' => Some(137), // Skin in shadow
+        _ => None,
+    }
+}
+
+// Where the player is in their stride, driving the weapon's sway and the
+// head-bob
+#[derive(Clone, Debug, Default)]
+pub struct WalkCycle {
+    phase: f64, // Radians through the walking cycle
+    swing: f64, // How far the sway and bob have built up, from 0 at rest to 1 walking
+    last_position: Option<(f64, f64)>,
+}
+
+impl WalkCycle {
+    // Advance the cycle by how far the player moved this frame
+    pub fn update(&mut self, player: &Player) {
+        let position = (player.x, player.y);
+        let step = self
+            .last_position
+            .map_or(0.0, |(x, y)| (position.0 - x).hypot(position.1 - y));
+        self.last_position = Some(position);
+        let walking = step > 0.0 && step <= MAX_STEP;
+        if walking {
+            self.phase = (self.phase + step / STRIDE * 2.0 * PI) % (2.0 * PI);
+        }
+        let target = if walking { 1.0 } else { 0.0 };
+        self.swing += (target - self.swing) * SWING_EASE;
+    }
+
+    // Side-to-side swing from -1 to 1, once each way per stride
+    pub fn sway(&self) -> f64 {
+        self.phase.sin() * self.swing
+    }
+
+    // Up-and-down bob from 0 to 1, dipping with each footstep
+    pub fn bob(&self) -> f64 {
+        (1.0 - (2.0 * self.phase).cos()) / 2.0 * self.swing
+    }
+}
+
+// The weapon as a layer over a view of the given size in pixels
+pub fn overlay(walk: &WalkCycle, width: usize, height: usize) -> Overlay {
+    let art_width = WEAPON[0].len();
+    let scale = ((height as f64 * HEIGHT / WEAPON.len() as f64).round() as usize).max(1);
+    let (weapon_width, weapon_height) = (art_width * scale, WEAPON.len() * scale);
+
+    let left = (width as f64 - weapon_width as f64) / 2.0 + walk.sway() * SWAY * weapon_width as f64;
+    let top = height as f64 - weapon_height as f64 + walk.bob() * BOB * weapon_height as f64;
+
+    let pixels = (0..weapon_height)
+        .map(|y| {
+            let row = WEAPON[y / scale].as_bytes();
+            (0..weapon_width).map(|x| color(row[x / scale])).collect()
+        })
+        .collect();
+    Overlay {
+        left: left.round() as isize,
+        top: top.round() as isize,
+        pixels,
+    }
+}
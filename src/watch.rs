@@ -0,0 +1,48 @@
+// Watching a file for changes by polling its modification time, so levels
+// can be reloaded while they are being edited in another program.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500); // Time between checks of the file
+
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>, // Modification time when last checked, None if unreadable
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> Self {
+        FileWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Whether the file has been modified since the last time this returned
+    // true. Checks at most once every POLL_INTERVAL; a file that has gone
+    // missing isn't a change, but its reappearance is.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+
+    // Treat the file as it is now as already seen, after writing it ourselves
+    pub fn mark_seen(&mut self) {
+        self.modified = modified(&self.path);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,17 @@
+// Windows: cells walled off by a grate of iron bars. Rays record the grate
+// and carry on through it, so the bars are drawn over whatever lies behind
+// and the view shows through the gaps. Nothing can walk through a window.
+
+pub const WINDOW_CELL: u8 = b'=';
+
+const BARS: f64 = 4.0; // Upright bars across each face
+const BAR_WIDTH: f64 = 0.3; // Fraction of the space between uprights taken by a bar
+const RAILS: [f64; 3] = [0.0, 0.5, 1.0]; // Heights of the cross rails, from the top of the wall down
+const RAIL_HEIGHT: f64 = 0.04; // Half the thickness of a cross rail
+
+// Whether a point on a window's face is covered by a bar, at horizontal
+// coordinate `u` and vertical coordinate `v` from the top (both 0.0-1.0)
+pub fn is_bar(u: f64, v: f64) -> bool {
+    let upright = (u * BARS + BAR_WIDTH / 2.0).fract() < BAR_WIDTH;
+    upright || RAILS.iter().any(|&rail| (v - rail).abs() < RAIL_HEIGHT)
+}
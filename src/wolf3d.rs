@@ -0,0 +1,184 @@
+// Wolfenstein 3D level importer. Levels are read from a game's GAMEMAPS file
+// using the MAPHEAD file beside it: plane 0 (walls) becomes the wall grid and
+// plane 1 (objects) gives the player's start and marks secret push-walls.
+
+use std::path::Path;
+
+use crate::door::DOOR_CELL;
+use crate::map::Map;
+use crate::pushwall::PUSH_WALL_CELL;
+use crate::mapfile::MapFile;
+use crate::RaycastError;
+
+const LEVEL_COUNT: usize = 100; // Level offsets listed in MAPHEAD
+const CARMACK_NEAR: u8 = 0xa7; // Compression tags in the high byte of a word
+const CARMACK_FAR: u8 = 0xa8;
+const LAST_WALL_TILE: u16 = 89; // Tiles 1-89 are walls, 90-101 doors and the rest floor
+const FIRST_DOOR_TILE: u16 = 90;
+const LAST_DOOR_TILE: u16 = 101;
+const PUSH_WALL_OBJECT: u16 = 98; // Object marking a wall as a secret push-wall
+const PLAYER_START: [(u16, f64); 4] = [
+    (19, -std::f64::consts::FRAC_PI_2), // Facing north
+    (20, 0.0), // East
+    (21, std::f64::consts::FRAC_PI_2), // South
+    (22, std::f64::consts::PI), // West
+];
+
+// Whether a path names a GAMEMAPS file, such as GAMEMAPS.WL6
+pub fn is_gamemaps(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("gamemaps"))
+}
+
+// Import level `level` (0-based) from a GAMEMAPS file. MAPHEAD is looked for
+// in the same directory with the same extension and case.
+pub fn load(gamemaps: &Path, level: usize) -> Result<MapFile, RaycastError> {
+    let lowercase = gamemaps.file_stem().is_some_and(|stem| stem == "gamemaps");
+    let mut maphead = gamemaps.with_file_name(if lowercase { "maphead" } else { "MAPHEAD" });
+    if let Some(extension) = gamemaps.extension() {
+        maphead.set_extension(extension);
+    }
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|err| RaycastError::Config(format!("map {}: {}", path.display(), err)))
+    };
+    let head = read(&maphead)?;
+    let maps = read(gamemaps)?;
+    let corrupt = |reason: &str| RaycastError::Config(format!("map {}: {}", gamemaps.display(), reason));
+
+    if level >= LEVEL_COUNT {
+        return Err(RaycastError::Config(format!("level {} out of range (1-{})", level + 1, LEVEL_COUNT)));
+    }
+    let rlew_tag = word(&head, 0).ok_or_else(|| corrupt("MAPHEAD is too short"))?;
+    let offset = head
+        .get(2 + level * 4..6 + level * 4)
+        .map(|bytes| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| corrupt("MAPHEAD is too short"))?;
+    if offset <= 0 {
+        return Err(RaycastError::Config(format!("level {} is empty", level + 1)));
+    }
+
+    // Level header: three plane offsets and lengths, the size and the name
+    let header = maps
+        .get(offset as usize..offset as usize + 38)
+        .ok_or_else(|| corrupt("level header is past the end of the file"))?;
+    let plane = |index: usize| -> Result<Vec<u16>, RaycastError> {
+        let start = u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().unwrap()) as usize;
+        let length = word(header, 12 + index * 2).unwrap_or(0) as usize;
+        let data = maps
+            .get(start..start + length)
+            .ok_or_else(|| corrupt("plane data is past the end of the file"))?;
+        let expanded = carmack_expand(data).ok_or_else(|| corrupt("bad compressed plane data"))?;
+        rlew_expand(&expanded, rlew_tag).ok_or_else(|| corrupt("bad compressed plane data"))
+    };
+    let width = word(header, 18).unwrap_or(0) as usize;
+    let height = word(header, 20).unwrap_or(0) as usize;
+    let name: String = header[22..38]
+        .iter()
+        .take_while(|&&byte| byte != 0)
+        .map(|&byte| byte as char)
+        .collect();
+    let walls = plane(0)?;
+    let objects = plane(1)?;
+    if walls.len() < width * height || objects.len() < width * height || width == 0 {
+        return Err(corrupt("level is smaller than its header says"));
+    }
+
+    let (start, angle) = objects
+        .iter()
+        .enumerate()
+        .find_map(|(index, tile)| {
+            let &(_, angle) = PLAYER_START.iter().find(|(start, _)| start == tile)?;
+            Some(((index % width, index / width), angle))
+        })
+        .unwrap_or(((width / 2, height / 2), 0.0));
+    let rows: Vec<Vec<u8>> = walls
+        .chunks(width)
+        .zip(objects.chunks(width))
+        .take(height)
+        .map(|(row, objects)| {
+            row.iter()
+                .zip(objects)
+                .map(|(&tile, &object)| match tile {
+                    1..=LAST_WALL_TILE if object == PUSH_WALL_OBJECT => PUSH_WALL_CELL,
+                    // Spread Wolf3D's wall graphics over the three textured materials
+                    1..=LAST_WALL_TILE => b'1' + ((tile - 1) % 3) as u8,
+                    FIRST_DOOR_TILE..=LAST_DOOR_TILE => DOOR_CELL,
+                    _ => b'0',
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(MapFile {
+        name,
+        spawns: vec![(start.0 as f64 + 0.5, start.1 as f64 + 0.5, angle)],
+        ..MapFile::from_walls(Map::from_rows(&rows).ok_or_else(|| corrupt("level has no tiles"))?)
+    })
+}
+
+// Little-endian 16-bit word at a byte offset
+fn word(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+// Undo Carmack compression. The output starts with its own length in bytes
+// followed by words that are either literal or copies of earlier output:
+// near copies count back from the end, far copies from the start.
+fn carmack_expand(data: &[u8]) -> Option<Vec<u8>> {
+    let length = word(data, 0)? as usize / 2;
+    let mut output: Vec<u16> = Vec::with_capacity(length);
+    let mut position = 2;
+    while output.len() < length {
+        let value = word(data, position)?;
+        position += 2;
+        let [count, tag] = value.to_le_bytes();
+        if tag != CARMACK_NEAR && tag != CARMACK_FAR {
+            output.push(value);
+            continue;
+        }
+        if count == 0 {
+            // An escaped literal whose high byte happens to be a tag
+            output.push(u16::from_le_bytes([*data.get(position)?, tag]));
+            position += 1;
+            continue;
+        }
+        let from = if tag == CARMACK_NEAR {
+            let back = *data.get(position)? as usize;
+            position += 1;
+            output.len().checked_sub(back)?
+        } else {
+            let from = word(data, position)? as usize;
+            position += 2;
+            from
+        };
+        for index in from..from + count as usize {
+            output.push(*output.get(index)?);
+        }
+    }
+    output.truncate(length);
+    Some(output.into_iter().flat_map(u16::to_le_bytes).collect())
+}
+
+// Undo RLEW compression: the tag word is followed by a repeat count and the
+// word to repeat; anything else is literal. The data starts with the
+// expanded length in bytes.
+fn rlew_expand(data: &[u8], tag: u16) -> Option<Vec<u16>> {
+    let length = word(data, 0)? as usize / 2;
+    let mut output = Vec::with_capacity(length);
+    let mut position = 2;
+    while output.len() < length {
+        let value = word(data, position)?;
+        position += 2;
+        if value == tag {
+            let count = word(data, position)? as usize;
+            let repeated = word(data, position + 2)?;
+            position += 4;
+            output.extend(std::iter::repeat(repeated).take(count));
+        } else {
+            output.push(value);
+        }
+    }
+    output.truncate(length);
+    Some(output)
+}
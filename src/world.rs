@@ -0,0 +1,165 @@
+// Endless worlds streamed in chunks. The world is divided into square chunks
+// that are generated from the seed and their position when first needed.
+// Only the chunks around the player are kept, joined into a window that the
+// raycaster plays in; the window moves with the player, so memory use and
+// ray traversal stay bounded however far they roam.
+//
+// Each chunk is a room walled on its west and north sides, with the walls on
+// its east and south sides belonging to its neighbours. Every wall has a gap
+// or is left out altogether, so every chunk can be reached, and the room is
+// dotted with pillars that never touch each other or the walls.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::generate::Rng;
+use crate::map::Map;
+
+pub const CHUNK_SIZE: usize = 16; // Cells along each side of a chunk
+const WINDOW_RADIUS: i64 = 2; // Chunks kept on each side of the player's chunk
+const WINDOW_CHUNKS: usize = 2 * WINDOW_RADIUS as usize + 1; // Chunks along each side of the window
+const OPEN_EDGE_CHANCE: usize = 4; // One in this many chunk walls is left out
+const PILLAR_CHANCE: usize = 6; // One in this many pillar spots has a pillar
+// Wall materials picked from for each chunk: every solid one, leaving out
+// glass and the water curtain
+const WALL_MATERIALS: [u8; 7] = [b'1', b'2', b'3', b'6', b'7', b'8', b'9'];
+
+pub struct World {
+    seed: u64,
+    // Chunks generated so far, or changed during play, each row by row
+    chunks: HashMap<(i64, i64), Vec<u8>>,
+    // Chunks changed during play, kept even once they leave the window
+    edited: HashSet<(i64, i64)>,
+    center: (i64, i64), // Chunk in the middle of the window
+}
+
+impl World {
+    pub fn new(seed: u64) -> Self {
+        World {
+            seed,
+            chunks: HashMap::new(),
+            edited: HashSet::new(),
+            center: (0, 0),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("World (seed {})", self.seed)
+    }
+
+    // World cell at the window's top-left corner
+    pub fn origin(&self) -> (i64, i64) {
+        let corner = |center: i64| (center - WINDOW_RADIUS) * CHUNK_SIZE as i64;
+        (corner(self.center.0), corner(self.center.1))
+    }
+
+    // Chunk holding a world cell
+    pub fn chunk_at(x: i64, y: i64) -> (i64, i64) {
+        (x.div_euclid(CHUNK_SIZE as i64), y.div_euclid(CHUNK_SIZE as i64))
+    }
+
+    pub fn center(&self) -> (i64, i64) {
+        self.center
+    }
+
+    // Number of chunks held in memory
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Where a new player starts, in window cells: the middle of the first
+    // chunk, which pillars never take
+    pub fn spawn(&self) -> (f64, f64) {
+        let (origin_x, origin_y) = self.origin();
+        let middle = (CHUNK_SIZE / 2 + 1) as f64 + 0.5;
+        (middle - origin_x as f64, middle - origin_y as f64)
+    }
+
+    // Move the window to be centered on a chunk, dropping chunks that fall
+    // outside it unless they have been changed. Returns how many cells the
+    // window's origin moved along each axis.
+    pub fn recenter(&mut self, center: (i64, i64)) -> (i64, i64) {
+        let before = self.origin();
+        self.center = center;
+        let after = self.origin();
+        let near = |(x, y): (i64, i64)| (x - center.0).abs() <= WINDOW_RADIUS && (y - center.1).abs() <= WINDOW_RADIUS;
+        let edited = &self.edited;
+        self.chunks.retain(|&chunk, _| near(chunk) || edited.contains(&chunk));
+        (after.0 - before.0, after.1 - before.1)
+    }
+
+    // The cells of the chunks around the center, generating any not seen before
+    pub fn window(&mut self) -> Map {
+        let size = WINDOW_CHUNKS * CHUNK_SIZE;
+        let mut window = Map::filled(size, size, b'0');
+        for chunk_y in 0..WINDOW_CHUNKS {
+            for chunk_x in 0..WINDOW_CHUNKS {
+                let chunk = (
+                    self.center.0 - WINDOW_RADIUS + chunk_x as i64,
+                    self.center.1 - WINDOW_RADIUS + chunk_y as i64,
+                );
+                let seed = self.seed;
+                let cells = self.chunks.entry(chunk).or_insert_with(|| generate_chunk(seed, chunk));
+                for (index, &cell) in cells.iter().enumerate() {
+                    let x = chunk_x * CHUNK_SIZE + index % CHUNK_SIZE;
+                    let y = chunk_y * CHUNK_SIZE + index / CHUNK_SIZE;
+                    window.set(x, y, cell);
+                }
+            }
+        }
+        window
+    }
+
+    // Copy a window's cells back into its chunks, so changes made during play
+    // (crumbled walls, stopped push-walls, editor changes) outlast the window
+    pub fn store(&mut self, window: &Map) {
+        for ((x, y), cell) in window.cells() {
+            let chunk = (
+                self.center.0 - WINDOW_RADIUS + (x / CHUNK_SIZE) as i64,
+                self.center.1 - WINDOW_RADIUS + (y / CHUNK_SIZE) as i64,
+            );
+            let Some(cells) = self.chunks.get_mut(&chunk) else {
+                continue;
+            };
+            let index = (y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE;
+            if cells[index] != cell {
+                cells[index] = cell;
+                self.edited.insert(chunk);
+            }
+        }
+    }
+}
+
+// Build a chunk from the seed and its position alone, so it comes out the
+// same every time it is generated
+fn generate_chunk(seed: u64, (chunk_x, chunk_y): (i64, i64)) -> Vec<u8> {
+    let mixed = (chunk_x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (chunk_y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    let mut rng = Rng::new(seed ^ mixed);
+    let material = WALL_MATERIALS[rng.below(WALL_MATERIALS.len())];
+    let mut cells = vec![b'0'; CHUNK_SIZE * CHUNK_SIZE];
+
+    // The west and north walls, each with a gap two to four cells wide, or
+    // left out. The corner post always stands.
+    for along_x in [false, true] {
+        if rng.below(OPEN_EDGE_CHANCE) == 0 {
+            continue;
+        }
+        let width = 2 + rng.below(3);
+        let start = 1 + rng.below(CHUNK_SIZE - 1 - width);
+        for position in (0..CHUNK_SIZE).filter(|position| !(start..start + width).contains(position)) {
+            let index = if along_x { position } else { position * CHUNK_SIZE };
+            cells[index] = material;
+        }
+    }
+    cells[0] = material;
+
+    // Pillars on every other cell, two cells from the walls, can't cut any
+    // part of the room off
+    for y in (2..CHUNK_SIZE - 1).step_by(2) {
+        for x in (2..CHUNK_SIZE - 1).step_by(2) {
+            if rng.below(PILLAR_CHANCE) == 0 {
+                cells[y * CHUNK_SIZE + x] = material;
+            }
+        }
+    }
+    cells
+}